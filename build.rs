@@ -0,0 +1,59 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // for `nextmeet version --verbose`; a source tarball has no git history
+    // HEAD and the branch it points to move on commits and checkouts, not on every `git add`;
+    // only existing files are listed, cargo reruns on every build for a missing one
+    let head = std::fs::read_to_string(".git/HEAD").unwrap_or_default();
+    let watched = [".git/HEAD".to_string(), ".git/packed-refs".to_string()]
+        .into_iter()
+        .chain(
+            head.strip_prefix("ref: ")
+                .map(|branch| format!(".git/{}", branch.trim())),
+        );
+    for path in watched.filter(|path| std::path::Path::new(path).exists()) {
+        println!("cargo:rerun-if-changed={path}");
+    }
+    let commit = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=NEXTMEET_COMMIT={commit}");
+    println!(
+        "cargo:rustc-env=NEXTMEET_BUILD_DATE={}",
+        chrono::Utc::now().format("%Y-%m-%d %H:%M UTC")
+    );
+
+    // the C header for the ffi feature, regenerated whenever the bindings change; it goes to
+    // OUT_DIR since the sources may well be read-only
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        cbindgen::Builder::new()
+            .with_src(format!("{crate_dir}/src/ffi.rs"))
+            .with_language(cbindgen::Language::C)
+            .with_include_guard("NEXTMEET_H")
+            .with_parse_deps(false)
+            .generate()
+            .expect("Failed to generate the C header")
+            .write_to_file(format!("{out_dir}/nextmeet.h"));
+    }
+
+    // the gRPC service, with a bundled protoc so building needs nothing installed
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/nextmeet.proto");
+        std::env::set_var(
+            "PROTOC",
+            protoc_bin_vendored::protoc_bin_path().expect("No bundled protoc for this platform"),
+        );
+        tonic_build::compile_protos("proto/nextmeet.proto")
+            .expect("Failed to compile proto/nextmeet.proto");
+    }
+}
@@ -0,0 +1,4 @@
+#!/bin/sh
+# Alfred Script Filter for a "nm" keyword: Enter opens the meeting,
+# Cmd+Enter sets {var:action} to "copy" so the workflow can copy the link instead
+exec nextmeet --output alfred
@@ -0,0 +1,3 @@
+#!/bin/sh
+# Argos / Kargos plugin: copy to ~/.config/argos/nextmeet.1m.sh
+exec nextmeet --output argos --max-width 30
@@ -0,0 +1,3 @@
+#!/bin/sh
+# xbar / SwiftBar plugin: refreshes every minute, click an entry to join
+exec nextmeet --output xbar --max-width 30
@@ -0,0 +1,9 @@
+#!/bin/sh
+
+# @raycast.schemaVersion 1
+# @raycast.title Join Next Meeting
+# @raycast.mode silent
+# @raycast.packageName nextmeet
+
+link=$(nextmeet -m) || { echo "No meeting to join"; exit 1; }
+open "$link"
@@ -0,0 +1,10 @@
+#!/bin/sh
+
+# @raycast.schemaVersion 1
+# @raycast.title Next Meeting
+# @raycast.mode inline
+# @raycast.refreshTime 1m
+# @raycast.packageName nextmeet
+
+# Switch the mode to fullOutput to see all of today's meetings
+exec nextmeet --output raycast --max-width 40
@@ -0,0 +1,107 @@
+use super::store::Store;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+// Repeated invocations within this window are answered without calling Google
+const TTL_SECONDS: i64 = 60;
+
+static STALE: Mutex<Option<DateTime<Local>>> = Mutex::new(None);
+static BYPASS: AtomicBool = AtomicBool::new(false);
+
+pub fn store(from: NaiveDate, to: NaiveDate, response: &str) {
+    let Ok(response) = serde_json::from_str::<serde_json::Value>(response) else {
+        return;
+    };
+    let items = response["items"].as_array().cloned().unwrap_or_default();
+
+    // the cache is a convenience, failing to write it must not fail the command
+    if let Err(err) = Store::open().and_then(|mut store| store.save(from, to, &items, Local::now()))
+    {
+        eprintln!("Warning: could not update the local store: {}", err);
+    }
+}
+
+// --no-cache: always ask Google, the cache is still written for offline use
+pub fn bypass() {
+    BYPASS.store(true, Ordering::Relaxed);
+}
+
+pub fn clear() -> Result<(), Box<dyn std::error::Error>> {
+    Store::open()?.forget_fetches()
+}
+
+fn is_recent(fetched_at: DateTime<Local>, now: DateTime<Local>) -> bool {
+    now - fetched_at < Duration::seconds(TTL_SECONDS) && fetched_at <= now
+}
+
+// The stored events for the window, as a calendar API response, with when they were fetched
+fn cached(from: NaiveDate, to: NaiveDate) -> Option<(DateTime<Local>, String)> {
+    let store = Store::open().ok()?;
+    let fetched_at = store.fetched_at(from, to).ok()??;
+    let items = store.events(from, to).ok()?;
+    Some((
+        fetched_at,
+        serde_json::json!({ "items": items }).to_string(),
+    ))
+}
+
+// Whether a recent response may answer a fetch; watch polls on its own schedule and must
+// see a change however soon after its last poll it happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fetch {
+    Cached,
+    Fresh,
+}
+
+fn reusable(fetch: Fetch, fetched_at: DateTime<Local>, now: DateTime<Local>) -> bool {
+    fetch == Fetch::Cached && is_recent(fetched_at, now)
+}
+
+pub fn recent(from: NaiveDate, to: NaiveDate, fetch: Fetch) -> Option<String> {
+    if BYPASS.load(Ordering::Relaxed) {
+        return None;
+    }
+    cached(from, to)
+        .filter(|(fetched_at, _)| reusable(fetch, *fetched_at, Local::now()))
+        .map(|(_, response)| response)
+}
+
+// Returns the cached response and remembers that what follows is stale
+pub fn load(from: NaiveDate, to: NaiveDate) -> Option<String> {
+    let (fetched_at, response) = cached(from, to)?;
+    *STALE.lock().unwrap() = Some(fetched_at);
+    Some(response)
+}
+
+// When results were served from the cache, the time they were fetched
+pub fn stale() -> Option<DateTime<Local>> {
+    *STALE.lock().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_after_ttl() {
+        let fetched_at: DateTime<Local> = "2023-03-01T10:00:00+01:00".parse().unwrap();
+
+        assert!(is_recent(fetched_at, fetched_at + Duration::seconds(30)));
+        assert!(!is_recent(fetched_at, fetched_at + Duration::seconds(60)));
+        assert!(!is_recent(fetched_at, fetched_at - Duration::seconds(30)));
+    }
+
+    #[test]
+    fn fresh_fetches_skip_the_cache() {
+        let fetched_at: DateTime<Local> = "2023-03-01T10:00:00+01:00".parse().unwrap();
+        let poll = fetched_at + Duration::seconds(30);
+
+        assert!(reusable(Fetch::Cached, fetched_at, poll));
+        assert!(!reusable(Fetch::Fresh, fetched_at, poll));
+    }
+}
@@ -0,0 +1,890 @@
+use super::cache;
+use super::http;
+use super::meetings::Meeting;
+use super::settings::EventTemplate;
+use super::settings::Settings;
+use super::tokens::Tokens;
+use chrono::DateTime;
+use chrono::Local;
+use chrono::NaiveDate;
+use reqwest::Method;
+use serde_json::Value;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+static UNATTENDED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rsvp {
+    Accept,
+    Decline,
+    Tentative,
+}
+
+impl Rsvp {
+    pub fn status(&self) -> &'static str {
+        match self {
+            Rsvp::Accept => "accepted",
+            Rsvp::Decline => "declined",
+            Rsvp::Tentative => "tentative",
+        }
+    }
+}
+
+impl FromStr for Rsvp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "accept" => Ok(Rsvp::Accept),
+            "decline" => Ok(Rsvp::Decline),
+            "tentative" => Ok(Rsvp::Tentative),
+            _ => Err(format!(
+                "Unknown response: {s}, use accept, decline or tentative"
+            )),
+        }
+    }
+}
+
+// From the daemon and the like, where a browser login would hang: changes fail instead
+pub fn unattended() {
+    UNATTENDED.store(true, Ordering::Relaxed);
+}
+
+// Changes go to the first configured calendar, the account's own
+fn events_url(path: &str) -> Result<String, Box<dyn Error>> {
+    let calendar = Settings::load()?.calendars().remove(0);
+    Ok(format!(
+        "https://www.googleapis.com/calendar/v3/calendars/{}/events{}",
+        urlencoding::encode(&calendar),
+        path
+    ))
+}
+
+// Calendar links carry the event as `eid`: the id and the calendar, base64 encoded
+pub fn event_id_from_link(link: &str) -> Result<String, String> {
+    let url = reqwest::Url::parse(link).map_err(|_| format!("Not a link: {link}"))?;
+    let segments: Vec<String> = url
+        .path_segments()
+        .map(|segments| segments.map(str::to_owned).collect())
+        .unwrap_or_default();
+    let eid = url
+        .query_pairs()
+        .find(|(key, _)| key == "eid")
+        .map(|(_, eid)| eid.into_owned())
+        .or_else(|| {
+            segments
+                .windows(2)
+                .find(|pair| pair[0] == "event" || pair[0] == "eventedit")
+                .map(|pair| pair[1].clone())
+        })
+        .ok_or("No event in this link")?;
+
+    let mut encoded = eid.replace('-', "+").replace('_', "/");
+    while encoded.len() % 4 != 0 {
+        encoded.push('=');
+    }
+    let decoded = openssl::base64::decode_block(&encoded)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or("The event in this link is not readable")?;
+    decoded
+        .split_whitespace()
+        .next()
+        .map(str::to_owned)
+        .ok_or_else(|| "No event in this link".to_string())
+}
+
+fn event_path(id: &str) -> String {
+    format!("/{}", urlencoding::encode(id))
+}
+
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|value| value["error"]["message"].as_str().map(str::to_owned))
+        .unwrap_or_else(|| body.to_string())
+}
+
+async fn request(method: Method, url: &str, body: Option<&Value>) -> Result<Value, Box<dyn Error>> {
+    let tokens = if UNATTENDED.load(Ordering::Relaxed) {
+        Tokens::writable_unattended().await?
+    } else {
+        Tokens::writable().await?
+    };
+    send(&tokens, method, url, body).await
+}
+
+async fn send(
+    tokens: &Tokens,
+    method: Method,
+    url: &str,
+    body: Option<&Value>,
+) -> Result<Value, Box<dyn Error>> {
+    let mut request = http::client()
+        .request(method.clone(), url)
+        .bearer_auth(&tokens.access_token);
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    let text = response.text().await?;
+    if !status.is_success() {
+        return Err(format!("Calendar API: {}", error_message(&text)).into());
+    }
+
+    // whatever was cached no longer matches the calendar
+    if method != Method::GET {
+        let _ = cache::clear();
+    }
+    Ok(serde_json::from_str(&text).unwrap_or(Value::Null))
+}
+
+pub async fn event(id: &str) -> Result<Value, Box<dyn Error>> {
+    request(Method::GET, &events_url(&event_path(id))?, None).await
+}
+
+// Read-only and never logging in, for the watch daemon and the jobs schedule-reminders
+// leaves behind
+pub async fn event_unattended(id: &str) -> Result<Value, Box<dyn Error>> {
+    let tokens = Tokens::load()?.refresh().await?;
+    send(&tokens, Method::GET, &events_url(&event_path(id))?, None).await
+}
+
+// conferenceDataVersion=1 makes Google honour conferenceData in the body
+async fn patch_with(id: &str, patch: &Value, query: &str) -> Result<Meeting, Box<dyn Error>> {
+    let url = events_url(&format!(
+        "{}?conferenceDataVersion=1{}",
+        event_path(id),
+        query
+    ))?;
+    Ok(serde_json::from_value(
+        request(Method::PATCH, &url, Some(patch)).await?,
+    )?)
+}
+
+pub async fn patch(id: &str, patch: &Value) -> Result<Meeting, Box<dyn Error>> {
+    patch_with(id, patch, "").await
+}
+
+// For changes the organizer makes that guests see without an email about them
+pub async fn patch_silently(id: &str, patch: &Value) -> Result<Meeting, Box<dyn Error>> {
+    patch_with(id, patch, "&sendUpdates=none").await
+}
+
+// For changes the others get an email about: the organizer's edits, and answers to the
+// organizer, who otherwise isn't reliably told
+pub async fn update(id: &str, patch: &Value) -> Result<Meeting, Box<dyn Error>> {
+    patch_with(id, patch, "&sendUpdates=all").await
+}
+
+pub async fn delete(id: &str, notify: bool) -> Result<(), Box<dyn Error>> {
+    let updates = if notify { "all" } else { "none" };
+    let url = events_url(&format!("{}?sendUpdates={}", event_path(id), updates))?;
+    request(Method::DELETE, &url, None).await?;
+    Ok(())
+}
+
+const NOTE_SEPARATOR: &str = "\n\n---\n";
+
+pub fn append_description(event: &Value, text: &str) -> Value {
+    let description = match event["description"]
+        .as_str()
+        .filter(|d| !d.trim().is_empty())
+    {
+        Some(description) => format!("{}{}{}", description.trim_end(), NOTE_SEPARATOR, text),
+        None => text.to_string(),
+    };
+    serde_json::json!({ "description": description })
+}
+
+// Reminders are personal, so these only change my copy of the event
+pub fn reminders(offsets: &[chrono::Duration]) -> Value {
+    let overrides: Vec<_> = offsets
+        .iter()
+        .map(|offset| serde_json::json!({ "method": "popup", "minutes": offset.num_minutes() }))
+        .collect();
+    serde_json::json!({ "reminders": { "useDefault": false, "overrides": overrides } })
+}
+
+// Existing guests are sent back as they are, with their responses
+pub fn add_attendees(event: &Value, emails: &[String]) -> Value {
+    let mut attendees = event["attendees"].as_array().cloned().unwrap_or_default();
+    for email in emails {
+        let invited = attendees
+            .iter()
+            .any(|attendee| attendee["email"].as_str() == Some(email.as_str()));
+        if !invited {
+            attendees.push(serde_json::json!({ "email": email }));
+        }
+    }
+    serde_json::json!({ "attendees": attendees })
+}
+
+pub fn organized(event: &Value) -> Result<(), String> {
+    match event["organizer"]["self"].as_bool() {
+        Some(true) => Ok(()),
+        _ => Err("Only the organizer can change this meeting".to_string()),
+    }
+}
+
+// Moves the start and end separately, keeping any time zone the event was created in
+pub fn shift(
+    event: &Value,
+    start_offset: chrono::Duration,
+    end_offset: chrono::Duration,
+) -> Result<Value, Box<dyn Error>> {
+    let new_start = event_time(event, "start")? + start_offset;
+    let new_end = event_time(event, "end")? + end_offset;
+    if new_start >= new_end {
+        return Err("The meeting would end before it starts".into());
+    }
+
+    let mut start = event["start"].clone();
+    let mut end = event["end"].clone();
+    start["dateTime"] = new_start.to_rfc3339().into();
+    end["dateTime"] = new_end.to_rfc3339().into();
+    Ok(serde_json::json!({ "start": start, "end": end }))
+}
+
+pub async fn insert(event: &Value) -> Result<Meeting, Box<dyn Error>> {
+    // invitees hear about it from Google
+    let url = events_url("?conferenceDataVersion=1&sendUpdates=all")?;
+    Ok(serde_json::from_value(
+        request(Method::POST, &url, Some(event)).await?,
+    )?)
+}
+
+// Asks Google to attach a new Meet link to the event
+pub fn conference() -> Value {
+    let request_id = format!(
+        "nextmeet-{}-{}",
+        std::process::id(),
+        Local::now().timestamp_nanos()
+    );
+    serde_json::json!({
+        "createRequest": {
+            "requestId": request_id,
+            "conferenceSolutionKey": { "type": "hangoutsMeet" }
+        }
+    })
+}
+
+pub fn new_event(
+    title: &str,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    attendees: &[String],
+) -> Value {
+    serde_json::json!({
+        "summary": title,
+        "start": { "dateTime": start.to_rfc3339() },
+        "end": { "dateTime": end.to_rfc3339() },
+        "attendees": attendees
+            .iter()
+            .map(|email| serde_json::json!({ "email": email }))
+            .collect::<Vec<_>>(),
+        "conferenceData": conference(),
+    })
+}
+
+pub fn from_template(
+    template: &EventTemplate,
+    title: &str,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Result<Value, String> {
+    let mut event = new_event(title, start, end, &template.attendees);
+    if let Some(description) = &template.description {
+        event["description"] = description.as_str().into();
+    }
+    match template.conference.as_deref() {
+        None | Some("meet") => (),
+        Some("none") => {
+            event.as_object_mut().unwrap().remove("conferenceData");
+        }
+        Some(other) => {
+            return Err(format!(
+                "Unknown conference type: {other}, use meet or none"
+            ))
+        }
+    }
+    Ok(event)
+}
+
+// Google only allows focus time on work accounts; auto-decline covers invitations that
+// arrive later, existing ones are left alone
+pub fn focus_event(
+    title: &str,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    auto_decline: bool,
+) -> Value {
+    let mode = if auto_decline {
+        "declineOnlyNewConflictingInvitations"
+    } else {
+        "declineNone"
+    };
+    serde_json::json!({
+        "summary": title,
+        "eventType": "focusTime",
+        "transparency": "opaque",
+        "start": { "dateTime": start.to_rfc3339() },
+        "end": { "dateTime": end.to_rfc3339() },
+        "focusTimeProperties": {
+            "autoDeclineMode": mode,
+            "chatStatus": "doNotDisturb"
+        },
+    })
+}
+
+// Out of office always declines what lands on it, including invitations already there
+pub fn ooo_event(title: &str, start: DateTime<Local>, end: DateTime<Local>) -> Value {
+    serde_json::json!({
+        "summary": title,
+        "eventType": "outOfOffice",
+        "transparency": "opaque",
+        "start": { "dateTime": start.to_rfc3339() },
+        "end": { "dateTime": end.to_rfc3339() },
+        "outOfOfficeProperties": {
+            "autoDeclineMode": "declineAllConflictingInvitations",
+            "declineMessage": "I'm out of office"
+        },
+    })
+}
+
+// "home" and "office" are kinds Google knows, anything else is a custom place
+pub fn working_location(location: &str) -> Value {
+    let (summary, properties) = match location {
+        "home" => (
+            "Home",
+            serde_json::json!({ "type": "homeOffice", "homeOffice": {} }),
+        ),
+        "office" => (
+            "Office",
+            serde_json::json!({ "type": "officeLocation", "officeLocation": {} }),
+        ),
+        label => (
+            label,
+            serde_json::json!({ "type": "customLocation", "customLocation": { "label": label } }),
+        ),
+    };
+    serde_json::json!({
+        "summary": summary,
+        "workingLocationProperties": properties,
+    })
+}
+
+pub fn location_event(location: &str, day: NaiveDate) -> Value {
+    let mut event = working_location(location);
+    event["eventType"] = "workingLocation".into();
+    event["visibility"] = "public".into();
+    event["transparency"] = "transparent".into();
+    event["start"] = serde_json::json!({ "date": day.to_string() });
+    event["end"] = serde_json::json!({ "date": (day + chrono::Days::new(1)).to_string() });
+    event
+}
+
+// Google parses the text itself, the Meet link is added afterwards
+pub async fn quick_add(text: &str) -> Result<Meeting, Box<dyn Error>> {
+    let url = events_url(&format!("/quickAdd?text={}", urlencoding::encode(text)))?;
+    let created = request(Method::POST, &url, None).await?;
+    let id = created["id"]
+        .as_str()
+        .ok_or("Google did not return the event")?;
+    patch(id, &serde_json::json!({ "conferenceData": conference() })).await
+}
+
+// Attendees are replaced as a whole by a patch, so everyone else is sent back untouched
+pub fn respond(event: &Value, rsvp: Rsvp, comment: Option<&str>) -> Result<Value, String> {
+    let mut attendees = event["attendees"]
+        .as_array()
+        .cloned()
+        .ok_or("You are not invited to this event")?;
+    let me = attendees
+        .iter_mut()
+        .find(|attendee| attendee["self"].as_bool() == Some(true))
+        .ok_or("You are not invited to this event")?;
+
+    me["responseStatus"] = rsvp.status().into();
+    if let Some(comment) = comment {
+        me["comment"] = comment.into();
+    }
+    Ok(serde_json::json!({ "attendees": attendees }))
+}
+
+pub async fn rsvp(id: &str, rsvp: Rsvp, comment: Option<&str>) -> Result<Meeting, Box<dyn Error>> {
+    let event = event(id).await?;
+    update(id, &respond(&event, rsvp, comment)?).await
+}
+
+// Instances that far ahead are declined, an endless series has no last one
+const SERIES_HORIZON_DAYS: i64 = 365;
+
+pub fn series_id(event: &Value) -> Option<&str> {
+    event["recurringEventId"].as_str().or_else(|| {
+        event["recurrence"]
+            .is_array()
+            .then(|| event["id"].as_str())?
+    })
+}
+
+async fn instances(series: &str, now: DateTime<Local>) -> Result<Vec<Value>, Box<dyn Error>> {
+    let mut instances = vec![];
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut path = format!(
+            "{}/instances?timeMin={}&timeMax={}",
+            event_path(series),
+            urlencoding::encode(&now.to_rfc3339()),
+            urlencoding::encode(&(now + chrono::Duration::days(SERIES_HORIZON_DAYS)).to_rfc3339())
+        );
+        if let Some(token) = &page_token {
+            path += &format!("&pageToken={}", urlencoding::encode(token));
+        }
+
+        let page = request(Method::GET, &events_url(&path)?, None).await?;
+        instances.extend(page["items"].as_array().cloned().unwrap_or_default());
+        match page["nextPageToken"].as_str() {
+            Some(token) => page_token = Some(token.to_string()),
+            None => return Ok(instances),
+        }
+    }
+}
+
+fn declined(event: &Value) -> bool {
+    event["attendees"].as_array().is_some_and(|attendees| {
+        attendees.iter().any(|attendee| {
+            attendee["self"].as_bool() == Some(true)
+                && attendee["responseStatus"] == Rsvp::Decline.status()
+        })
+    })
+}
+
+// Declines every upcoming instance of the series the event belongs to
+pub async fn decline_series(id: &str, comment: Option<&str>) -> Result<usize, Box<dyn Error>> {
+    let event = event(id).await?;
+    let series = series_id(&event).ok_or("The event is not part of a recurring series")?;
+
+    let mut count = 0;
+    for instance in instances(series, Local::now()).await? {
+        if declined(&instance) {
+            continue;
+        }
+        let instance_id = instance["id"].as_str().ok_or("An instance has no id")?;
+        update(instance_id, &respond(&instance, Rsvp::Decline, comment)?).await?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// Same title, guests and kind of conference, shifted by offset; the copy gets its own link
+pub fn followup_event(event: &Value, offset: chrono::Duration) -> Result<Value, Box<dyn Error>> {
+    let start = later_event_time(event, "start", offset)?;
+    let end = later_event_time(event, "end", offset)?;
+    let attendees: Vec<String> = event["attendees"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|attendee| attendee["self"].as_bool() != Some(true))
+        .filter_map(|attendee| attendee["email"].as_str().map(str::to_owned))
+        .collect();
+
+    let mut followup = new_event(
+        event["summary"].as_str().unwrap_or("Follow-up"),
+        start,
+        end,
+        &attendees,
+    );
+    if event["conferenceData"].is_null() {
+        followup.as_object_mut().unwrap().remove("conferenceData");
+    }
+    for field in ["start", "end"] {
+        if let Some(zone) = event[field]["timeZone"].as_str() {
+            followup[field]["timeZone"] = zone.into();
+        }
+    }
+    Ok(followup)
+}
+
+// Google has no API for proposing a new time, so the proposal travels in the RSVP comment
+pub fn proposal(start: DateTime<Local>, end: DateTime<Local>) -> String {
+    format!(
+        "Could we move this to {}-{}?",
+        start.format("%a %d/%m %H:%M"),
+        end.format("%H:%M")
+    )
+}
+
+fn event_time(event: &Value, field: &str) -> Result<DateTime<Local>, Box<dyn Error>> {
+    let time = event[field]["dateTime"]
+        .as_str()
+        .ok_or(format!("The event has no {field} time"))?;
+    Ok(time.parse()?)
+}
+
+// Wall-clock time in the event's own time zone, the local one without it, so a week later is
+// the same hour even across a DST change
+fn later_event_time(
+    event: &Value,
+    field: &str,
+    offset: chrono::Duration,
+) -> Result<DateTime<Local>, Box<dyn Error>> {
+    let time = event_time(event, field)?;
+    let invalid = || format!("No such time in the event's time zone: {}", time + offset);
+    let later = match event[field]["timeZone"].as_str() {
+        Some(zone) => {
+            let zone: chrono_tz::Tz = zone.parse()?;
+            let naive = time.with_timezone(&zone).naive_local() + offset;
+            naive
+                .and_local_timezone(zone)
+                .earliest()
+                .ok_or_else(invalid)?
+                .with_timezone(&Local)
+        }
+        None => (time.naive_local() + offset)
+            .and_local_timezone(Local)
+            .earliest()
+            .ok_or_else(invalid)?,
+    };
+    Ok(later)
+}
+
+pub async fn propose(
+    id: &str,
+    start: DateTime<Local>,
+    rsvp: Rsvp,
+) -> Result<String, Box<dyn Error>> {
+    let event = event(id).await?;
+    let length = event_time(&event, "end")? - event_time(&event, "start")?;
+    let comment = proposal(start, start + length);
+    update(id, &respond(&event, rsvp, Some(&comment))?).await?;
+    Ok(comment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rsvp() {
+        assert_eq!("accept".parse(), Ok(Rsvp::Accept));
+        assert_eq!("tentative".parse(), Ok(Rsvp::Tentative));
+        assert!("maybe".parse::<Rsvp>().is_err());
+    }
+
+    #[test]
+    fn responds_for_self_only() {
+        let event = serde_json::json!({
+            "attendees": [
+                { "email": "boss@example.org", "responseStatus": "accepted", "organizer": true },
+                { "email": "me@example.org", "responseStatus": "needsAction", "self": true }
+            ]
+        });
+
+        let patch = respond(&event, Rsvp::Decline, Some("On holiday")).unwrap();
+        assert_eq!(
+            patch,
+            serde_json::json!({
+                "attendees": [
+                    { "email": "boss@example.org", "responseStatus": "accepted", "organizer": true },
+                    {
+                        "email": "me@example.org",
+                        "responseStatus": "declined",
+                        "self": true,
+                        "comment": "On holiday"
+                    }
+                ]
+            })
+        );
+
+        assert!(respond(&serde_json::json!({}), Rsvp::Accept, None).is_err());
+    }
+
+    #[test]
+    fn builds_events() {
+        let start: DateTime<Local> = "2023-03-01T15:00:00+01:00".parse().unwrap();
+        let event = new_event(
+            "Coffee",
+            start,
+            start + chrono::Duration::minutes(30),
+            &["ana@example.org".to_string()],
+        );
+
+        assert_eq!(event["summary"], "Coffee");
+        assert_eq!(event["attendees"][0]["email"], "ana@example.org");
+        assert_eq!(
+            event["conferenceData"]["createRequest"]["conferenceSolutionKey"]["type"],
+            "hangoutsMeet"
+        );
+        assert_eq!(
+            event["start"]["dateTime"]
+                .as_str()
+                .unwrap()
+                .parse::<DateTime<Local>>(),
+            Ok(start)
+        );
+    }
+
+    #[test]
+    fn resolves_series() {
+        let instance = serde_json::json!({ "id": "abc_20230301", "recurringEventId": "abc" });
+        assert_eq!(series_id(&instance), Some("abc"));
+
+        let master = serde_json::json!({ "id": "abc", "recurrence": ["RRULE:FREQ=WEEKLY"] });
+        assert_eq!(series_id(&master), Some("abc"));
+
+        assert_eq!(series_id(&serde_json::json!({ "id": "single" })), None);
+    }
+
+    #[test]
+    fn proposes_in_comment() {
+        let start: DateTime<Local> = "2023-03-02T14:00:00+01:00".parse().unwrap();
+        let comment = proposal(start, start + chrono::Duration::minutes(45));
+        assert!(comment.starts_with("Could we move this to "));
+        assert!(comment.ends_with('?'));
+    }
+
+    #[test]
+    fn builds_focus_time() {
+        let start: DateTime<Local> = "2023-03-01T14:00:00+01:00".parse().unwrap();
+        let end = start + chrono::Duration::hours(2);
+
+        let event = focus_event("Focus time", start, end, true);
+        assert_eq!(event["eventType"], "focusTime");
+        assert_eq!(
+            event["focusTimeProperties"]["autoDeclineMode"],
+            "declineOnlyNewConflictingInvitations"
+        );
+        let event = focus_event("Focus time", start, end, false);
+        assert_eq!(
+            event["focusTimeProperties"]["autoDeclineMode"],
+            "declineNone"
+        );
+    }
+
+    #[test]
+    fn reads_event_links() {
+        let id = "7kqpk1ol2ah0p5tbmc3prhcnqd_20230301T090000Z";
+        let eid = "N2txcGsxb2wyYWgwcDV0Ym1jM3ByaGNucWRfMjAyMzAzMDFUMDkwMDAwWiBhbmFAZXhhbXBsZS5vcmc";
+        assert_eq!(
+            event_id_from_link(&format!(
+                "https://calendar.google.com/calendar/event?eid={eid}"
+            )),
+            Ok(id.to_string())
+        );
+        assert_eq!(
+            event_id_from_link(&format!(
+                "https://calendar.google.com/calendar/u/0/r/eventedit/{eid}"
+            )),
+            Ok(id.to_string())
+        );
+        assert!(event_id_from_link("https://calendar.google.com/calendar/u/0/r").is_err());
+        assert!(event_id_from_link("not a link").is_err());
+    }
+
+    #[test]
+    fn builds_events_from_templates() {
+        let template: EventTemplate = serde_json::from_value(serde_json::json!({
+            "title": "Retro",
+            "duration": "1h",
+            "attendees": ["team@example.org"],
+            "description": "What went well?",
+            "conference": "none"
+        }))
+        .unwrap();
+        let start = "2023-03-01T10:00:00+01:00".parse().unwrap();
+        let end = "2023-03-01T11:00:00+01:00".parse().unwrap();
+        let event = from_template(&template, "Retro", start, end).unwrap();
+        assert_eq!(event["attendees"][0]["email"], "team@example.org");
+        assert_eq!(event["description"], "What went well?");
+        assert!(event.get("conferenceData").is_none());
+
+        let template = EventTemplate {
+            conference: Some("zoom".to_string()),
+            ..Default::default()
+        };
+        assert!(from_template(&template, "Retro", start, end).is_err());
+    }
+
+    #[test]
+    fn builds_location_events() {
+        let day = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let event = location_event("home", day);
+        assert_eq!(event["eventType"], "workingLocation");
+        assert_eq!(event["workingLocationProperties"]["type"], "homeOffice");
+        assert_eq!(event["start"]["date"], "2023-03-01");
+        assert_eq!(event["end"]["date"], "2023-03-02");
+
+        let event = working_location("Café Roma");
+        assert_eq!(
+            event["workingLocationProperties"]["customLocation"]["label"],
+            "Café Roma"
+        );
+    }
+
+    #[test]
+    fn builds_ooo_events() {
+        let start = "2023-03-01T00:00:00+01:00".parse().unwrap();
+        let end = "2023-03-03T00:00:00+01:00".parse().unwrap();
+        let event = ooo_event("Out of office", start, end);
+        assert_eq!(event["eventType"], "outOfOffice");
+        assert_eq!(
+            event["outOfOfficeProperties"]["autoDeclineMode"],
+            "declineAllConflictingInvitations"
+        );
+    }
+
+    #[test]
+    fn clones_followups() {
+        let event = serde_json::json!({
+            "summary": "Planning",
+            "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+            "end": { "dateTime": "2023-03-01T10:30:00+01:00" },
+            "attendees": [
+                { "email": "me@example.org", "self": true, "responseStatus": "accepted" },
+                { "email": "ana@example.org", "responseStatus": "accepted" }
+            ],
+            "conferenceData": { "conferenceId": "abc-defg-hij" }
+        });
+
+        let followup = followup_event(&event, chrono::Duration::weeks(1)).unwrap();
+        assert_eq!(followup["summary"], "Planning");
+        assert_eq!(
+            followup["attendees"],
+            serde_json::json!([{ "email": "ana@example.org" }])
+        );
+        assert_eq!(
+            event_time(&followup, "start").unwrap(),
+            "2023-03-08T10:00:00+01:00"
+                .parse::<DateTime<Local>>()
+                .unwrap()
+        );
+        assert!(followup["conferenceData"]["createRequest"].is_object());
+
+        let mut event = event;
+        event.as_object_mut().unwrap().remove("conferenceData");
+        let followup = followup_event(&event, chrono::Duration::weeks(1)).unwrap();
+        assert!(followup.get("conferenceData").is_none());
+
+        // Rome moves its clocks on 26 March, the follow-up is still at 10:00 there
+        let event = serde_json::json!({
+            "start": { "dateTime": "2023-03-20T10:00:00+01:00", "timeZone": "Europe/Rome" },
+            "end": { "dateTime": "2023-03-20T10:30:00+01:00", "timeZone": "Europe/Rome" }
+        });
+        let followup = followup_event(&event, chrono::Duration::weeks(1)).unwrap();
+        assert_eq!(
+            event_time(&followup, "end").unwrap(),
+            "2023-03-27T10:30:00+02:00"
+                .parse::<DateTime<Local>>()
+                .unwrap()
+        );
+        assert_eq!(followup["start"]["timeZone"], "Europe/Rome");
+    }
+
+    #[test]
+    fn shifts_events() {
+        let event = serde_json::json!({
+            "organizer": { "email": "me@example.org", "self": true },
+            "start": { "dateTime": "2023-03-01T10:00:00+01:00", "timeZone": "Europe/Rome" },
+            "end": { "dateTime": "2023-03-01T10:30:00+01:00", "timeZone": "Europe/Rome" }
+        });
+        assert_eq!(organized(&event), Ok(()));
+
+        let moved = shift(
+            &event,
+            chrono::Duration::minutes(15),
+            chrono::Duration::minutes(15),
+        )
+        .unwrap();
+        assert_eq!(moved["start"]["timeZone"], "Europe/Rome");
+        assert_eq!(
+            event_time(&moved, "start").unwrap(),
+            event_time(&event, "start").unwrap() + chrono::Duration::minutes(15)
+        );
+
+        let extended = shift(
+            &event,
+            chrono::Duration::zero(),
+            chrono::Duration::minutes(15),
+        )
+        .unwrap();
+        assert_eq!(
+            event_time(&extended, "end").unwrap() - event_time(&extended, "start").unwrap(),
+            chrono::Duration::minutes(45)
+        );
+
+        assert!(shift(
+            &event,
+            chrono::Duration::zero(),
+            chrono::Duration::minutes(-30)
+        )
+        .is_err());
+        assert!(
+            organized(&serde_json::json!({ "organizer": { "email": "boss@example.org" } }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn appends_to_description() {
+        let event = serde_json::json!({ "description": "Weekly sync\n" });
+        assert_eq!(
+            append_description(&event, "agenda: budget"),
+            serde_json::json!({ "description": "Weekly sync\n\n---\nagenda: budget" })
+        );
+        assert_eq!(
+            append_description(&serde_json::json!({}), "agenda: budget"),
+            serde_json::json!({ "description": "agenda: budget" })
+        );
+    }
+
+    #[test]
+    fn overrides_reminders() {
+        assert_eq!(
+            reminders(&[chrono::Duration::minutes(10), chrono::Duration::hours(1)]),
+            serde_json::json!({
+                "reminders": {
+                    "useDefault": false,
+                    "overrides": [
+                        { "method": "popup", "minutes": 10 },
+                        { "method": "popup", "minutes": 60 }
+                    ]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn adds_attendees_once() {
+        let event = serde_json::json!({
+            "attendees": [{ "email": "ana@example.org", "responseStatus": "accepted" }]
+        });
+        let patch = add_attendees(
+            &event,
+            &["ana@example.org".to_string(), "bo@example.org".to_string()],
+        );
+        assert_eq!(
+            patch,
+            serde_json::json!({
+                "attendees": [
+                    { "email": "ana@example.org", "responseStatus": "accepted" },
+                    { "email": "bo@example.org" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn extracts_api_errors() {
+        assert_eq!(
+            error_message(r#"{ "error": { "code": 404, "message": "Not Found" } }"#),
+            "Not Found"
+        );
+        assert_eq!(error_message("Bad Gateway"), "Bad Gateway");
+    }
+}
@@ -0,0 +1,144 @@
+use super::meetings;
+use super::meetings::Meeting;
+use super::meetings::Ongoing;
+use super::tokens::Tokens;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use std::error::Error;
+use std::ops::RangeInclusive;
+
+// Where an embedding program keeps its tokens: a keyring, a database, a secret store
+pub trait TokenStore {
+    fn load(&self) -> Result<Tokens, Box<dyn Error>>;
+
+    fn save(&self, tokens: &Tokens) -> Result<(), Box<dyn Error>>;
+}
+
+// ~/.nextmeet, shared with the nextmeet binary
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileStore;
+
+impl TokenStore for FileStore {
+    fn load(&self) -> Result<Tokens, Box<dyn Error>> {
+        Tokens::load()
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<(), Box<dyn Error>> {
+        tokens.save()
+    }
+}
+
+// Access tokens this close to expiring are renewed before being used
+const EXPIRY_MARGIN_SECONDS: i64 = 60;
+
+fn fresh(tokens: &Tokens, now: DateTime<Local>) -> bool {
+    tokens
+        .expires_at
+        .is_some_and(|expires_at| expires_at - now >= Duration::seconds(EXPIRY_MARGIN_SECONDS))
+}
+
+// The calendar for other programs: never opens a browser to log in, so the tokens have to
+// come from a previous `nextmeet` run or from the store. It keeps its own options, none of
+// the binary's command line switches affect it
+pub struct Client {
+    store: Box<dyn TokenStore>,
+    ongoing: Ongoing,
+}
+
+impl Default for Client {
+    fn default() -> Client {
+        Client::with_store(FileStore)
+    }
+}
+
+impl Client {
+    pub fn new() -> Client {
+        Client::default()
+    }
+
+    pub fn with_store(store: impl TokenStore + 'static) -> Client {
+        Client {
+            store: Box::new(store),
+            ongoing: Ongoing::default(),
+        }
+    }
+
+    // What next_meeting does about a meeting already in progress, Nearest by default
+    pub fn with_ongoing(mut self, ongoing: Ongoing) -> Client {
+        self.ongoing = ongoing;
+        self
+    }
+
+    async fn tokens(&self) -> Result<Tokens, Box<dyn Error>> {
+        let tokens = self
+            .store
+            .load()
+            .map_err(|err| format!("Not logged in, run nextmeet once to log in: {err}"))?;
+        if fresh(&tokens, Local::now()) {
+            return Ok(tokens);
+        }
+
+        let tokens = tokens.renew().await?;
+        self.store.save(&tokens)?;
+        Ok(tokens)
+    }
+
+    async fn meetings(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<Meeting>, Box<dyn Error>> {
+        let tokens = self.tokens().await?;
+        meetings::fetch_range(&tokens, from, to).await
+    }
+
+    // Accepted meetings with a link on the days in the range, e.g. `today..=today`
+    pub async fn agenda(
+        &self,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Result<Vec<Meeting>, Box<dyn Error>> {
+        Ok(meetings::agenda(
+            self.meetings(*range.start(), *range.end()).await?,
+        ))
+    }
+
+    // What `nextmeet` prints: the ongoing or upcoming meeting, or being out of office
+    pub async fn next_meeting(&self) -> Result<Option<Meeting>, Box<dyn Error>> {
+        let now = Local::now();
+        let today = now.date_naive();
+        let meetings = self.meetings(today, today).await?;
+        Ok(meetings::out_of_office(&meetings, now)
+            .or_else(|| meetings::select(&meetings, now, self.ongoing))
+            .cloned())
+    }
+
+    pub async fn join_link(&self) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self
+            .next_meeting()
+            .await?
+            .and_then(|meeting| meeting.get_link()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_tokens_until_they_expire() {
+        let now: DateTime<Local> = "2023-03-01T10:00:00+01:00".parse().unwrap();
+        let tokens = |expires_at: Option<DateTime<Local>>| Tokens {
+            access_token: "access".to_string(),
+            refresh_token: Some("refresh".to_string()),
+            expires_at,
+            writable: false,
+        };
+
+        assert!(fresh(&tokens(Some(now + Duration::minutes(30))), now));
+        assert!(!fresh(&tokens(Some(now + Duration::seconds(10))), now));
+        assert!(!fresh(&tokens(Some(now - Duration::hours(1))), now));
+        assert!(!fresh(&tokens(None), now));
+    }
+}
@@ -0,0 +1,41 @@
+use super::duration;
+use chrono::DateTime;
+use chrono::Local;
+use std::sync::Mutex;
+
+static NOW: Mutex<Option<DateTime<Local>>> = Mutex::new(None);
+
+// --now: answer as if it were this moment, for demos, tests and "what's next at 3pm?"
+pub fn set(now: DateTime<Local>) {
+    *NOW.lock().unwrap() = Some(now);
+}
+
+// The moment queries are answered for; waiting, caching and creating events keep using
+// the real clock
+pub fn now() -> DateTime<Local> {
+    NOW.lock().unwrap().unwrap_or_else(Local::now)
+}
+
+// A full RFC 3339 timestamp, or anything --at takes such as "15:00" or "tomorrow 9:00"
+pub fn parse(text: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    match DateTime::parse_from_rfc3339(text) {
+        Ok(at) => Ok(at.with_timezone(&Local)),
+        Err(_) => duration::parse_at(text, now),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_now() {
+        let now: DateTime<Local> = "2024-06-01T09:00:00+02:00".parse().unwrap();
+        assert_eq!(parse("2024-06-01T09:00:00+02:00", Local::now()), Ok(now));
+        assert_eq!(
+            parse("15:00", now).map(|at| at.date_naive()),
+            Ok(now.date_naive())
+        );
+        assert!(parse("teatime", now).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use super::duration::format_clock;
+use super::meetings;
+use super::meetings::next_meeting;
+use super::meetings::ongoing_meeting;
+use super::meetings::Meeting;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use crossterm::terminal;
+use std::error::Error;
+use std::io::Write;
+
+const REFRESH_MINUTES: i64 = 5;
+
+pub fn line(meetings: &[Meeting], now: DateTime<Local>) -> String {
+    if let Some(meeting) = ongoing_meeting(meetings, now) {
+        let left = meeting
+            .end()
+            .map(|end| end - now)
+            .unwrap_or(Duration::zero());
+        return format!("{} ends in {}", meeting.summary(), format_clock(left));
+    }
+    match next_meeting(meetings, now) {
+        Some(meeting) => {
+            let until = meeting
+                .start()
+                .map(|start| start - now)
+                .unwrap_or(Duration::zero());
+            format!("{} in {}", meeting.summary(), format_clock(until))
+        }
+        None => "No more meetings".to_string(),
+    }
+}
+
+pub async fn run() -> Result<(), Box<dyn Error>> {
+    let mut stdout = std::io::stdout();
+    let mut meetings = vec![];
+    let mut loaded_at: Option<DateTime<Local>> = None;
+
+    loop {
+        let now = Local::now();
+        let due = loaded_at
+            .map(|at| {
+                now - at >= Duration::minutes(REFRESH_MINUTES)
+                    || at.date_naive() != now.date_naive()
+            })
+            .unwrap_or(true);
+        if due {
+            let today = now.date_naive();
+            match meetings::retrieve_agenda(today, today).await {
+                Ok(fetched) => {
+                    meetings = fetched;
+                    loaded_at = Some(now);
+                }
+                // keep counting down on the agenda we have
+                Err(err) if loaded_at.is_some() => eprintln!("\rError: {}", err),
+                Err(err) => return Err(err),
+            }
+        }
+
+        crossterm::execute!(stdout, terminal::Clear(terminal::ClearType::CurrentLine))?;
+        write!(stdout, "\r{}", line(&meetings, now))?;
+        stdout.flush()?;
+
+        let wait = 1000 - now.timestamp_subsec_millis().min(999);
+        tokio::time::sleep(std::time::Duration::from_millis(wait.into())).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(summary: &str, start: &str, end: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "summary": summary,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+            "attendees": [{ "self": true, "responseStatus": "accepted" }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn counts_down() {
+        let meetings = [
+            meeting(
+                "First",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:30:00+01:00",
+            ),
+            meeting(
+                "Second",
+                "2023-03-01T11:00:00+01:00",
+                "2023-03-01T11:30:00+01:00",
+            ),
+        ];
+        let line = |now: &str| line(&meetings, now.parse().unwrap());
+
+        assert_eq!(line("2023-03-01T10:52:18+01:00"), "Second in 07:42");
+        assert_eq!(line("2023-03-01T10:20:00+01:00"), "First ends in 10:00");
+        assert_eq!(line("2023-03-01T12:00:00+01:00"), "No more meetings");
+    }
+}
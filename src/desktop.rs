@@ -0,0 +1,177 @@
+use super::notifier::Notifier;
+use super::notifier::Reminder;
+use super::settings;
+use async_trait::async_trait;
+use std::error::Error;
+use std::time::Duration;
+
+const JOIN: &str = "join";
+const SNOOZE: &str = "snooze";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Join,
+    Snooze,
+    Dismissed,
+}
+
+fn snooze_label(snooze: Duration) -> String {
+    format!("Snooze {} min", snooze.as_secs() / 60)
+}
+
+#[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+fn action(name: &str) -> Action {
+    match name {
+        "default" | JOIN => Action::Join,
+        SNOOZE => Action::Snooze,
+        _ => Action::Dismissed,
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn show(reminder: &Reminder, snooze: Duration) -> Result<Action, Box<dyn Error>> {
+    let mut notification = notify_rust::Notification::new();
+    notification
+        .appname("nextmeet")
+        .summary(&reminder.title)
+        .body(&reminder.message)
+        .icon("x-office-calendar");
+
+    if reminder.urgent {
+        notification.urgency(notify_rust::Urgency::Critical);
+    }
+    if reminder.link.is_some() {
+        notification.action(JOIN, "Join");
+    }
+    notification.action(SNOOZE, &snooze_label(snooze));
+
+    let mut result = Action::Dismissed;
+    notification
+        .show()?
+        .wait_for_action(|name| result = action(name));
+    Ok(result)
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// A Notification Center banner: a dialog would be modal and steal the focus, while banners
+// from osascript can't carry buttons, so like toasts it can't be acted upon
+#[cfg(target_os = "macos")]
+pub fn show(reminder: &Reminder, _snooze: Duration) -> Result<Action, Box<dyn Error>> {
+    let script = format!(
+        "display notification {} with title \"nextmeet\" subtitle {}",
+        applescript_string(&reminder.message),
+        applescript_string(&reminder.title)
+    );
+    std::process::Command::new("osascript")
+        .arg("-e")
+        .arg(script)
+        .status()?;
+    Ok(Action::Dismissed)
+}
+
+// Toasts are fire-and-forget here: clicking them can't be routed back to the process
+#[cfg(windows)]
+pub fn show(reminder: &Reminder, _snooze: Duration) -> Result<Action, Box<dyn Error>> {
+    notify_rust::Notification::new()
+        .appname("nextmeet")
+        .summary(&reminder.title)
+        .body(&reminder.message)
+        .show()?;
+    Ok(Action::Dismissed)
+}
+
+// A plain notification without actions, for problems rather than meetings
+pub fn alert(title: &str, message: &str) -> Result<(), Box<dyn Error>> {
+    notify_rust::Notification::new()
+        .appname("nextmeet")
+        .summary(title)
+        .body(message)
+        .show()?;
+    Ok(())
+}
+
+// Blocks until the reminder is acted upon, showing it again after every snooze
+pub fn remind(reminder: &Reminder, snooze: Duration) -> Result<Action, Box<dyn Error>> {
+    loop {
+        match show(reminder, snooze)? {
+            Action::Snooze => std::thread::sleep(snooze),
+            Action::Join => {
+                if let Some(link) = &reminder.link {
+                    crate::open::open(link)?;
+                    crate::store::record_join(&reminder.meeting);
+                }
+                return Ok(Action::Join);
+            }
+            Action::Dismissed => return Ok(Action::Dismissed),
+        }
+    }
+}
+
+pub struct Desktop {
+    settings: settings::Desktop,
+}
+
+impl Desktop {
+    pub fn new(settings: settings::Desktop) -> Desktop {
+        Desktop { settings }
+    }
+
+    fn snooze(&self) -> Duration {
+        self.settings
+            .snooze
+            .as_deref()
+            .and_then(|snooze| crate::duration::parse(snooze).ok())
+            .and_then(|snooze| snooze.to_std().ok())
+            .unwrap_or(Duration::from_secs(5 * 60))
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for Desktop {
+    fn name(&self) -> &'static str {
+        "desktop"
+    }
+
+    fn before(&self) -> &[String] {
+        &self.settings.before
+    }
+
+    async fn send(&self, reminder: &Reminder) -> Result<(), Box<dyn Error>> {
+        if let Some(sound) = &self.settings.sound {
+            crate::sound::play(sound, reminder.minutes_before);
+        }
+
+        let reminder = reminder.clone();
+        let snooze = self.snooze();
+
+        // waiting for the user would stall the watch loop
+        std::thread::spawn(move || {
+            if let Err(err) = remind(&reminder, snooze) {
+                eprintln!("Error: desktop notification: {}", err);
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_actions() {
+        assert_eq!(action("join"), Action::Join);
+        assert_eq!(action("default"), Action::Join);
+        assert_eq!(action("snooze"), Action::Snooze);
+        assert_eq!(action("__closed"), Action::Dismissed);
+    }
+
+    #[test]
+    fn escapes_applescript_strings() {
+        assert_eq!(applescript_string(r#"say "hi""#), r#""say \"hi\"""#);
+    }
+}
@@ -0,0 +1,263 @@
+use super::meetings;
+use super::meetings::Meeting;
+use super::output::time_range;
+use chrono::DateTime;
+use chrono::Local;
+
+#[derive(Debug)]
+pub enum Change<'a> {
+    Added(&'a Meeting),
+    Cancelled(&'a Meeting),
+    Moved(&'a Meeting, &'a Meeting),
+    Relinked(&'a Meeting, &'a Meeting),
+    Renamed(&'a Meeting, &'a Meeting),
+}
+
+// The id alone, a renamed meeting is still the same meeting
+fn key(meeting: &Meeting) -> &str {
+    meeting.id().unwrap_or(meeting.summary())
+}
+
+pub fn diff<'a>(before: &'a [Meeting], after: &'a [Meeting]) -> Vec<Change<'a>> {
+    let mut changes = vec![];
+
+    for old in before {
+        match after.iter().find(|new| key(new) == key(old)) {
+            None => changes.push(Change::Cancelled(old)),
+            Some(new) => {
+                if old.start().ok() != new.start().ok() || old.end().ok() != new.end().ok() {
+                    changes.push(Change::Moved(old, new));
+                } else if old.summary() != new.summary() {
+                    changes.push(Change::Renamed(old, new));
+                } else if old.get_link() != new.get_link() {
+                    changes.push(Change::Relinked(old, new));
+                }
+            }
+        }
+    }
+    for new in after {
+        if !before.iter().any(|old| key(old) == key(new)) {
+            changes.push(Change::Added(new));
+        }
+    }
+
+    changes.sort_by_key(|change| match change {
+        Change::Added(meeting)
+        | Change::Cancelled(meeting)
+        | Change::Moved(_, meeting)
+        | Change::Relinked(_, meeting)
+        | Change::Renamed(_, meeting) => meeting.start().ok(),
+    });
+    changes
+}
+
+// What happened to the meeting that was about to start before the refresh, if anything;
+// meetings added in front of it are left to the usual reminders
+pub fn next_change<'a>(
+    before: &'a [Meeting],
+    after: &'a [Meeting],
+    now: DateTime<Local>,
+) -> Option<Change<'a>> {
+    let next = meetings::select(before, now, meetings::Ongoing::Skip)?;
+    diff(before, after).into_iter().find(|change| match change {
+        Change::Cancelled(old)
+        | Change::Moved(old, _)
+        | Change::Relinked(old, _)
+        | Change::Renamed(old, _) => key(old) == key(next),
+        Change::Added(_) => false,
+    })
+}
+
+pub fn render(changes: &[Change]) -> String {
+    changes
+        .iter()
+        .map(|change| match change {
+            Change::Added(meeting) => format!("+ {} {}", time_range(meeting), meeting.summary()),
+            Change::Cancelled(meeting) => {
+                format!("- {} {}", time_range(meeting), meeting.summary())
+            }
+            Change::Moved(old, new) => format!(
+                "~ {} → {} {}",
+                time_range(old),
+                time_range(new),
+                new.summary()
+            ),
+            Change::Relinked(_, new) => {
+                format!("@ {} {} (new link)", time_range(new), new.summary())
+            }
+            Change::Renamed(old, new) => format!(
+                "* {} {} (was {})",
+                time_range(new),
+                new.summary(),
+                old.summary()
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(id: &str, start: &str, end: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "summary": id,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reports_changes() {
+        let before = [
+            meeting(
+                "standup",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:15:00+01:00",
+            ),
+            meeting(
+                "retro",
+                "2023-03-01T15:00:00+01:00",
+                "2023-03-01T16:00:00+01:00",
+            ),
+            meeting(
+                "1:1",
+                "2023-03-01T17:00:00+01:00",
+                "2023-03-01T17:30:00+01:00",
+            ),
+        ];
+        let after = [
+            meeting(
+                "standup",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:15:00+01:00",
+            ),
+            meeting(
+                "review",
+                "2023-03-01T14:00:00+01:00",
+                "2023-03-01T14:30:00+01:00",
+            ),
+            meeting(
+                "retro",
+                "2023-03-01T16:00:00+01:00",
+                "2023-03-01T17:00:00+01:00",
+            ),
+        ];
+
+        let changes = diff(&before, &after);
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(changes[0], Change::Added(m) if m.summary() == "review"));
+        assert!(matches!(changes[1], Change::Moved(_, m) if m.summary() == "retro"));
+        assert!(matches!(changes[2], Change::Cancelled(m) if m.summary() == "1:1"));
+
+        let lines: Vec<_> = render(&changes).lines().map(|l| l.to_string()).collect();
+        assert!(lines[0].starts_with("+ ") && lines[0].ends_with(" review"));
+        assert!(lines[1].starts_with("~ ") && lines[1].contains(" → "));
+        assert!(lines[2].starts_with("- "));
+
+        assert!(diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn reports_renames() {
+        let before = [meeting(
+            "standup",
+            "2023-03-01T10:00:00+01:00",
+            "2023-03-01T10:15:00+01:00",
+        )];
+        let after = [serde_json::from_value(serde_json::json!({
+            "id": "standup",
+            "summary": "Daily",
+            "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+            "end": { "dateTime": "2023-03-01T10:15:00+01:00" }
+        }))
+        .unwrap()];
+
+        let changes = diff(&before, &after);
+        assert!(matches!(changes[..], [Change::Renamed(_, m)] if m.summary() == "Daily"));
+        assert!(render(&changes).ends_with("Daily (was standup)"));
+    }
+
+    #[test]
+    fn reports_changes_to_the_next_meeting() {
+        let now = "2023-03-01T09:00:00+01:00".parse().unwrap();
+        let accepted = |id: &str, start: &str, end: &str, link: &str| -> Meeting {
+            serde_json::from_value(serde_json::json!({
+                "id": id,
+                "summary": id,
+                "hangoutLink": link,
+                "start": { "dateTime": start },
+                "end": { "dateTime": end },
+                "attendees": [{ "self": true, "responseStatus": "accepted" }]
+            }))
+            .unwrap()
+        };
+        let standup = |start, end, link| accepted("standup", start, end, link);
+        let retro = accepted(
+            "retro",
+            "2023-03-01T15:00:00+01:00",
+            "2023-03-01T16:00:00+01:00",
+            "https://meet.google.com/ddd-eeee-fff",
+        );
+        let before = [
+            standup(
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:15:00+01:00",
+                "https://meet.google.com/aaa-bbbb-ccc",
+            ),
+            retro.clone(),
+        ];
+
+        let moved = [
+            standup(
+                "2023-03-01T10:30:00+01:00",
+                "2023-03-01T10:45:00+01:00",
+                "https://meet.google.com/aaa-bbbb-ccc",
+            ),
+            retro.clone(),
+        ];
+        assert!(matches!(
+            next_change(&before, &moved, now),
+            Some(Change::Moved(_, m)) if m.summary() == "standup"
+        ));
+
+        let relinked = [
+            standup(
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:15:00+01:00",
+                "https://meet.google.com/xxx-yyyy-zzz",
+            ),
+            retro.clone(),
+        ];
+        let change = next_change(&before, &relinked, now);
+        assert!(matches!(change, Some(Change::Relinked(..))));
+        assert!(render(&[change.unwrap()]).starts_with("@ "));
+
+        let renamed = [
+            serde_json::from_value(serde_json::json!({
+                "id": "standup",
+                "summary": "Daily",
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" },
+                "attendees": [{ "self": true, "responseStatus": "accepted" }]
+            }))
+            .unwrap(),
+            retro.clone(),
+        ];
+        let change = next_change(&before, &renamed, now);
+        assert!(matches!(change, Some(Change::Renamed(_, m)) if m.summary() == "Daily"));
+        assert!(render(&[change.unwrap()]).ends_with("Daily (was standup)"));
+
+        assert!(matches!(
+            next_change(&before, &[retro], now),
+            Some(Change::Cancelled(m)) if m.summary() == "standup"
+        ));
+
+        // only the next meeting counts, not the ones after it
+        assert!(next_change(&before, &before[..1], now).is_none());
+    }
+}
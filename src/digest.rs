@@ -0,0 +1,155 @@
+use super::meetings::order;
+use super::meetings::Meeting;
+use super::output::time_range;
+use super::slots::busy;
+use super::stats::hours;
+use chrono::Duration;
+use std::fmt::Display;
+
+// A few lines about the day, to show at login or hand to a hook
+pub struct Digest {
+    pub meetings: Vec<Meeting>,
+    pub total: Duration,
+    pub conflicts: Vec<(Meeting, Meeting)>,
+    pub missing_links: Vec<Meeting>,
+}
+
+fn overlap(a: &Meeting, b: &Meeting) -> bool {
+    match (a.start(), a.end(), b.start(), b.end()) {
+        (Ok(a_start), Ok(a_end), Ok(b_start), Ok(b_end)) => a_start < b_end && b_start < a_end,
+        _ => false,
+    }
+}
+
+// Accepted meetings with a time only, all-day events don't take up the day
+pub fn compute(meetings: &[Meeting]) -> Digest {
+    let mut meetings: Vec<Meeting> = meetings
+        .iter()
+        .filter(|meeting| meeting.accepted() && meeting.start().is_ok() && meeting.end().is_ok())
+        .cloned()
+        .collect();
+    meetings.sort_by(order);
+
+    let total = busy(&meetings)
+        .iter()
+        .fold(Duration::zero(), |total, (start, end)| {
+            total + (*end - *start)
+        });
+    let conflicts = meetings
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| {
+            meetings[i + 1..]
+                .iter()
+                .filter(|b| overlap(a, b))
+                .map(|b| (a.clone(), b.clone()))
+        })
+        .collect();
+    let missing_links = meetings
+        .iter()
+        .filter(|meeting| meeting.get_link().is_none())
+        .cloned()
+        .collect();
+
+    Digest {
+        meetings,
+        total,
+        conflicts,
+        missing_links,
+    }
+}
+
+impl Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (Some(first), Some(last)) = (self.meetings.first(), self.meetings.last()) else {
+            return write!(f, "No meetings");
+        };
+        let plural = if self.meetings.len() == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} meeting{}, {} in total",
+            self.meetings.len(),
+            plural,
+            hours(self.total)
+        )?;
+        write!(f, "\nFirst: {} {}", time_range(first), first.summary())?;
+        if self.meetings.len() > 1 {
+            write!(f, "\nLast: {} {}", time_range(last), last.summary())?;
+        }
+        for (a, b) in &self.conflicts {
+            write!(
+                f,
+                "\nConflict: {} {} and {} {}",
+                time_range(a),
+                a.summary(),
+                time_range(b),
+                b.summary()
+            )?;
+        }
+        for meeting in &self.missing_links {
+            write!(
+                f,
+                "\nNo link: {} {}",
+                time_range(meeting),
+                meeting.summary()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(summary: &str, start: &str, end: &str, link: Option<&str>) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "summary": summary,
+            "hangoutLink": link,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "attendees": [{ "self": true, "responseStatus": "accepted" }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn summarizes_day() {
+        let link = Some("https://meet.google.com/aaa-bbbb-ccc");
+        let meetings = [
+            meeting(
+                "Retro",
+                "2023-03-01T15:00:00+01:00",
+                "2023-03-01T16:00:00+01:00",
+                link,
+            ),
+            meeting(
+                "Standup",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:30:00+01:00",
+                link,
+            ),
+            meeting(
+                "Interview",
+                "2023-03-01T15:30:00+01:00",
+                "2023-03-01T16:30:00+01:00",
+                None,
+            ),
+        ];
+
+        let digest = compute(&meetings);
+        assert_eq!(digest.meetings.len(), 3);
+        assert_eq!(digest.meetings[0].summary(), "Standup");
+        assert_eq!(digest.total, Duration::minutes(120));
+        assert_eq!(digest.conflicts.len(), 1);
+        assert_eq!(digest.conflicts[0].0.summary(), "Retro");
+        assert_eq!(digest.missing_links[0].summary(), "Interview");
+
+        let text = digest.to_string();
+        assert!(text.starts_with("3 meetings, 2h00m in total\nFirst: "));
+        assert!(text.contains("\nLast: ") && text.contains(" Interview\nConflict: "));
+        assert!(text.ends_with(" Interview"));
+
+        assert_eq!(compute(&[]).to_string(), "No meetings");
+    }
+}
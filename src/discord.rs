@@ -0,0 +1,153 @@
+use super::meetings::Meeting;
+use std::error::Error;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+
+const HANDSHAKE: u32 = 0;
+const FRAME: u32 = 1;
+// a Discord that stopped answering must not stall the watch loop
+const TIMEOUT_SECONDS: u64 = 5;
+
+fn frame(op: u32, payload: &serde_json::Value) -> Vec<u8> {
+    let payload = payload.to_string();
+    let mut frame = Vec::with_capacity(8 + payload.len());
+    frame.extend_from_slice(&op.to_le_bytes());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload.as_bytes());
+    frame
+}
+
+fn socket_dirs() -> Vec<String> {
+    ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .chain(std::iter::once("/tmp".to_string()))
+        .collect()
+}
+
+fn nonce() -> String {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos().to_string())
+        .unwrap_or_default()
+}
+
+fn activity(meeting: &Meeting) -> serde_json::Value {
+    let back_at = meeting
+        .end()
+        .map(|end| format!("back at {}", end.format("%H:%M")))
+        .unwrap_or_default();
+
+    serde_json::json!({
+        "details": "In a meeting",
+        "state": back_at,
+        "timestamps": {
+            "start": meeting.start().ok().map(|start| start.timestamp()),
+            "end": meeting.end().ok().map(|end| end.timestamp()),
+        },
+    })
+}
+
+pub struct Presence {
+    client_id: String,
+    stream: Option<UnixStream>,
+}
+
+impl Presence {
+    pub fn new(client_id: &str) -> Presence {
+        Presence {
+            client_id: client_id.to_string(),
+            stream: None,
+        }
+    }
+
+    fn read_frame(stream: &mut UnixStream) -> Result<serde_json::Value, Box<dyn Error>> {
+        let mut header = [0u8; 8];
+        stream.read_exact(&mut header)?;
+        let len = u32::from_le_bytes(header[4..8].try_into()?) as usize;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload)?;
+        Ok(serde_json::from_slice(&payload)?)
+    }
+
+    fn connect(&mut self) -> Result<&mut UnixStream, Box<dyn Error>> {
+        if self.stream.is_none() {
+            let mut stream = socket_dirs()
+                .iter()
+                .flat_map(|dir| (0..10).map(move |i| format!("{dir}/discord-ipc-{i}")))
+                .find_map(|path| UnixStream::connect(path).ok())
+                .ok_or("Discord is not running")?;
+            let timeout = Some(std::time::Duration::from_secs(TIMEOUT_SECONDS));
+            stream.set_read_timeout(timeout)?;
+            stream.set_write_timeout(timeout)?;
+
+            let handshake = serde_json::json!({ "v": 1, "client_id": self.client_id });
+            stream.write_all(&frame(HANDSHAKE, &handshake))?;
+            Self::read_frame(&mut stream)?;
+            self.stream = Some(stream);
+        }
+
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    fn set_activity(&mut self, activity: serde_json::Value) -> Result<(), Box<dyn Error>> {
+        let payload = serde_json::json!({
+            "cmd": "SET_ACTIVITY",
+            "args": { "pid": std::process::id(), "activity": activity },
+            "nonce": nonce(),
+        });
+
+        let result = self.connect().and_then(|stream| {
+            stream.write_all(&frame(FRAME, &payload))?;
+            Self::read_frame(stream)
+        });
+
+        if result.is_err() {
+            // Discord was probably restarted, reconnect on the next update
+            self.stream = None;
+        }
+
+        result.map(|_| ())
+    }
+
+    pub fn set_meeting(&mut self, meeting: &Meeting) -> Result<(), Box<dyn Error>> {
+        self.set_activity(activity(meeting))
+    }
+
+    pub fn clear(&mut self) -> Result<(), Box<dyn Error>> {
+        self.set_activity(serde_json::Value::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_length_prefixed() {
+        let result = frame(FRAME, &serde_json::json!({ "a": 1 }));
+
+        assert_eq!(&result[0..4], &[1, 0, 0, 0]);
+        assert_eq!(&result[4..8], &[7, 0, 0, 0]);
+        assert_eq!(&result[8..], b"{\"a\":1}");
+    }
+
+    #[test]
+    fn activity_shows_end_time() {
+        let m: Meeting = serde_json::from_str(
+            r#"{
+                "summary": "Standup",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" }
+            }"#,
+        )
+        .unwrap();
+        let end = m.end().unwrap();
+
+        let result = activity(&m);
+        assert_eq!(result["details"], "In a meeting");
+        assert_eq!(result["state"], format!("back at {}", end.format("%H:%M")));
+        assert_eq!(result["timestamps"]["end"], end.timestamp());
+    }
+}
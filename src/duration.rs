@@ -0,0 +1,155 @@
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDateTime;
+use chrono::NaiveTime;
+
+// Seconds the unit stands for; a number too large for a Duration is an error, not a panic
+fn span(value: i64, unit: char) -> Option<Duration> {
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    value
+        .checked_mul(seconds)
+        .filter(|seconds| *seconds <= i64::MAX / 1000)
+        .map(Duration::seconds)
+}
+
+pub fn parse(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {text}");
+    let mut total = Duration::zero();
+    let mut number = String::new();
+
+    for c in text.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let value: i64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+        total = span(value, c)
+            .and_then(|span| total.checked_add(&span))
+            .ok_or_else(invalid)?;
+    }
+
+    if !number.is_empty() {
+        // a bare number is read as minutes
+        let value = number.parse().map_err(|_| invalid())?;
+        total = span(value, 'm')
+            .and_then(|span| total.checked_add(&span))
+            .ok_or_else(invalid)?;
+    }
+
+    if text.trim().is_empty() {
+        Err(invalid())
+    } else {
+        Ok(total)
+    }
+}
+
+// "+15m", "-15m" or plain "15m"
+pub fn parse_signed(text: &str) -> Result<Duration, String> {
+    match text.trim().strip_prefix('-') {
+        Some(rest) => parse(rest).map(|duration| -duration),
+        None => parse(text.trim().trim_start_matches('+')),
+    }
+}
+
+// "15:00" or "today 15:00", "tomorrow 15:00", otherwise a full local date and time
+// such as "2023-03-01 15:00"
+pub fn parse_at(text: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let invalid = || format!("Invalid time: {text}");
+    let (day, time) = match text.split_once(' ') {
+        Some(("today", time)) => (now.date_naive(), time),
+        Some(("tomorrow", time)) => (now.date_naive() + chrono::Days::new(1), time),
+        _ => (now.date_naive(), text),
+    };
+    let naive = match NaiveTime::parse_from_str(time, "%H:%M") {
+        Ok(time) => day.and_time(time),
+        Err(_) => NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M")
+            .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M"))
+            .map_err(|_| invalid())?,
+    };
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or_else(invalid)
+}
+
+pub fn format_clock(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_units() {
+        assert_eq!(parse("30s"), Ok(Duration::seconds(30)));
+        assert_eq!(parse("5m"), Ok(Duration::minutes(5)));
+        assert_eq!(parse("1h30m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse("7d"), Ok(Duration::days(7)));
+        assert_eq!(parse("1w"), Ok(Duration::weeks(1)));
+        assert_eq!(parse("10"), Ok(Duration::minutes(10)));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse("").is_err());
+        assert!(parse("m").is_err());
+        assert!(parse("5y").is_err());
+        assert!(parse("99999999999999d").is_err());
+        assert!(parse("99999999999999999999").is_err());
+        assert!(parse_signed("-99999999999999w").is_err());
+    }
+
+    #[test]
+    fn formats_clock() {
+        assert_eq!(format_clock(Duration::seconds(462)), "07:42");
+        assert_eq!(format_clock(Duration::seconds(3725)), "1:02:05");
+        assert_eq!(format_clock(Duration::seconds(-5)), "00:00");
+    }
+
+    #[test]
+    fn parses_signed() {
+        assert_eq!(parse_signed("+15m"), Ok(Duration::minutes(15)));
+        assert_eq!(parse_signed("-1h"), Ok(Duration::hours(-1)));
+        assert_eq!(parse_signed("10"), Ok(Duration::minutes(10)));
+        assert!(parse_signed("-").is_err());
+    }
+
+    #[test]
+    fn parses_times() {
+        let now: DateTime<Local> = "2023-03-01T09:00:00+01:00".parse().unwrap();
+
+        let at = parse_at("15:30", now).unwrap();
+        assert_eq!(at.date_naive(), now.date_naive());
+        assert_eq!(at.format("%H:%M").to_string(), "15:30");
+
+        let at = parse_at("2023-03-02 08:15", now).unwrap();
+        assert_eq!(at.format("%Y-%m-%d %H:%M").to_string(), "2023-03-02 08:15");
+        assert_eq!(parse_at("2023-03-02T08:15", now), Ok(at));
+
+        let at = parse_at("tomorrow 14:00", now).unwrap();
+        assert_eq!(at.format("%Y-%m-%d %H:%M").to_string(), "2023-03-02 14:00");
+
+        assert!(parse_at("tomorrow", now).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use super::digest;
+use super::meetings::Meeting;
+use super::settings::Email;
+use super::slots;
+use super::template;
+use chrono::DateTime;
+use chrono::Local;
+use chrono::NaiveTime;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::AsyncSmtpTransport;
+use lettre::AsyncTransport;
+use lettre::Message;
+use lettre::Tokio1Executor;
+use std::error::Error;
+
+const DEFAULT_PORT: u16 = 587;
+// The port where SMTP is spoken over TLS right away instead of after STARTTLS
+const TLS_PORT: u16 = 465;
+// Sent from the watch loop, which must get back to the systemd watchdog within a minute
+const TIMEOUT_SECONDS: u64 = 15;
+
+// Once a day, on the first tick at or after the configured time
+pub fn due(at: NaiveTime, since: DateTime<Local>, now: DateTime<Local>) -> bool {
+    slots::at(now.date_naive(), at).is_some_and(|at| since < at && at <= now)
+}
+
+pub fn message(
+    settings: &Email,
+    meetings: &[Meeting],
+    now: DateTime<Local>,
+) -> Result<Message, Box<dyn Error>> {
+    let body = match &settings.template {
+        Some(path) => template::render_file(path, meetings, now)?,
+        None => digest::compute(meetings).to_string(),
+    };
+    let subject = match &settings.subject {
+        Some(subject) => subject.clone(),
+        None => format!("Agenda for {}", now.format("%A %d/%m")),
+    };
+
+    let mut builder = Message::builder()
+        .from(settings.from.parse()?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN);
+    for to in &settings.to {
+        builder = builder.to(to.parse()?);
+    }
+    Ok(builder.body(body)?)
+}
+
+pub async fn send(settings: &Email, message: Message) -> Result<(), Box<dyn Error>> {
+    let port = settings.port.unwrap_or(DEFAULT_PORT);
+    let mut transport = if port == TLS_PORT {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.server)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.server)?
+    }
+    .port(port)
+    .timeout(Some(std::time::Duration::from_secs(TIMEOUT_SECONDS)));
+    if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+
+    transport.build().send(message).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Email {
+        serde_json::from_str(
+            r#"{ "server": "smtp.example.org", "from": "nextmeet@example.org",
+                 "to": ["me@example.org", "team@example.org"], "at": "07:30" }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn sends_once_a_day() {
+        let at = settings().at().unwrap();
+        let time = |time: &str| time.parse::<DateTime<Local>>().unwrap();
+        let now = slots::at("2023-03-01".parse().unwrap(), at).unwrap();
+
+        assert!(due(at, now - chrono::Duration::seconds(15), now));
+        assert!(!due(at, now, now + chrono::Duration::seconds(15)));
+        assert!(!due(
+            at,
+            time("2023-03-01T06:00:00+01:00"),
+            now - chrono::Duration::minutes(1)
+        ));
+    }
+
+    #[test]
+    fn builds_digest_mail() {
+        let meeting: Meeting = serde_json::from_str(
+            r#"{ "summary": "Standup",
+                 "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                 "end": { "dateTime": "2023-03-01T10:30:00+01:00" },
+                 "attendees": [{ "self": true, "responseStatus": "accepted" }] }"#,
+        )
+        .unwrap();
+        let now = "2023-03-01T07:30:00+01:00".parse().unwrap();
+
+        let message = message(&settings(), &[meeting], now).unwrap();
+        let text = String::from_utf8(message.formatted()).unwrap();
+        assert!(text.contains("Subject: Agenda for Wednesday 01/03"));
+        assert!(text.contains("To: me@example.org, team@example.org"));
+        assert!(text.contains("1 meeting, 0h30m in total"));
+
+        let mut invalid = settings();
+        invalid.from = "nextmeet".to_string();
+        assert!(super::message(&invalid, &[], now).is_err());
+    }
+}
@@ -0,0 +1,340 @@
+use super::meetings::Meeting;
+use chrono::DateTime;
+use chrono::Local;
+use chrono::Utc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Ics,
+    Taskwarrior,
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn csv(meetings: &[Meeting]) -> String {
+    let format = |date: Result<DateTime<Local>, _>, fmt: &str| {
+        date.map(|date: DateTime<Local>| date.format(fmt).to_string())
+            .unwrap_or_default()
+    };
+
+    let mut lines = vec!["date,start,end,summary,link,organizer,response,note".to_string()];
+    for meeting in meetings {
+        let fields = [
+            format(meeting.start(), "%Y-%m-%d"),
+            format(meeting.start(), "%H:%M"),
+            format(meeting.end(), "%H:%M"),
+            meeting.summary().to_string(),
+            meeting.get_link().unwrap_or_default(),
+            meeting.organizer().unwrap_or_default().to_string(),
+            meeting.response_status().unwrap_or_default().to_string(),
+            meeting.note().unwrap_or_default().to_string(),
+        ];
+        lines.push(
+            fields
+                .iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+    }
+
+    lines.join("\n")
+}
+
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn ics_date_time(date: DateTime<Local>) -> String {
+    date.with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+// RFC 5545 content lines are folded at 75 octets, continuation lines start with a space
+fn ics_fold(line: String) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+// RFC 5545 requires a UID, events without any id are left out
+fn vevent(meeting: &Meeting, uid: &str, now: DateTime<Local>) -> Option<Vec<String>> {
+    let (Ok(start), Ok(end)) = (meeting.start(), meeting.end()) else {
+        return None;
+    };
+
+    let mut lines = vec!["BEGIN:VEVENT".to_string(), format!("UID:{}", uid)];
+    lines.push(format!("DTSTAMP:{}", ics_date_time(now)));
+    lines.push(format!("DTSTART:{}", ics_date_time(start)));
+    lines.push(format!("DTEND:{}", ics_date_time(end)));
+    lines.push(format!("SUMMARY:{}", ics_text(meeting.summary())));
+    if let Some(description) = meeting.description() {
+        lines.push(format!("DESCRIPTION:{}", ics_text(description)));
+    }
+    if let Some(note) = meeting.note() {
+        lines.push(format!("COMMENT:{}", ics_text(note)));
+    }
+    if let Some(link) = meeting.get_link() {
+        lines.push(format!("URL:{}", link));
+        lines.push(format!("LOCATION:{}", ics_text(&link)));
+    }
+    lines.push("END:VEVENT".to_string());
+    Some(lines)
+}
+
+fn calendar(events: Vec<String>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//nextmeet//nextmeet//EN".to_string(),
+    ];
+    lines.extend(events);
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .into_iter()
+        .map(ics_fold)
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+fn ics(meetings: &[Meeting], now: DateTime<Local>) -> String {
+    calendar(
+        meetings
+            .iter()
+            .filter_map(|meeting| vevent(meeting, meeting.ical_uid()?, now))
+            .flatten()
+            .collect(),
+    )
+}
+
+// Set on every event vdir-sync writes, the only files it will ever remove
+pub const NEXTMEET_ID: &str = "X-NEXTMEET-ID";
+
+// A calendar holding one event, as a vdir keeps them; the event id is the UID since the
+// instances of a recurring meeting share their iCalUID
+pub fn ics_event(meeting: &Meeting, now: DateTime<Local>) -> Option<String> {
+    let id = meeting.id()?;
+    let mut event = vevent(meeting, id, now)?;
+    event.insert(event.len() - 1, format!("{NEXTMEET_ID}:{id}"));
+    Some(calendar(event))
+}
+
+// RFC 4122 name-based (v5) UUIDs in the URL namespace, so the same event always becomes the
+// same task and importing again updates it instead of adding a duplicate
+const UUID_NAMESPACE: [u8; 16] = [
+    0x6b, 0xa7, 0xb8, 0x11, 0x9d, 0xad, 0x11, 0xd1, 0x80, 0xb4, 0x00, 0xc0, 0x4f, 0xd4, 0x30, 0xc8,
+];
+
+fn task_uuid(id: &str) -> String {
+    let mut hasher = openssl::sha::Sha1::new();
+    hasher.update(&UUID_NAMESPACE);
+    hasher.update(id.as_bytes());
+    let mut bytes = hasher.finish();
+    bytes[6] = (bytes[6] & 0x0f) | 0x50;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+// For `task import`, one task per meeting due when it starts
+fn taskwarrior(meetings: &[Meeting], now: DateTime<Local>) -> String {
+    let tasks: Vec<_> = meetings
+        .iter()
+        .filter_map(|meeting| {
+            let (Some(id), Ok(start)) = (meeting.id(), meeting.start()) else {
+                return None;
+            };
+            let mut tags = vec!["meeting"];
+            if meeting.event_type() != "default" {
+                tags.push(meeting.event_type());
+            }
+
+            let mut task = serde_json::json!({
+                "uuid": task_uuid(id),
+                "description": meeting.summary(),
+                "status": "pending",
+                "entry": ics_date_time(now),
+                "due": ics_date_time(start),
+                "tags": tags,
+            });
+            if let Some(link) = meeting.get_link() {
+                task["annotations"] = serde_json::json!([
+                    { "entry": ics_date_time(now), "description": link },
+                ]);
+            }
+            Some(task)
+        })
+        .collect();
+
+    serde_json::Value::from(tasks).to_string()
+}
+
+pub fn export(format: Format, meetings: &[Meeting], now: DateTime<Local>) -> String {
+    match format {
+        Format::Csv => csv(meetings),
+        Format::Ics => ics(meetings, now),
+        Format::Taskwarrior => taskwarrior(meetings, now),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_escapes_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn csv_rows() {
+        let m: Meeting = serde_json::from_str(
+            r#"{
+                "summary": "Planning, Q3",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T11:00:00+01:00" },
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                "organizer": { "email": "boss@example.org" },
+                "attendees": [{ "self": true, "responseStatus": "accepted" }]
+            }"#,
+        )
+        .unwrap();
+        let start = m.start().unwrap();
+        let end = m.end().unwrap();
+
+        let result = export(Format::Csv, &[m], Local::now());
+        let lines: Vec<_> = result.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "date,start,end,summary,link,organizer,response,note"
+        );
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},{},{},\"Planning, Q3\",https://meet.google.com/aaa-bbbb-ccc,boss@example.org,accepted,",
+                start.format("%Y-%m-%d"),
+                start.format("%H:%M"),
+                end.format("%H:%M")
+            )
+        );
+    }
+
+    #[test]
+    fn ics_escapes_text() {
+        assert_eq!(ics_text("a, b; c\nd"), "a\\, b\\; c\\nd");
+    }
+
+    #[test]
+    fn ics_folds_long_lines() {
+        let folded = ics_fold("X".repeat(100));
+        let lines: Vec<_> = folded.split("\r\n").collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1], format!(" {}", "X".repeat(25)));
+    }
+
+    #[test]
+    fn ics_events() {
+        let mut m: Meeting = serde_json::from_str(
+            r#"{
+                "id": "abc123",
+                "iCalUID": "abc123@google.com",
+                "summary": "Standup",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" },
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc"
+            }"#,
+        )
+        .unwrap();
+
+        m.set_note(Some("bring the Q3 numbers".to_string()));
+        let result = export(Format::Ics, &[m], Local::now());
+        let lines: Vec<_> = result.split("\r\n").collect();
+
+        assert_eq!(lines.first(), Some(&"BEGIN:VCALENDAR"));
+        assert_eq!(lines.last(), Some(&"END:VCALENDAR"));
+        assert!(lines.contains(&"UID:abc123@google.com"));
+        assert!(lines.contains(&"DTSTART:20230301T090000Z"));
+        assert!(lines.contains(&"DTEND:20230301T091500Z"));
+        assert!(lines.contains(&"URL:https://meet.google.com/aaa-bbbb-ccc"));
+        assert!(lines.contains(&"COMMENT:bring the Q3 numbers"));
+
+        let without_uid: Meeting = serde_json::from_str(
+            r#"{ "id": "def456", "summary": "Retro",
+                 "start": { "dateTime": "2023-03-01T15:00:00+01:00" },
+                 "end": { "dateTime": "2023-03-01T16:00:00+01:00" } }"#,
+        )
+        .unwrap();
+        let result = export(Format::Ics, &[without_uid], Local::now());
+        assert!(result.contains("\r\nUID:def456\r\n"));
+    }
+
+    #[test]
+    fn taskwarrior_tasks() {
+        let m: Meeting = serde_json::from_str(
+            r#"{
+                "id": "abc123",
+                "summary": "Standup",
+                "eventType": "focusTime",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" },
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc"
+            }"#,
+        )
+        .unwrap();
+
+        let result = export(Format::Taskwarrior, &[m], Local::now());
+        let tasks: serde_json::Value = serde_json::from_str(&result).unwrap();
+        let task = &tasks[0];
+
+        assert_eq!(task["description"], "Standup");
+        assert_eq!(task["due"], "20230301T090000Z");
+        assert_eq!(task["tags"], serde_json::json!(["meeting", "focusTime"]));
+        assert_eq!(
+            task["annotations"][0]["description"],
+            "https://meet.google.com/aaa-bbbb-ccc"
+        );
+        assert_eq!(task["uuid"], task_uuid("abc123"));
+        // what Python's uuid.uuid5(uuid.NAMESPACE_URL, "python.org") gives
+        assert_eq!(
+            task_uuid("python.org"),
+            "7af94e2b-4dd9-50f0-9c9a-8a48519bdef0"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+// C entry points for status bar plugins that would rather not spawn a process, built with
+// `cargo build --lib --release --features ffi` as libnextmeet.so; the header is written to
+// nextmeet.h in the build script's OUT_DIR, under target/release/build. Strings handed out
+// are owned by the caller and go back through nextmeet_free_string, a NULL return means the
+// call failed and nextmeet_last_error says why.
+use super::client::Client;
+use chrono::Local;
+use chrono::NaiveDate;
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::c_char;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::future::Future;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn to_c(text: String) -> *mut c_char {
+    // interior NULs can't cross into C, they only show up in odd descriptions
+    CString::new(text.replace('\0', ""))
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+fn run<F>(call: F) -> *mut c_char
+where
+    F: Future<Output = Result<String, Box<dyn Error>>>,
+{
+    let result = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| err.into())
+        .and_then(|runtime| runtime.block_on(call));
+    match result {
+        Ok(text) => {
+            LAST_ERROR.with(|error| *error.borrow_mut() = None);
+            to_c(text)
+        }
+        Err(err) => {
+            let message = CString::new(err.to_string().replace('\0', "")).unwrap_or_default();
+            LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// A missing date stands for today
+unsafe fn date(text: *const c_char) -> Result<NaiveDate, Box<dyn Error>> {
+    if text.is_null() {
+        return Ok(Local::now().date_naive());
+    }
+    Ok(CStr::from_ptr(text).to_str()?.parse()?)
+}
+
+/// The upcoming meeting as JSON, as in the `-j` and `-mf` outputs, or `null`.
+#[no_mangle]
+pub extern "C" fn nextmeet_next_meeting_json() -> *mut c_char {
+    run(async {
+        let meeting = Client::new().next_meeting().await?;
+        Ok(serde_json::to_string(&meeting)?)
+    })
+}
+
+/// The link of the upcoming meeting, an empty string when there is none.
+#[no_mangle]
+pub extern "C" fn nextmeet_join_link() -> *mut c_char {
+    run(async { Ok(Client::new().join_link().await?.unwrap_or_default()) })
+}
+
+/// The agenda between two `YYYY-MM-DD` dates as a JSON array; NULL dates mean today.
+///
+/// # Safety
+///
+/// `from` and `to` must be NULL or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn nextmeet_agenda_json(
+    from: *const c_char,
+    to: *const c_char,
+) -> *mut c_char {
+    let range = date(from).and_then(|from| Ok(from..=date(to)?));
+    run(async {
+        let meetings = Client::new().agenda(range?).await?;
+        Ok(serde_json::to_string(&meetings)?)
+    })
+}
+
+/// Why the last call on this thread returned NULL, or NULL if it succeeded.
+/// The string belongs to the library and is valid until the next call.
+#[no_mangle]
+pub extern "C" fn nextmeet_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |error| error.as_ptr())
+    })
+}
+
+/// Frees a string returned by the library.
+///
+/// # Safety
+///
+/// `text` must be NULL or a string returned by one of the functions above, freed only once.
+#[no_mangle]
+pub unsafe extern "C" fn nextmeet_free_string(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_errors() {
+        let text = run(async { Ok("{}".to_string()) });
+        assert_eq!(unsafe { CStr::from_ptr(text) }.to_str(), Ok("{}"));
+        assert!(nextmeet_last_error().is_null());
+        unsafe { nextmeet_free_string(text) };
+
+        assert!(run(async { Err("No calendar".into()) }).is_null());
+        let error = unsafe { CStr::from_ptr(nextmeet_last_error()) };
+        assert_eq!(error.to_str(), Ok("No calendar"));
+    }
+
+    #[test]
+    fn reads_dates() {
+        let day = CString::new("2023-03-01").unwrap();
+        assert_eq!(
+            unsafe { date(day.as_ptr()) }.ok(),
+            NaiveDate::from_ymd_opt(2023, 3, 1)
+        );
+        assert!(unsafe { date(std::ptr::null()) }.is_ok());
+        let garbage = CString::new("tomorrow").unwrap();
+        assert!(unsafe { date(garbage.as_ptr()) }.is_err());
+    }
+}
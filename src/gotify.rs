@@ -0,0 +1,84 @@
+use super::notifier::Notifier;
+use super::notifier::Reminder;
+use super::settings;
+use async_trait::async_trait;
+use std::error::Error;
+
+// The top of Gotify's scale, which clients show even when muted
+const URGENT_PRIORITY: u8 = 10;
+
+pub struct Gotify {
+    settings: settings::Gotify,
+}
+
+impl Gotify {
+    pub fn new(settings: settings::Gotify) -> Gotify {
+        Gotify { settings }
+    }
+}
+
+fn payload(reminder: &Reminder, priority: u8) -> serde_json::Value {
+    let mut payload = serde_json::json!({
+        "title": reminder.title,
+        "message": reminder.message,
+        "priority": priority,
+    });
+    if let Some(link) = &reminder.link {
+        payload["extras"] = serde_json::json!({
+            "client::notification": { "click": { "url": link } },
+        });
+    }
+    payload
+}
+
+#[async_trait(?Send)]
+impl Notifier for Gotify {
+    fn name(&self) -> &'static str {
+        "gotify"
+    }
+
+    fn before(&self) -> &[String] {
+        &self.settings.before
+    }
+
+    async fn send(&self, reminder: &Reminder) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/message", self.settings.server.trim_end_matches('/'));
+        let priority = if reminder.urgent {
+            URGENT_PRIORITY
+        } else {
+            self.settings.priority.unwrap_or(5)
+        };
+
+        crate::http::client()
+            .post(url)
+            .header("X-Gotify-Key", &self.settings.token)
+            .header("Content-Type", "application/json")
+            .body(payload(reminder, priority).to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn click_url_in_extras() {
+        let reminder = Reminder {
+            title: "Standup".to_string(),
+            message: "Starts at 10:00 (in 5 minutes)".to_string(),
+            link: Some("https://meet.google.com/aaa-bbbb-ccc".to_string()),
+            ..Default::default()
+        };
+
+        let result = payload(&reminder, 8);
+        assert_eq!(result["priority"], 8);
+        assert_eq!(
+            result["extras"]["client::notification"]["click"]["url"],
+            "https://meet.google.com/aaa-bbbb-ccc"
+        );
+    }
+}
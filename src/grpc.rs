@@ -0,0 +1,148 @@
+use super::meetings::next_meeting;
+use super::meetings::Meeting;
+use super::server;
+use super::server::Shared;
+use chrono::Local;
+use futures::Stream;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio::sync::watch;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+
+pub mod proto {
+    tonic::include_proto!("nextmeet");
+}
+
+use proto::calendar_server::Calendar;
+use proto::calendar_server::CalendarServer;
+
+impl From<&Meeting> for proto::Meeting {
+    fn from(meeting: &Meeting) -> proto::Meeting {
+        proto::Meeting {
+            id: meeting.id().unwrap_or_default().to_string(),
+            summary: meeting.summary().to_string(),
+            start: meeting
+                .start()
+                .map(|start| start.timestamp())
+                .unwrap_or_default(),
+            end: meeting.end().map(|end| end.timestamp()).unwrap_or_default(),
+            link: meeting.get_link().unwrap_or_default(),
+            organizer: meeting.organizer().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+fn agenda(meetings: &[Meeting]) -> proto::Agenda {
+    proto::Agenda {
+        meetings: meetings.iter().map(proto::Meeting::from).collect(),
+    }
+}
+
+// Shares the REST server's state, so both answer from the same cache and tokens
+struct Service {
+    shared: Shared,
+}
+
+// Waits for today's agenda to change, refreshing it meanwhile; false once the server is gone
+async fn changed(shared: &Shared, updates: &mut watch::Receiver<Vec<Meeting>>) -> bool {
+    loop {
+        let refresh = std::time::Duration::from_secs(server::EVENTS_REFRESH_SECONDS);
+        tokio::select! {
+            changed = updates.changed() => return changed.is_ok(),
+            _ = tokio::time::sleep(refresh) => {
+                let _ = server::agenda(shared, Local::now().date_naive()).await;
+            }
+        }
+    }
+}
+
+type AgendaStream = Pin<Box<dyn Stream<Item = Result<proto::Agenda, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Calendar for Service {
+    async fn get_next_meeting(
+        &self,
+        _: Request<proto::NextMeetingRequest>,
+    ) -> Result<Response<proto::NextMeetingResponse>, Status> {
+        let now = Local::now();
+        let meetings = server::agenda(&self.shared, now.date_naive())
+            .await
+            .map_err(Status::unavailable)?;
+        Ok(Response::new(proto::NextMeetingResponse {
+            meeting: next_meeting(&meetings, now).map(proto::Meeting::from),
+        }))
+    }
+
+    type StreamAgendaStream = AgendaStream;
+
+    // Like /events: a snapshot on connect, then one per change
+    async fn stream_agenda(
+        &self,
+        _: Request<proto::AgendaRequest>,
+    ) -> Result<Response<AgendaStream>, Status> {
+        let shared = self.shared.clone();
+        let updates = shared.lock().unwrap().updates.subscribe();
+        server::agenda(&shared, Local::now().date_naive())
+            .await
+            .map_err(Status::unavailable)?;
+
+        let stream = futures::stream::unfold(
+            (shared, updates, true),
+            |(shared, mut updates, first)| async move {
+                if !first && !changed(&shared, &mut updates).await {
+                    return None;
+                }
+                let snapshot = agenda(&updates.borrow_and_update());
+                Some((Ok(snapshot), (shared, updates, false)))
+            },
+        );
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+pub fn spawn(listen: &str, shared: Shared) -> Result<(), Box<dyn Error>> {
+    let addr: SocketAddr = listen
+        .parse()
+        .map_err(|_| format!("Invalid gRPC listen address: {listen}"))?;
+    let server = tonic::transport::Server::builder()
+        .add_service(CalendarServer::new(Service { shared }))
+        .serve(addr);
+
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            eprintln!("Error: gRPC server: {}", err);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_meetings() {
+        let meeting: Meeting = serde_json::from_str(
+            r#"{ "id": "standup", "summary": "Standup",
+                 "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                 "organizer": { "email": "boss@example.org" },
+                 "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                 "end": { "dateTime": "2023-03-01T10:30:00+01:00" } }"#,
+        )
+        .unwrap();
+
+        let converted = agenda(&[meeting]).meetings.remove(0);
+        assert_eq!(converted.id, "standup");
+        assert_eq!(converted.start, 1677661200);
+        assert_eq!(converted.end - converted.start, 30 * 60);
+        assert_eq!(converted.link, "https://meet.google.com/aaa-bbbb-ccc");
+        assert_eq!(converted.organizer, "boss@example.org");
+
+        let empty = proto::Meeting::from(&Meeting::default());
+        assert_eq!(empty.summary, "No summary");
+        assert_eq!(empty.start, 0);
+    }
+}
@@ -0,0 +1,515 @@
+// One description of the command line for `--help` and `nextmeet man`, kept next to the
+// argument parsing in main.rs: a command or option added there belongs here too
+pub struct Command {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub about: &'static str,
+    pub options: &'static [(&'static str, &'static str)],
+}
+
+pub const ABOUT: &str = "Shows your next Google Calendar meeting, for status bars and terminals";
+
+// Read by every command that shows meetings
+pub const OPTIONS: &[(&str, &str)] = &[
+    ("-m", "Print only the link of the next meeting"),
+    ("-j", "Print today's events as returned by the Calendar API"),
+    (
+        "-mf",
+        "Print the next meeting as JSON, without ever asking to log in",
+    ),
+    (
+        "-al",
+        "Print the other links in the next meeting's description",
+    ),
+    ("-a", "List today's meetings instead of the next one"),
+    (
+        "--cost",
+        "With -a, show what each meeting costs in people's time; text and ndjson only",
+    ),
+    ("-d", "Print the API response to stderr"),
+    (
+        "--output FORMAT",
+        "text, i3blocks, i3bar, xmobar, tmux, xbar, argos, alfred, raycast, widget, ndjson, \
+         markdown, org, html or gcalcli",
+    ),
+    ("--max-width N", "Shorten summaries to N characters"),
+    ("--template FILE", "Render the agenda with a Tera template"),
+    ("--qr", "Print the next meeting's link as a QR code"),
+    ("--from DATE", "First day of the agenda, YYYY-MM-DD"),
+    ("--to DATE", "Last day of the agenda, YYYY-MM-DD"),
+    (
+        "--ongoing POLICY",
+        "Whether the next meeting can be one already started: nearest (default), prefer, \
+         skip or only",
+    ),
+    (
+        "--now TIME",
+        "Answer as if it were TIME, e.g. 2024-06-01T09:00:00+02:00 or 15:00",
+    ),
+    (
+        "--no-cache",
+        "Always ask Google instead of answering from the 60s cache",
+    ),
+    ("--dump-response FILE", "Save the raw API responses to FILE"),
+    (
+        "--from-file FILE",
+        "Read the API response from FILE instead of Google, e.g. one saved by --dump-response",
+    ),
+    (
+        "--stdin",
+        "Read the API response from stdin instead of Google",
+    ),
+];
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "week",
+        usage: "week",
+        about: "List the meetings of the current week",
+        options: &[],
+    },
+    Command {
+        name: "join",
+        usage: "join [--nth N | --pick] [--at-start [--early DURATION]]",
+        about: "Open the link of the next meeting",
+        options: &[
+            ("--nth N", "Join the Nth meeting of the day instead"),
+            ("--pick", "Choose the meeting from a list"),
+            ("--at-start", "Wait for the meeting to start, counting down"),
+            (
+                "--early DURATION",
+                "With --at-start, join this long before the start",
+            ),
+        ],
+    },
+    Command {
+        name: "now",
+        usage: "now",
+        about: "Print the ongoing meeting, exiting 0 while busy and 1 when free",
+        options: &[],
+    },
+    Command {
+        name: "free-at",
+        usage: "free-at",
+        about: "Print when the current run of back-to-back meetings ends",
+        options: &[],
+    },
+    Command {
+        name: "gap",
+        usage: "gap [--between HH:MM HH:MM]",
+        about: "Print the largest free interval left today",
+        options: &[(
+            "--between HH:MM HH:MM",
+            "Look only within this window instead of working hours",
+        )],
+    },
+    Command {
+        name: "watch",
+        usage: "watch [--listen ADDRESS]",
+        about: "Keep running, firing hooks, webhooks and notifiers around meetings",
+        options: &[(
+            "--listen ADDRESS",
+            "Also serve metrics and push notifications",
+        )],
+    },
+    Command {
+        name: "serve",
+        usage: "serve [--listen ADDRESS] [--grpc-listen ADDRESS]",
+        about: "Answer /next, /today, /agenda and /events over HTTP",
+        options: &[
+            (
+                "--listen ADDRESS",
+                "Where to listen, 127.0.0.1:8686 by default",
+            ),
+            (
+                "--grpc-listen ADDRESS",
+                "Also serve the gRPC API, with the grpc feature",
+            ),
+        ],
+    },
+    Command {
+        name: "query",
+        usage: "query [next|link|today]",
+        about: "Ask a running server over its Unix socket",
+        options: &[],
+    },
+    Command {
+        name: "notify",
+        usage: "notify",
+        about: "Show a desktop notification for the next meeting",
+        options: &[
+            ("--event MEETING", "For this meeting instead"),
+            (
+                "--starts TIME",
+                "With --event, skip it unless the meeting still starts then",
+            ),
+        ],
+    },
+    Command {
+        name: "schedule-reminders",
+        usage: "schedule-reminders [--dry-run]",
+        about: "Leave today's reminders to systemd timers or at jobs instead of watch",
+        options: &[("--dry-run", "Print the commands instead of running them")],
+    },
+    Command {
+        name: "tui",
+        usage: "tui",
+        about: "Browse the day and week agenda in the terminal",
+        options: &[],
+    },
+    Command {
+        name: "pick",
+        usage: "pick [--open]",
+        about: "Choose a meeting and print its link",
+        options: &[("--open", "Open the link instead of printing it")],
+    },
+    Command {
+        name: "countdown",
+        usage: "countdown",
+        about: "Count down to the next meeting on a single line",
+        options: &[],
+    },
+    Command {
+        name: "export",
+        usage: "export --csv|--ics|--taskwarrior [--accepted] [--with-link]",
+        about: "Export the agenda between --from and --to",
+        options: &[
+            ("--csv", "As CSV"),
+            ("--ics", "As an iCalendar file"),
+            (
+                "--taskwarrior",
+                "As tasks for `task import`, which updates them when imported again",
+            ),
+            ("--accepted", "Only meetings I accepted"),
+            ("--with-link", "Only meetings with a link"),
+        ],
+    },
+    Command {
+        name: "org-sync",
+        usage: "org-sync [--file PATH]",
+        about: "Keep an org-agenda file in step with the meetings of the week from --from",
+        options: &[(
+            "--file PATH",
+            "The file to keep, ~/org/meetings.org by default",
+        )],
+    },
+    Command {
+        name: "vdir-sync",
+        usage: "vdir-sync [--dir PATH]",
+        about: "Mirror the week from --from into a vdir for khal, one .ics per event",
+        options: &[(
+            "--dir PATH",
+            "A vdir of its own, never one vdirsyncer syncs; the vdir setting by default",
+        )],
+    },
+    Command {
+        name: "stats",
+        usage: "stats [--week | --month] [--cost]",
+        about: "Summarize the meeting load of the week or month",
+        options: &[(
+            "--cost",
+            "Add what the meetings cost: length, attendees and the hourly rate setting",
+        )],
+    },
+    Command {
+        name: "digest",
+        usage: "digest",
+        about: "Summarize today, or --from to --to: time in meetings, conflicts, missing links",
+        options: &[],
+    },
+    Command {
+        name: "history",
+        usage: "history [--since DURATION]",
+        about: "List the meetings attended recently",
+        options: &[],
+    },
+    Command {
+        name: "diff",
+        usage: "diff",
+        about: "Report meetings added, cancelled or moved since the last fetch",
+        options: &[],
+    },
+    Command {
+        name: "snooze",
+        usage: "snooze MEETING DURATION",
+        about: "Hide a meeting from the next meeting for a while",
+        options: &[],
+    },
+    Command {
+        name: "dismiss",
+        usage: "dismiss MEETING",
+        about: "Hide a meeting from the next meeting for good",
+        options: &[],
+    },
+    Command {
+        name: "note",
+        usage: "note MEETING [TEXT]",
+        about: "Keep a local note on a meeting, an empty text removes it",
+        options: &[],
+    },
+    Command {
+        name: "rsvp",
+        usage: "rsvp [--series] MEETING accept|decline|tentative [--comment TEXT]",
+        about: "Answer an invitation",
+        options: &[
+            ("--series", "Decline every upcoming meeting of the series"),
+            (
+                "--event-link URL",
+                "Take the meeting from a pasted calendar link",
+            ),
+            ("--comment TEXT", "Send a comment with the answer"),
+        ],
+    },
+    Command {
+        name: "decline-matching",
+        usage: "decline-matching TEXT [--dry-run] [--yes]",
+        about: "Decline the invitations between --from and --to whose title contains TEXT",
+        options: &[
+            ("--dry-run", "Only list what would be declined"),
+            ("--yes", "Don't ask for confirmation"),
+        ],
+    },
+    Command {
+        name: "propose",
+        usage: "propose MEETING --at TIME",
+        about: "Answer an invitation proposing another time",
+        options: &[],
+    },
+    Command {
+        name: "add",
+        usage: "add [TEXT] [--at TIME] [--duration DURATION] [--title TITLE] [--template NAME]",
+        about: "Create an event, from free text through Google's quick add",
+        options: &[
+            ("--at TIME", "When it starts"),
+            (
+                "--duration DURATION",
+                "How long it lasts, 30 minutes by default",
+            ),
+            ("--title TITLE", "What it is called"),
+            ("--template NAME", "Start from a template in the settings"),
+        ],
+    },
+    Command {
+        name: "instant",
+        usage: "instant [--title TITLE] [--invite EMAIL]...",
+        about: "Create a meeting starting now and copy its Meet link",
+        options: &[],
+    },
+    Command {
+        name: "block",
+        usage: "block [--minutes N] [--at TIME] [--auto-decline]",
+        about: "Block focus time in the first free slot of working hours",
+        options: &[(
+            "--auto-decline",
+            "Decline invitations during the focus time",
+        )],
+    },
+    Command {
+        name: "ooo",
+        usage: "ooo --from DATE --to DATE [--title TITLE]",
+        about: "Create an out of office event for whole days",
+        options: &[],
+    },
+    Command {
+        name: "location",
+        usage: "location home|office|PLACE",
+        about: "Set today's working location",
+        options: &[],
+    },
+    Command {
+        name: "followup",
+        usage: "followup MEETING [--in DURATION]",
+        about: "Schedule the same meeting again, a week later by default",
+        options: &[],
+    },
+    Command {
+        name: "move",
+        usage: "move MEETING OFFSET",
+        about: "Move a meeting I organize, e.g. by +15m or -1h",
+        options: &[],
+    },
+    Command {
+        name: "extend",
+        usage: "extend MEETING OFFSET",
+        about: "Change the end of a meeting I organize",
+        options: &[],
+    },
+    Command {
+        name: "cancel",
+        usage: "cancel MEETING [--notify-attendees] [--yes]",
+        about: "Delete a meeting I organize, or decline it",
+        options: &[],
+    },
+    Command {
+        name: "invite",
+        usage: "invite MEETING EMAIL...",
+        about: "Add guests to a meeting I organize",
+        options: &[],
+    },
+    Command {
+        name: "remind",
+        usage: "remind MEETING OFFSET...",
+        about: "Set the reminders of a meeting",
+        options: &[],
+    },
+    Command {
+        name: "annotate",
+        usage: "annotate MEETING TEXT",
+        about: "Append text to the description of a meeting you organize, visible to everyone",
+        options: &[],
+    },
+    Command {
+        name: "native-host",
+        usage: "native-host",
+        about: "Answer a browser extension over native messaging",
+        options: &[],
+    },
+    Command {
+        name: "cache",
+        usage: "cache clear",
+        about: "Forget the fetched agendas",
+        options: &[],
+    },
+    Command {
+        name: "daemon",
+        usage: "daemon install",
+        about: "Install and enable a systemd user service for serve",
+        options: &[],
+    },
+    Command {
+        name: "version",
+        usage: "version [--verbose]",
+        about: "Print the version",
+        options: &[(
+            "--verbose",
+            "Also the commit, build date, features and files in use",
+        )],
+    },
+    Command {
+        name: "man",
+        usage: "man",
+        about: "Print this manual as a roff man page",
+        options: &[],
+    },
+];
+
+const MEETING: &str = "MEETING is an event id, a calendar link, \"next\" or \"last\".";
+
+// What scripts can rely on in lists and JSON outputs
+const ORDER: &str = "Meetings are listed by start time; meetings starting together come shorter \
+first, then by summary and then by event id. The next meeting is the closest to now, unless \
+--ongoing says otherwise, with the same tie-breaking.";
+
+pub fn command(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+fn options(options: &[(&str, &str)]) -> String {
+    let width = options
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    options
+        .iter()
+        .map(|(name, about)| format!("  {name:width$}  {about}\n"))
+        .collect()
+}
+
+// `nextmeet --help` lists the commands, `nextmeet rsvp --help` describes one
+pub fn render(command: Option<&Command>) -> String {
+    match command {
+        Some(command) => {
+            let mut help = format!("{}\n\nUsage: nextmeet {}\n", command.about, command.usage);
+            if command.usage.contains("MEETING") {
+                help += &format!("\n{MEETING}\n");
+            }
+            if !command.options.is_empty() {
+                help += &format!("\nOptions:\n{}", options(command.options));
+            }
+            help
+        }
+        None => {
+            let width = COMMANDS
+                .iter()
+                .map(|command| command.name.len())
+                .max()
+                .unwrap_or(0);
+            let commands: String = COMMANDS
+                .iter()
+                .map(|command| format!("  {:width$}  {}\n", command.name, command.about))
+                .collect();
+            format!(
+                "{ABOUT}\n\nUsage: nextmeet [COMMAND] [OPTIONS]\n\nCommands:\n{commands}\nOptions:\n{}\nRun nextmeet COMMAND --help for more.\n",
+                options(OPTIONS)
+            )
+        }
+    }
+}
+
+fn roff(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    match text.starts_with(['.', '\'']) {
+        true => format!("\\&{text}"),
+        false => text,
+    }
+}
+
+fn roff_options(options: &[(&str, &str)]) -> String {
+    options
+        .iter()
+        .map(|(name, about)| format!(".TP\n\\fB{}\\fR\n{}\n", roff(name), roff(about)))
+        .collect()
+}
+
+// For packaging: nextmeet man > /usr/local/share/man/man1/nextmeet.1
+pub fn man() -> String {
+    let mut page = format!(
+        ".TH NEXTMEET 1 \"\" \"nextmeet {}\"\n.SH NAME\nnextmeet \\- {}\n.SH SYNOPSIS\n\\fBnextmeet\\fR [\\fICOMMAND\\fR] [\\fIOPTIONS\\fR]\n.SH OPTIONS\n{}.SH COMMANDS\n",
+        env!("CARGO_PKG_VERSION"),
+        roff(ABOUT),
+        roff_options(OPTIONS)
+    );
+    for command in COMMANDS {
+        page += &format!(
+            ".SS {}\n\\fBnextmeet {}\\fR\n.PP\n{}\n{}",
+            roff(command.name),
+            roff(command.usage),
+            roff(command.about),
+            roff_options(command.options)
+        );
+    }
+    page += &format!(
+        ".PP\n{}\n.SH ORDER\n{}\n.SH FILES\n.TP\n\\fI~/.nextmeet\\fR\nOAuth tokens\n.TP\n\\fI~/.nextmeet.json\\fR\nSettings\n",
+        roff(MEETING),
+        roff(ORDER)
+    );
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_commands() {
+        let help = render(command("rsvp"));
+        assert!(help.starts_with("Answer an invitation\n\nUsage: nextmeet rsvp "));
+        assert!(help.contains("\n  --event-link URL  Take the meeting"));
+        assert!(help.contains(MEETING));
+
+        let help = render(None);
+        assert!(help.contains("\n  decline-matching    Decline the invitations"));
+        assert!(help.contains("\n  --output FORMAT"));
+        assert!(command("weather").is_none());
+    }
+
+    #[test]
+    fn renders_man_page() {
+        let page = man();
+        assert!(page.starts_with(".TH NEXTMEET 1 "));
+        assert!(page.contains(".SS rsvp\n\\fBnextmeet rsvp [\\-\\-series]"));
+        assert!(page.lines().all(|line| !line.starts_with("..")));
+        assert_eq!(roff(".hidden"), "\\&.hidden");
+    }
+}
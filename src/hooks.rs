@@ -0,0 +1,107 @@
+use super::duration;
+use super::meetings::Meeting;
+use super::settings::Hooks;
+use super::watch::Event;
+use std::process::Command;
+
+fn command(hooks: &Hooks, event: Event) -> Option<&str> {
+    match event {
+        Event::Before(offset) => hooks.before_meeting.as_deref().filter(|_| {
+            hooks
+                .before
+                .iter()
+                .any(|b| duration::parse(b) == Ok(offset))
+        }),
+        Event::Started => hooks.on_meeting_start.as_deref(),
+        Event::Ended => hooks.on_meeting_end.as_deref(),
+    }
+}
+
+pub fn environment(event: Event, meeting: &Meeting) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("NEXTMEET_EVENT", event.name().to_string()),
+        ("NEXTMEET_SUMMARY", meeting.summary().to_string()),
+    ];
+
+    if let Event::Before(offset) = event {
+        env.push(("NEXTMEET_MINUTES_BEFORE", offset.num_minutes().to_string()));
+    }
+    if let Some(id) = meeting.id() {
+        env.push(("NEXTMEET_ID", id.to_string()));
+    }
+    if let Ok(start) = meeting.start() {
+        env.push(("NEXTMEET_START", start.to_rfc3339()));
+    }
+    if let Ok(end) = meeting.end() {
+        env.push(("NEXTMEET_END", end.to_rfc3339()));
+    }
+    if let Some(link) = meeting.get_link() {
+        env.push(("NEXTMEET_LINK", link));
+    }
+    if let Some(organizer) = meeting.organizer() {
+        env.push(("NEXTMEET_ORGANIZER", organizer.to_string()));
+    }
+
+    env
+}
+
+pub fn run(hooks: &Hooks, event: Event, meeting: &Meeting) {
+    let Some(command) = command(hooks, event) else {
+        return;
+    };
+
+    match Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(environment(event, meeting))
+        .spawn()
+    {
+        // reaped off-thread so a long-running watch doesn't collect zombies
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(err) => eprintln!("Error: failed to run {} hook: {}", event.name(), err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposes_meeting_metadata() {
+        let m: Meeting = serde_json::from_str(
+            r#"{
+                "id": "abc123",
+                "summary": "Standup",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc"
+            }"#,
+        )
+        .unwrap();
+
+        let env = environment(Event::Before(chrono::Duration::minutes(5)), &m);
+        let get = |key| env.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+
+        assert_eq!(get("NEXTMEET_EVENT"), Some("before"));
+        assert_eq!(get("NEXTMEET_MINUTES_BEFORE"), Some("5"));
+        assert_eq!(get("NEXTMEET_ID"), Some("abc123"));
+        assert_eq!(
+            get("NEXTMEET_LINK"),
+            Some("https://meet.google.com/aaa-bbbb-ccc")
+        );
+        assert_eq!(get("NEXTMEET_END"), None);
+    }
+
+    #[test]
+    fn before_hook_only_for_its_offsets() {
+        let hooks = Hooks {
+            before_meeting: Some("true".to_string()),
+            before: vec!["5m".to_string()],
+            ..Default::default()
+        };
+
+        assert!(command(&hooks, Event::Before(chrono::Duration::minutes(5))).is_some());
+        assert!(command(&hooks, Event::Before(chrono::Duration::minutes(1))).is_none());
+    }
+}
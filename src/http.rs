@@ -0,0 +1,17 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// One client for the whole process so daemon and server modes reuse connections;
+// credentials are added per request since they differ between services
+pub fn client() -> &'static reqwest::Client {
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent(concat!("nextmeet/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to build the HTTP client")
+    })
+}
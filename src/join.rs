@@ -0,0 +1,165 @@
+use super::duration::format_clock;
+use super::meetings;
+use super::meetings::next_meeting;
+use super::meetings::Meeting;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use std::error::Error;
+use std::io::BufRead;
+use std::io::Write;
+
+pub enum Selection {
+    Next,
+    Nth(usize),
+    Pick,
+}
+
+pub fn upcoming(meetings: &[Meeting], now: DateTime<Local>) -> Vec<&Meeting> {
+    meetings
+        .iter()
+        .filter(|meeting| meeting.end().map(|end| end > now).unwrap_or(false))
+        .collect()
+}
+
+fn pick<'a>(meetings: &[&'a Meeting]) -> Result<Option<&'a Meeting>, Box<dyn Error>> {
+    let mut stderr = std::io::stderr();
+    for (index, meeting) in meetings.iter().enumerate() {
+        let start = meeting
+            .start()
+            .map(|date| date.format("%H:%M").to_string())
+            .unwrap_or_default();
+        writeln!(stderr, "{}) {} {}", index + 1, start, meeting.summary())?;
+    }
+    write!(stderr, "Meeting to join: ")?;
+    stderr.flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    let index: usize = answer.trim().parse().map_err(|_| "Invalid choice")?;
+
+    Ok(index
+        .checked_sub(1)
+        .and_then(|index| meetings.get(index))
+        .copied())
+}
+
+pub fn select(
+    selection: &Selection,
+    meetings: &[Meeting],
+    now: DateTime<Local>,
+) -> Result<Option<Meeting>, Box<dyn Error>> {
+    let meeting = match selection {
+        Selection::Next => next_meeting(meetings, now),
+        Selection::Nth(n) => upcoming(meetings, now).get(n.saturating_sub(1)).copied(),
+        Selection::Pick => pick(&upcoming(meetings, now))?,
+    };
+
+    Ok(meeting.cloned())
+}
+
+// Re-poll the calendar while waiting so moved or cancelled meetings are noticed
+const REPOLL_INTERVAL: i64 = 5 * 60;
+
+fn wake_up_at(target: DateTime<Local>, now: DateTime<Local>) -> DateTime<Local> {
+    if target - now > Duration::minutes(2) {
+        (now + Duration::seconds(REPOLL_INTERVAL)).min(target - Duration::minutes(1))
+    } else {
+        target
+    }
+}
+
+pub async fn at_start(selection: &Selection, early: Duration) -> Result<Meeting, Box<dyn Error>> {
+    let mut stderr = std::io::stderr();
+
+    loop {
+        let today = Local::now().date_naive();
+        let meetings = meetings::retrieve_agenda(today, today).await?;
+        let meeting = select(selection, &meetings, Local::now())?
+            .filter(|m| m.get_link().is_some())
+            .ok_or("No meeting to join")?;
+        let target = meeting
+            .start()?
+            .checked_sub_signed(early)
+            .ok_or("--early is too long")?;
+
+        let wake_up = wake_up_at(target, Local::now());
+        while Local::now() < wake_up {
+            write!(
+                stderr,
+                "\r\x1b[K{} in {}",
+                meeting.summary(),
+                format_clock(target - Local::now())
+            )?;
+            stderr.flush()?;
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+
+        if Local::now() >= target {
+            writeln!(stderr, "\r\x1b[KJoining {}", meeting.summary())?;
+            return Ok(meeting);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(summary: &str, start: &str, end: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "summary": summary,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+            "attendees": [{ "self": true, "responseStatus": "accepted" }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn selects_nth_upcoming() {
+        let meetings = [
+            meeting(
+                "Past",
+                "2023-03-01T08:00:00+01:00",
+                "2023-03-01T08:30:00+01:00",
+            ),
+            meeting(
+                "First",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:30:00+01:00",
+            ),
+            meeting(
+                "Second",
+                "2023-03-01T11:00:00+01:00",
+                "2023-03-01T11:30:00+01:00",
+            ),
+        ];
+        let now = "2023-03-01T09:00:00+01:00".parse().unwrap();
+
+        let first = select(&Selection::Nth(1), &meetings, now).unwrap().unwrap();
+        assert_eq!(first.summary(), "First");
+
+        let second = select(&Selection::Nth(2), &meetings, now).unwrap().unwrap();
+        assert_eq!(second.summary(), "Second");
+
+        assert!(select(&Selection::Nth(3), &meetings, now)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn wakes_up_to_repoll_before_target() {
+        let now: DateTime<Local> = "2023-03-01T09:00:00+01:00".parse().unwrap();
+
+        let target = now + Duration::minutes(30);
+        assert_eq!(wake_up_at(target, now), now + Duration::minutes(5));
+
+        let target = now + Duration::minutes(4);
+        assert_eq!(wake_up_at(target, now), now + Duration::minutes(3));
+
+        let target = now + Duration::seconds(90);
+        assert_eq!(wake_up_at(target, now), target);
+    }
+}
@@ -0,0 +1,166 @@
+// The library is the Client, meetings and tokens; the other public modules are there for the
+// nextmeet binary only and may change in any release
+#[cfg(not(test))]
+pub(crate) mod config;
+
+#[cfg(test)]
+pub(crate) mod config {
+    pub const EMAIL: &str = "my-email@example.org";
+    pub const CLIENT_ID: &str = "some_client_id";
+    pub const CLIENT_SECRET: &str = "client_secret";
+}
+
+pub mod tokens;
+
+pub mod client;
+
+#[cfg(feature = "ffi")]
+pub(crate) mod ffi;
+
+pub mod meetings;
+
+#[doc(hidden)]
+pub mod output;
+
+#[doc(hidden)]
+pub mod export;
+
+#[doc(hidden)]
+pub mod template;
+
+#[doc(hidden)]
+pub mod open;
+
+#[doc(hidden)]
+pub mod join;
+
+#[doc(hidden)]
+pub mod duration;
+
+#[doc(hidden)]
+pub mod clock;
+
+#[doc(hidden)]
+pub mod help;
+
+#[doc(hidden)]
+pub mod version;
+
+#[doc(hidden)]
+pub mod settings;
+
+pub(crate) mod hooks;
+
+#[doc(hidden)]
+pub mod watch;
+
+#[cfg(unix)]
+pub(crate) mod discord;
+
+#[cfg(feature = "mqtt")]
+pub(crate) mod mqtt;
+
+pub(crate) mod webhooks;
+
+#[cfg(feature = "email")]
+pub(crate) mod email;
+
+#[doc(hidden)]
+pub mod notifier;
+
+pub(crate) mod ntfy;
+
+pub(crate) mod pushover;
+
+pub(crate) mod gotify;
+
+pub(crate) mod telegram;
+
+pub(crate) mod matrix;
+
+#[cfg(feature = "desktop")]
+#[doc(hidden)]
+pub mod desktop;
+
+#[cfg(feature = "desktop")]
+pub(crate) mod sound;
+
+#[doc(hidden)]
+pub mod server;
+
+#[cfg(feature = "grpc")]
+#[doc(hidden)]
+pub mod grpc;
+
+pub(crate) mod metrics;
+
+pub(crate) mod push;
+
+#[doc(hidden)]
+pub mod native;
+
+#[cfg(feature = "tui")]
+#[doc(hidden)]
+pub mod tui;
+
+#[cfg(feature = "tui")]
+#[doc(hidden)]
+pub mod picker;
+
+#[cfg(feature = "tui")]
+#[doc(hidden)]
+pub mod countdown;
+
+#[doc(hidden)]
+pub mod cache;
+
+#[doc(hidden)]
+pub mod store;
+
+#[doc(hidden)]
+pub mod diff;
+
+#[doc(hidden)]
+pub mod stats;
+
+#[doc(hidden)]
+pub mod digest;
+
+#[doc(hidden)]
+pub mod slots;
+
+pub(crate) mod policy;
+
+pub(crate) mod snooze;
+
+#[doc(hidden)]
+pub mod schedule;
+
+#[doc(hidden)]
+pub mod notefile;
+
+pub(crate) mod http;
+
+#[doc(hidden)]
+pub mod orgsync;
+
+#[doc(hidden)]
+pub mod vdir;
+
+#[doc(hidden)]
+pub mod calendar;
+
+#[cfg(unix)]
+#[doc(hidden)]
+pub mod socket;
+
+#[cfg(unix)]
+#[doc(hidden)]
+pub mod systemd;
+
+pub use client::Client;
+pub use client::FileStore;
+pub use client::TokenStore;
+pub use meetings::Meeting;
+pub use meetings::Ongoing;
+pub use tokens::Tokens;
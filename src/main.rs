@@ -1,16 +1,111 @@
-#[cfg(not(test))]
-mod config;
-
-#[cfg(test)]
-mod config {
-    pub const EMAIL: &str = "my-email@example.org";
-    pub const CLIENT_ID: &str = "some_client_id";
-    pub const CLIENT_SECRET: &str = "client_secret";
+#[cfg(feature = "tui")]
+use nextmeet::countdown;
+#[cfg(feature = "desktop")]
+use nextmeet::desktop;
+#[cfg(feature = "grpc")]
+use nextmeet::grpc;
+#[cfg(feature = "desktop")]
+use nextmeet::notifier;
+#[cfg(feature = "tui")]
+use nextmeet::picker;
+#[cfg(unix)]
+use nextmeet::socket;
+#[cfg(unix)]
+use nextmeet::systemd;
+#[cfg(feature = "tui")]
+use nextmeet::tui;
+use nextmeet::{
+    cache, calendar, clock, diff, digest, duration, export, help, join, meetings, native, notefile,
+    open, orgsync, output, schedule, server, settings, slots, stats, store, template, vdir,
+    version, watch,
+};
+
+use chrono::Datelike;
+
+// "next" stands for the upcoming meeting, "last" for the one that ended most recently,
+// anything else is taken as an event id
+async fn meeting_id(target: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let meeting = match target {
+        "next" => meetings::retrieve_next(false)
+            .await?
+            .ok_or("No upcoming meeting")?,
+        "last" => {
+            let now = clock::now();
+            let today = now.date_naive();
+            let meetings = meetings::retrieve_range(today - chrono::Days::new(7), today).await?;
+            meetings::last_meeting(&meetings, now)
+                .cloned()
+                .ok_or("No recent meeting")?
+        }
+        // pasted from an invitation in chat or mail
+        link if link.starts_with("https://") => return Ok(calendar::event_id_from_link(link)?),
+        _ => return Ok(target.to_string()),
+    };
+    eprintln!("{}", meeting.summary());
+    Ok(meeting.id().ok_or("The meeting has no id")?.to_string())
+}
+
+// Settings are read first, so a bad config fails before the link is opened
+fn open_meeting(meeting: &meetings::Meeting) -> Result<(), Box<dyn std::error::Error>> {
+    let notes_file = settings::Settings::load()?
+        .notes_file
+        .filter(|notes_file| notes_file.on_join);
+    open::open(&meeting.get_link().unwrap_or_default())?;
+    store::record_join(meeting);
+    if let Some(notes_file) = notes_file {
+        println!("{}", notefile::create(&notes_file, meeting)?.display());
+    }
+    Ok(())
+}
+
+#[cfg(not(all(feature = "tui", feature = "desktop", feature = "grpc")))]
+fn unsupported(feature: &str) -> Box<dyn std::error::Error> {
+    format!("This command needs the {feature} feature, rebuild with --features {feature}").into()
 }
 
-mod tokens;
+fn confirm(question: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    use std::io::BufRead;
+    use std::io::Write;
 
-mod meetings;
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn stale(options: &mut output::Options) {
+    if let Some(fetched_at) = cache::stale() {
+        eprintln!(
+            "Warning: offline, showing the agenda fetched at {}",
+            fetched_at.format("%d/%m %H:%M")
+        );
+        options.stale = true;
+    }
+}
+
+const BLOCK_DAYS: u64 = 7;
+
+// length of created events without --duration
+const DEFAULT_MINUTES: i64 = 30;
+
+// The first slot of working hours this week with nothing in it
+async fn free_slot(
+    length: chrono::Duration,
+) -> Result<
+    (
+        chrono::DateTime<chrono::Local>,
+        chrono::DateTime<chrono::Local>,
+    ),
+    Box<dyn std::error::Error>,
+> {
+    let now = chrono::Local::now();
+    let hours = settings::Settings::load()?.working_hours.bounds()?;
+    let today = now.date_naive();
+    let meetings = meetings::retrieve_range(today, today + chrono::Days::new(BLOCK_DAYS)).await?;
+    let free = slots::working_free(&meetings, now, BLOCK_DAYS, hours);
+    Ok(slots::find(&free, length).ok_or("No free slot that long this week")?)
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -20,16 +115,920 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut machine_full = false;
     let mut additional_links = false;
     let mut all_meets = false;
+    let mut options = output::Options::default();
+    let mut export = false;
+    let mut export_format = None;
+    let mut from = clock::now().date_naive();
+    let mut to = None;
+    let mut accepted_only = false;
+    let mut with_link = false;
+    let mut template = None;
+    let mut qr = false;
+    let mut join = None;
+    let mut at_start = false;
+    let mut early = chrono::Duration::zero();
+    let mut watch = false;
+    let mut notify = false;
+    let mut listen = None;
+    let mut grpc_listen = None;
+    let mut serve = false;
+    let mut native_host = false;
+    let mut query = None;
+    let mut tui = false;
+    let mut pick = false;
+    let mut countdown = false;
+    let mut status_now = false;
+    let mut free_at = false;
+    let mut org_sync = false;
+    let mut vdir_sync = false;
+    let mut dir: Option<String> = None;
+    let mut file: Option<String> = None;
+    let mut schedule_reminders = false;
+    let mut event: Option<String> = None;
+    let mut starts = None;
+    let mut digest = false;
+    let mut gap = false;
+    let mut between: Option<(String, String)> = None;
+    let mut cache_command = None;
+    let mut show_diff = false;
+    let mut history = false;
+    let mut since = chrono::Duration::days(7);
+    let mut stats = false;
+    let mut month = false;
+    let mut cost = false;
+    let mut hide = None;
+    let mut note = None;
+    #[cfg(feature = "tui")]
+    let mut open_link = false;
+    let mut daemon_command = None;
+    let mut rsvp = None;
+    let mut series = false;
+    let mut add = None;
+    let mut at = None;
+    let mut length = None;
+    let mut title = None;
+    let mut instant = false;
+    let mut propose = None;
+    let mut block = false;
+    let mut ooo = false;
+    let mut location = None;
+    let mut followup = None;
+    let mut reschedule = None;
+    let mut cancel = None;
+    let mut guests = None;
+    let mut remind = None;
+    let mut annotate = None;
+    let mut notify_attendees = false;
+    let mut yes = false;
+    let mut decline_matching = None;
+    let mut dry_run = false;
+    let mut offset = chrono::Duration::weeks(1);
+    let mut auto_decline = false;
+    let mut invite = vec![];
+    let mut comment = None;
+
+    let mut man = false;
+    let mut show_version = false;
+    let mut verbose = false;
+
+    // before parsing, so `nextmeet rsvp --help` doesn't fail on the missing meeting
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    if argv.first().is_some_and(|arg| arg == "help")
+        || argv.iter().any(|arg| arg == "--help" || arg == "-h")
+    {
+        let command = argv.iter().find_map(|arg| help::command(arg));
+        print!("{}", help::render(command));
+        std::process::exit(0);
+    }
+
+    // before parsing, since `week` and the default --from depend on it
+    if let Some(at) = argv.iter().position(|arg| arg == "--now") {
+        let at = argv.get(at + 1).ok_or("Missing value for --now")?;
+        clock::set(clock::parse(at, chrono::Local::now())?);
+    }
+
+    let mut args = argv.into_iter().peekable();
+    while let Some(opt) = args.next() {
+        match opt.as_str() {
+            "-m" => only_link = true,
+            "-d" => {
+                debug = true;
+                meetings::set_debug();
+            }
+            "-j" => json = true,
+            "-mf" => machine_full = true,
+            "-al" => additional_links = true,
+            "-a" => all_meets = true,
+            "--output" => {
+                options.format = args.next().ok_or("Missing value for --output")?.parse()?
+            }
+            "--max-width" => {
+                options.max_width = Some(
+                    args.next()
+                        .ok_or("Missing value for --max-width")?
+                        .parse()?,
+                )
+            }
+            "week" => {
+                let today = clock::now().date_naive();
+                from = today - chrono::Days::new(today.weekday().num_days_from_monday().into());
+                to = Some(from + chrono::Days::new(6));
+                all_meets = true;
+            }
+            "--template" => template = Some(args.next().ok_or("Missing value for --template")?),
+            "--qr" => qr = true,
+            "join" => join = Some(join::Selection::Next),
+            "--nth" => {
+                join = Some(join::Selection::Nth(
+                    args.next().ok_or("Missing value for --nth")?.parse()?,
+                ))
+            }
+            "--pick" => join = Some(join::Selection::Pick),
+            "--at-start" => at_start = true,
+            "--early" => early = duration::parse(&args.next().ok_or("Missing value for --early")?)?,
+            "watch" => watch = true,
+            "notify" => notify = true,
+            "--event" => event = Some(args.next().ok_or("Missing value for --event")?),
+            "--starts" => starts = Some(args.next().ok_or("Missing value for --starts")?),
+            "schedule-reminders" => schedule_reminders = true,
+            "serve" => serve = true,
+            "native-host" => native_host = true,
+            "man" => man = true,
+            "version" | "--version" | "-V" => show_version = true,
+            "--verbose" | "-v" => verbose = true,
+            "tui" => tui = true,
+            "pick" => pick = true,
+            "countdown" => countdown = true,
+            "now" => status_now = true,
+            "free-at" => free_at = true,
+            "gap" => gap = true,
+            "--between" => {
+                let from = args.next().ok_or("Missing value for --between")?;
+                between = Some((from, args.next().ok_or("Missing end for --between")?));
+            }
+            "rsvp" => {
+                let mut first = args.next().ok_or("Missing meeting to respond to")?;
+                if first == "--series" {
+                    series = true;
+                    first = args.next().ok_or("Missing meeting to respond to")?;
+                }
+                let mut second = args.next().ok_or("Missing response")?;
+                if second == "--event-link" {
+                    second = args.next().ok_or("Missing value for --event-link")?;
+                }
+                // both `rsvp <id> decline` and `rsvp decline <id>` read naturally
+                rsvp = Some(match first.parse::<calendar::Rsvp>() {
+                    Ok(response) => (second, response),
+                    Err(_) => (first, second.parse()?),
+                });
+            }
+            "--series" => series = true,
+            "add" => {
+                add = Some(
+                    args.next_if(|arg| !arg.starts_with('-'))
+                        .unwrap_or_default(),
+                )
+            }
+            "--at" => at = Some(args.next().ok_or("Missing value for --at")?),
+            "--duration" => {
+                length = Some(duration::parse(
+                    &args.next().ok_or("Missing value for --duration")?,
+                )?)
+            }
+            "--title" => title = Some(args.next().ok_or("Missing value for --title")?),
+            "instant" => instant = true,
+            "block" => block = true,
+            "ooo" => ooo = true,
+            "location" => {
+                location = Some(
+                    args.next()
+                        .ok_or("Missing location, use home, office or the name of a place")?,
+                )
+            }
+            "move" | "extend" => {
+                let target = args.next().ok_or("Missing meeting to change")?;
+                let by = duration::parse_signed(&args.next().ok_or("Missing offset")?)?;
+                // moving shifts both ends, extending only the end
+                let start = if opt == "move" {
+                    by
+                } else {
+                    chrono::Duration::zero()
+                };
+                reschedule = Some((target, start, by));
+            }
+            "annotate" => {
+                let target = args.next().ok_or("Missing meeting to annotate")?;
+                annotate = Some((target, args.next().ok_or("Missing text to append")?));
+            }
+            "remind" => {
+                let target = args.next().ok_or("Missing meeting to set reminders on")?;
+                let mut offsets = vec![];
+                while let Some(offset) = args.next_if(|arg| !arg.starts_with('-')) {
+                    offsets.push(duration::parse(&offset)?);
+                }
+                if offsets.is_empty() {
+                    return Err("Missing reminder offset".into());
+                }
+                remind = Some((target, offsets));
+            }
+            "invite" => {
+                let target = args.next().ok_or("Missing meeting to invite to")?;
+                let mut emails = vec![];
+                while let Some(email) = args.next_if(|arg| !arg.starts_with('-')) {
+                    emails.push(email);
+                }
+                if emails.is_empty() {
+                    return Err("Missing people to invite".into());
+                }
+                guests = Some((target, emails));
+            }
+            "cancel" => cancel = Some(args.next().ok_or("Missing meeting to cancel")?),
+            "--notify-attendees" => notify_attendees = true,
+            "--yes" | "-y" => yes = true,
+            "decline-matching" => {
+                decline_matching = Some(args.next().ok_or("Missing text to match")?)
+            }
+            "--dry-run" => dry_run = true,
+            "followup" => followup = Some(args.next().ok_or("Missing meeting to follow up")?),
+            "--in" => offset = duration::parse(&args.next().ok_or("Missing value for --in")?)?,
+            "--auto-decline" => auto_decline = true,
+            "propose" => propose = Some(args.next().ok_or("Missing meeting to move")?),
+            "--minutes" => {
+                let minutes: i64 = args.next().ok_or("Missing value for --minutes")?.parse()?;
+                if minutes <= 0 {
+                    return Err("--minutes must be more than 0".into());
+                }
+                length = Some(chrono::Duration::minutes(minutes))
+            }
+            "--invite" => invite.push(args.next().ok_or("Missing value for --invite")?),
+            "--comment" => comment = Some(args.next().ok_or("Missing value for --comment")?),
+            "daemon" => daemon_command = Some(args.next().ok_or("Missing daemon command")?),
+            "cache" => cache_command = Some(args.next().ok_or("Missing cache command")?),
+            "--no-cache" => cache::bypass(),
+            "--dump-response" => {
+                meetings::dump_to(args.next().ok_or("Missing value for --dump-response")?);
+                // a cached answer has no raw payload to dump
+                cache::bypass();
+            }
+            "--ongoing" => {
+                meetings::set_ongoing(args.next().ok_or("Missing value for --ongoing")?.parse()?)
+            }
+            "--now" => {
+                args.next();
+            }
+            "--from-file" => {
+                let path = args.next().ok_or("Missing value for --from-file")?;
+                let response = std::fs::read_to_string(&path)
+                    .map_err(|err| format!("Could not read {path}: {err}"))?;
+                meetings::replay(&response)?;
+            }
+            "--stdin" => {
+                meetings::replay(&std::io::read_to_string(std::io::stdin())?)?;
+            }
+            "diff" => show_diff = true,
+            "history" => history = true,
+            "stats" => stats = true,
+            "digest" => digest = true,
+            "snooze" => {
+                let target = args.next().ok_or("Missing meeting to snooze")?;
+                let length = duration::parse(&args.next().ok_or("Missing snooze duration")?)?;
+                hide = Some((target, Some(length)));
+            }
+            "note" => {
+                let target = args.next().ok_or("Missing meeting to annotate")?;
+                note = Some((target, args.next().unwrap_or_default()));
+            }
+            "dismiss" => hide = Some((args.next().ok_or("Missing meeting to dismiss")?, None)),
+            "--week" => month = false,
+            "--month" => month = true,
+            "--cost" => cost = true,
+            "--since" => since = duration::parse(&args.next().ok_or("Missing value for --since")?)?,
+            #[cfg(feature = "tui")]
+            "--open" => open_link = true,
+            "query" => query = Some(args.next().unwrap_or_else(|| "next".to_string())),
+            "--listen" => listen = Some(args.next().ok_or("Missing value for --listen")?),
+            "--grpc-listen" => {
+                grpc_listen = Some(args.next().ok_or("Missing value for --grpc-listen")?)
+            }
+            "export" => export = true,
+            "org-sync" => org_sync = true,
+            "vdir-sync" => vdir_sync = true,
+            "--dir" => dir = Some(args.next().ok_or("Missing value for --dir")?),
+            "--file" => file = Some(args.next().ok_or("Missing value for --file")?),
+            "--csv" => export_format = Some(export::Format::Csv),
+            "--ics" => export_format = Some(export::Format::Ics),
+            "--taskwarrior" => export_format = Some(export::Format::Taskwarrior),
+            "--accepted" => accepted_only = true,
+            "--with-link" => with_link = true,
+            "--from" => from = args.next().ok_or("Missing value for --from")?.parse()?,
+            "--to" => to = Some(args.next().ok_or("Missing value for --to")?.parse()?),
+            _ => (),
+        }
+    }
+
+    if man {
+        print!("{}", help::man());
+        std::process::exit(0);
+    }
+
+    if show_version {
+        println!("{}", version::render(verbose));
+        std::process::exit(0);
+    }
+
+    #[cfg(not(feature = "tui"))]
+    if tui || pick || countdown {
+        return Err(unsupported("tui"));
+    }
+    #[cfg(not(feature = "desktop"))]
+    if notify || schedule_reminders || event.is_some() || starts.is_some() {
+        return Err(unsupported("desktop"));
+    }
+    #[cfg(not(feature = "grpc"))]
+    if grpc_listen.is_some() {
+        return Err(unsupported("grpc"));
+    }
+
+    if let Some(command) = daemon_command {
+        match command.as_str() {
+            #[cfg(unix)]
+            "install" => {
+                let listen = listen
+                    .or(settings::Settings::load()?.listen)
+                    .unwrap_or_else(|| "127.0.0.1:8686".to_string());
+                for path in systemd::install(&listen)? {
+                    println!("{}", path.display());
+                }
+                eprintln!("Run: systemctl --user daemon-reload && systemctl --user enable --now nextmeet.service");
+            }
+            _ => return Err(format!("Unknown daemon command: {command}").into()),
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(command) = cache_command {
+        match command.as_str() {
+            "clear" => cache::clear()?,
+            _ => return Err(format!("Unknown cache command: {command}").into()),
+        }
+        std::process::exit(0);
+    }
+
+    // free text goes through Google's quick add, --at creates the event as given and a
+    // template fills in the rest, in the first free slot unless --at says when
+    if let Some(text) = add {
+        let title = title.or(Some(text.clone()).filter(|text| !text.is_empty()));
+        let meeting = match (template, at) {
+            (Some(name), at) => {
+                let settings = settings::Settings::load()?;
+                let template = settings
+                    .templates
+                    .get(&name)
+                    .ok_or(format!("No template named {name}"))?;
+                let length = length
+                    .or(template.duration()?)
+                    .unwrap_or(chrono::Duration::minutes(DEFAULT_MINUTES));
+                let (start, end) = match at {
+                    Some(at) => {
+                        let start = duration::parse_at(&at, chrono::Local::now())?;
+                        (start, start + length)
+                    }
+                    None => free_slot(length).await?,
+                };
+                let title = title.or(template.title.clone()).unwrap_or(name);
+                calendar::insert(&calendar::from_template(template, &title, start, end)?).await?
+            }
+            (None, Some(at)) => {
+                let start = duration::parse_at(&at, chrono::Local::now())?;
+                let event = calendar::new_event(
+                    &title.ok_or("Missing --title")?,
+                    start,
+                    start + length.unwrap_or(chrono::Duration::minutes(DEFAULT_MINUTES)),
+                    &[],
+                );
+                calendar::insert(&event).await?
+            }
+            (None, None) if text.is_empty() => return Err("Missing event text".into()),
+            (None, None) => calendar::quick_add(&text).await?,
+        };
+        println!("{}", meeting);
+        std::process::exit(0);
+    }
+
+    if let Some(target) = propose {
+        let start = duration::parse_at(
+            &at.ok_or("Missing --at for the proposed time")?,
+            chrono::Local::now(),
+        )?;
+        let response: calendar::Rsvp = match settings::Settings::load()?.propose.as_deref() {
+            None | Some("decline") => calendar::Rsvp::Decline,
+            Some("tentative") => calendar::Rsvp::Tentative,
+            Some(other) => return Err(format!("Invalid propose setting: {other}").into()),
+        };
+        let id = meeting_id(&target).await?;
+        println!("{}", calendar::propose(&id, start, response).await?);
+        std::process::exit(0);
+    }
+
+    // unlike `note`, this is visible to everyone invited, so only the organizer may write it
+    if let Some((target, text)) = annotate {
+        let id = meeting_id(&target).await?;
+        let event = calendar::event(&id).await?;
+        calendar::organized(&event)?;
+        calendar::patch_silently(&id, &calendar::append_description(&event, &text)).await?;
+        std::process::exit(0);
+    }
+
+    if let Some((target, offsets)) = remind {
+        let id = meeting_id(&target).await?;
+        calendar::patch(&id, &calendar::reminders(&offsets)).await?;
+        std::process::exit(0);
+    }
+
+    if let Some((target, emails)) = guests {
+        let id = meeting_id(&target).await?;
+        let event = calendar::event(&id).await?;
+        calendar::organized(&event)?;
+        let meeting = calendar::update(&id, &calendar::add_attendees(&event, &emails)).await?;
+        println!("{}", meeting.attendees().join(", "));
+        std::process::exit(0);
+    }
+
+    // organizers delete the event, for everyone else cancelling means declining it
+    if let Some(target) = cancel {
+        let id = meeting_id(&target).await?;
+        let event = calendar::event(&id).await?;
+        let organizer = calendar::organized(&event).is_ok();
+        let summary = event["summary"].as_str().unwrap_or("No summary");
+        let question = if organizer {
+            format!("Cancel \"{}\" for everyone?", summary)
+        } else {
+            format!("Decline \"{}\"?", summary)
+        };
+        if !yes && !confirm(&question)? {
+            std::process::exit(1);
+        }
+        if organizer {
+            calendar::delete(&id, notify_attendees).await?;
+        } else {
+            calendar::rsvp(&id, calendar::Rsvp::Decline, comment.as_deref()).await?;
+        }
+        std::process::exit(0);
+    }
+
+    // everything that matches is listed before anything is declined
+    if let Some(pattern) = decline_matching {
+        let meetings = meetings::retrieve_range(from, to.unwrap_or(from)).await?;
+        let matching = meetings::invitations_matching(&meetings, &pattern);
+        if matching.is_empty() {
+            eprintln!("No invitations match \"{}\"", pattern);
+            std::process::exit(0);
+        }
+        for meeting in &matching {
+            println!(
+                "{} {} {}",
+                meeting.start()?.format("%a %d/%m"),
+                output::time_range(meeting),
+                meeting.summary()
+            );
+        }
+        if dry_run {
+            std::process::exit(0);
+        }
+        if !yes && !confirm(&format!("Decline these {} meetings?", matching.len()))? {
+            std::process::exit(1);
+        }
+
+        let mut failed = false;
+        for meeting in matching {
+            let Some(id) = meeting.id() else {
+                continue;
+            };
+            if let Err(err) = calendar::rsvp(id, calendar::Rsvp::Decline, comment.as_deref()).await
+            {
+                eprintln!("Error: {}: {}", meeting.summary(), err);
+                failed = true;
+            }
+        }
+        std::process::exit(if failed { 1 } else { 0 });
+    }
+
+    if let Some((target, start, end)) = reschedule {
+        let id = meeting_id(&target).await?;
+        let event = calendar::event(&id).await?;
+        calendar::organized(&event)?;
+        let meeting = calendar::update(&id, &calendar::shift(&event, start, end)?).await?;
+        println!("{}: {}", meeting.summary(), output::time_range(&meeting));
+        std::process::exit(0);
+    }
+
+    if let Some(target) = followup {
+        let id = meeting_id(&target).await?;
+        let event = calendar::followup_event(&calendar::event(&id).await?, offset)?;
+        println!("{}", calendar::insert(&event).await?);
+        std::process::exit(0);
+    }
+
+    // focus time goes in the first free slot of working hours unless --at says when
+    if block {
+        let length = length.unwrap_or(chrono::Duration::minutes(DEFAULT_MINUTES));
+        let (start, end) = match at {
+            Some(at) => {
+                let start = duration::parse_at(&at, chrono::Local::now())?;
+                (start, start + length)
+            }
+            None => free_slot(length).await?,
+        };
+        let title = title.as_deref().unwrap_or("Focus time");
+        let meeting =
+            calendar::insert(&calendar::focus_event(title, start, end, auto_decline)).await?;
+        println!("{}: {}", meeting.summary(), output::time_range(&meeting));
+        std::process::exit(0);
+    }
+
+    // whole days, from the start of --from to the end of --to
+    if ooo {
+        let to = to.ok_or("Missing value for --to")?;
+        let start = slots::at(from, chrono::NaiveTime::MIN).ok_or("Invalid --from")?;
+        let end =
+            slots::at(to + chrono::Days::new(1), chrono::NaiveTime::MIN).ok_or("Invalid --to")?;
+        if end <= start {
+            return Err("--to is before --from".into());
+        }
+        let title = title.as_deref().unwrap_or("Out of office");
+        let meeting = calendar::insert(&calendar::ooo_event(title, start, end)).await?;
+        println!(
+            "{}: {} - {}",
+            meeting.summary(),
+            start.format("%d/%m"),
+            to.format("%d/%m")
+        );
+        std::process::exit(0);
+    }
+
+    // one working location a day: today's is updated if there is one already
+    if let Some(location) = location {
+        let today = chrono::Local::now().date_naive();
+        let meetings = meetings::retrieve_range(today, today).await?;
+        let current = meetings::working_locations(&meetings, today)
+            .into_iter()
+            .find(|meeting| meeting.organized())
+            .and_then(|meeting| meeting.id());
+        let meeting = match current {
+            Some(id) => calendar::patch(id, &calendar::working_location(&location)).await?,
+            None => calendar::insert(&calendar::location_event(&location, today)).await?,
+        };
+        println!(
+            "Working from {} today",
+            meeting.working_location().unwrap_or(location)
+        );
+        std::process::exit(0);
+    }
+
+    if instant {
+        let now = chrono::Local::now();
+        let title = title.as_deref().unwrap_or("Quick meeting");
+        let event = calendar::new_event(
+            title,
+            now,
+            now + length.unwrap_or(chrono::Duration::minutes(DEFAULT_MINUTES)),
+            &invite,
+        );
+        let link = calendar::insert(&event)
+            .await?
+            .get_link()
+            .ok_or("Google did not create a Meet link")?;
+        println!("{}", link);
+        if let Err(err) = open::copy(&link) {
+            eprintln!("Warning: could not copy the link: {}", err);
+        }
+        std::process::exit(0);
+    }
+
+    if let Some((target, response)) = rsvp.as_ref().filter(|_| series) {
+        if *response != calendar::Rsvp::Decline {
+            return Err("Only declining a whole series is supported".into());
+        }
+        let id = meeting_id(target).await?;
+        let count = calendar::decline_series(&id, comment.as_deref()).await?;
+        println!("Declined {} upcoming meetings", count);
+        std::process::exit(0);
+    }
 
-    std::env::args().skip(1).for_each(|opt| match opt.as_str() {
-        "-m" => only_link = true,
-        "-d" => debug = true,
-        "-j" => json = true,
-        "-mf" => machine_full = true,
-        "-al" => additional_links = true,
-        "-a" => all_meets = true,
-        _ => (),
-    });
+    if let Some((target, response)) = rsvp {
+        let id = meeting_id(&target).await?;
+        let meeting = calendar::rsvp(&id, response, comment.as_deref()).await?;
+        println!("{}: {}", meeting.summary(), response.status());
+        std::process::exit(0);
+    }
+
+    if let Some((target, text)) = note {
+        let id = meeting_id(&target).await?;
+        store::Store::open()?.set_note(&id, &text)?;
+        std::process::exit(0);
+    }
+
+    // a snooze hides the meeting for a while, dismissing hides it for good
+    if let Some((target, length)) = hide {
+        let until = length.map(|length| clock::now() + length);
+        let id = meeting_id(&target).await?;
+        let store = store::Store::open()?;
+        store.set_override(&id, until)?;
+        store.prune_overrides(clock::now())?;
+        std::process::exit(0);
+    }
+
+    if stats {
+        let today = clock::now().date_naive();
+        let (from, to) = if month {
+            let first = today.with_day(1).ok_or("Invalid date")?;
+            (first, first + chrono::Months::new(1) - chrono::Days::new(1))
+        } else {
+            let monday = today - chrono::Days::new(today.weekday().num_days_from_monday().into());
+            (monday, monday + chrono::Days::new(6))
+        };
+        let settings = settings::Settings::load()?;
+        let hours = settings.working_hours.bounds()?;
+        let meetings = meetings::retrieve_range(from, to).await?;
+        let rate = cost.then_some(&settings.cost);
+        println!("{}", stats::compute(&meetings, from, to, hours, rate));
+        std::process::exit(0);
+    }
+
+    if digest {
+        let meetings = meetings::retrieve_range(from, to.unwrap_or(from)).await?;
+        println!("{}", digest::compute(&meetings));
+        std::process::exit(0);
+    }
+
+    if history {
+        let since = clock::now()
+            .checked_sub_signed(since)
+            .ok_or("--since is too long")?;
+        let entries = store::Store::open()?.history(since)?;
+        for entry in entries {
+            println!("{}", entry);
+        }
+        std::process::exit(0);
+    }
+
+    if show_diff {
+        let to = to.unwrap_or(from);
+        let store = store::Store::open()?;
+        let snapshot = store.fetched_at(from, to)?;
+        let before = store.meetings(from, to)?;
+
+        cache::bypass();
+        let after = meetings::retrieve_range(from, to).await?;
+        if cache::stale().is_some() {
+            return Err("Could not fetch the current agenda".into());
+        }
+        if snapshot.is_none() {
+            eprintln!("No previous snapshot, saved the current agenda");
+            std::process::exit(0);
+        }
+
+        let changes = diff::diff(&before, &after);
+        if !changes.is_empty() {
+            println!("{}", diff::render(&changes));
+        }
+        std::process::exit(0);
+    }
+
+    // the browser passes the extension's origin as an argument, which is ignored
+    if native_host {
+        native::run().await?;
+        std::process::exit(0);
+    }
+
+    #[cfg(unix)]
+    if let Some(query) = query {
+        let response = match socket::query(&query).await {
+            Ok(response) => response,
+            Err(_) => {
+                let today = clock::now().date_naive();
+                let meetings = meetings::retrieve_agenda(today, today).await?;
+                socket::answer(&query, &meetings, clock::now())?
+            }
+        };
+        if !response.is_empty() {
+            println!("{response}");
+        }
+        std::process::exit(0);
+    }
+
+    #[cfg(feature = "tui")]
+    if tui {
+        tui::run().await?;
+        std::process::exit(0);
+    }
+
+    // exits 0 while busy and 1 when free, so it can drive `if nextmeet now; then ...`
+    if status_now {
+        let today = clock::now().date_naive();
+        let meetings: Vec<_> = match meetings::retrieve_range(today, today).await {
+            Ok(meetings) => meetings.into_iter().filter(|m| m.accepted()).collect(),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(2);
+            }
+        };
+        let meeting = meetings::ongoing_meeting(&meetings, clock::now());
+        println!("{}", output::now(meeting));
+        std::process::exit(if meeting.is_some() { 0 } else { 1 });
+    }
+
+    // back-to-back and overlapping meetings make one block, like the answer to "when are you free?"
+    if free_at {
+        let today = clock::now().date_naive();
+        let meetings = meetings::retrieve_range(today, today).await?;
+        println!(
+            "{}",
+            output::free_at(slots::free_at(&meetings, clock::now()))
+        );
+        std::process::exit(0);
+    }
+
+    // within working hours unless --between says otherwise, and never before now
+    if gap {
+        let now = clock::now();
+        let (from, to) = match between {
+            Some((from, to)) => {
+                let time = |time: &str| {
+                    chrono::NaiveTime::parse_from_str(time, "%H:%M")
+                        .map_err(|_| format!("Invalid time: {time}"))
+                };
+                (time(&from)?, time(&to)?)
+            }
+            None => settings::Settings::load()?.working_hours.bounds()?,
+        };
+        let today = now.date_naive();
+        let window = slots::at(today, from)
+            .zip(slots::at(today, to))
+            .ok_or("Invalid window")?;
+        let meetings = meetings::retrieve_range(today, today).await?;
+        let free = slots::free(&slots::busy(&meetings), window.0.max(now), window.1);
+        match slots::largest(&free) {
+            Some(largest) => println!("{}", output::gap(largest)),
+            None => {
+                eprintln!(
+                    "No free time between {} and {}",
+                    from.format("%H:%M"),
+                    to.format("%H:%M")
+                );
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
+    }
+
+    #[cfg(feature = "tui")]
+    if countdown {
+        countdown::run().await?;
+        std::process::exit(0);
+    }
+
+    #[cfg(feature = "tui")]
+    if pick {
+        let meetings = meetings::retrieve_agenda(from, to.unwrap_or(from)).await?;
+        match picker::pick(&meetings)?.filter(|m| m.get_link().is_some()) {
+            Some(meeting) if open_link => {
+                open_meeting(meeting)?;
+            }
+            Some(meeting) => println!("{}", meeting.get_link().unwrap_or_default()),
+            None => std::process::exit(1),
+        }
+        std::process::exit(0);
+    }
+
+    if serve {
+        let settings = settings::Settings::load()?;
+        let listen = listen
+            .or(settings.listen)
+            .unwrap_or_else(|| "127.0.0.1:8686".to_string());
+        let shared = server::Shared::default();
+        server::spawn(&listen, shared.clone())?;
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_listen) = grpc_listen.or(settings.grpc_listen) {
+            grpc::spawn(&grpc_listen, shared.clone())?;
+            eprintln!("Listening for gRPC on {grpc_listen}");
+        }
+        #[cfg(unix)]
+        socket::spawn(shared)?;
+        #[cfg(unix)]
+        systemd::notify("READY=1");
+        eprintln!("Listening on http://{listen}");
+        tokio::signal::ctrl_c().await?;
+        std::process::exit(0);
+    }
+
+    if watch {
+        let mut settings = settings::Settings::load()?;
+        settings.listen = listen.or(settings.listen);
+        watch::run(settings).await?;
+    }
+
+    #[cfg(feature = "desktop")]
+    // --event is how schedule-reminders' jobs call back, without anyone to log in; a meeting
+    // cancelled or moved away from --starts since is skipped
+    if notify {
+        let meeting = match event {
+            Some(id) => {
+                let event = calendar::event_unattended(&meeting_id(&id).await?).await?;
+                if event["status"] == "cancelled" {
+                    std::process::exit(0);
+                }
+                let meeting: meetings::Meeting = serde_json::from_value(event)?;
+                if let Some(starts) = starts {
+                    let starts = duration::parse_at(&starts, chrono::Local::now())?;
+                    if meeting.start()? != starts {
+                        eprintln!("{} moved, no reminder", meeting.summary());
+                        std::process::exit(0);
+                    }
+                }
+                meeting
+            }
+            None => meetings::retrieve_next(debug)
+                .await?
+                .ok_or("No upcoming meeting")?,
+        };
+        let until_start = meeting.start()? - chrono::Local::now();
+        let event = if until_start > chrono::Duration::zero() {
+            watch::Event::Before(until_start)
+        } else {
+            watch::Event::Started
+        };
+        let reminder = notifier::Reminder::new(event, &meeting);
+        desktop::remind(&reminder, std::time::Duration::from_secs(5 * 60))?;
+        std::process::exit(0);
+    }
+
+    // for machines without a running watch: today's reminders become systemd timers or at jobs
+    if schedule_reminders {
+        let now = clock::now();
+        let mut before = settings::Settings::load()?.reminder_offsets()?;
+        if before.is_empty() {
+            before.push(chrono::Duration::minutes(schedule::DEFAULT_BEFORE_MINUTES));
+        }
+        let today = now.date_naive();
+        let meetings = meetings::retrieve_agenda(today, today).await?;
+        let scheduler = schedule::Scheduler::detect()?;
+        let exe = std::env::current_exe()?;
+        let exe = exe.to_string_lossy();
+        for (job, offset) in schedule::jobs(&meetings, now, &before) {
+            if dry_run {
+                println!("{}", schedule::describe(scheduler, &exe, &job, offset));
+                continue;
+            }
+            schedule::schedule(scheduler, &exe, &job, offset)?;
+            println!("{} {}", job.at.format("%H:%M"), job.summary);
+        }
+        std::process::exit(0);
+    }
+
+    // a week from --from unless --to says otherwise; only entries in that range are removed
+    if org_sync {
+        let to = to.unwrap_or(from + chrono::Days::new(BLOCK_DAYS));
+        let meetings: Vec<_> = meetings::retrieve_range(from, to)
+            .await?
+            .into_iter()
+            .filter(|m| m.accepted() && m.start().is_ok())
+            .collect();
+        let path = orgsync::path(file.as_deref());
+        orgsync::write(&path, &meetings, from, to)?;
+        println!("{}", path.display());
+        std::process::exit(0);
+    }
+
+    if vdir_sync {
+        let dir = dir
+            .or(settings::Settings::load()?.vdir)
+            .ok_or("Missing vdir, use --dir or set vdir")?;
+        let to = to.unwrap_or(from + chrono::Days::new(BLOCK_DAYS));
+        let meetings = meetings::retrieve_range(from, to).await?;
+        let dir = notefile::expand(&dir);
+        let changes = vdir::mirror(&dir, &meetings, from, to, clock::now())?;
+        eprintln!(
+            "{}: {} written, {} removed",
+            dir.display(),
+            changes.written,
+            changes.removed
+        );
+        std::process::exit(0);
+    }
+
+    if export {
+        let format =
+            export_format.ok_or("Missing export format, use --csv, --ics or --taskwarrior")?;
+        let meetings: Vec<_> = meetings::retrieve_range(from, to.unwrap_or(from))
+            .await?
+            .into_iter()
+            .filter(|m| !accepted_only || m.accepted())
+            .filter(|m| !with_link || m.get_link().is_some())
+            .collect();
+        println!("{}", export::export(format, &meetings, clock::now()));
+        std::process::exit(0);
+    }
 
     if json {
         match meetings::json().await {
@@ -45,59 +1044,128 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     if machine_full {
-        let tokens = tokens::Tokens::load();
-
-        if let Ok(tokens) = tokens.and_then(|t| t.refresh()) {
-            let result = meetings::retrieve_with_tokens(false, tokens)
-                .await?
-                .map(|m| serde_json::to_string(&m).unwrap())
-                .unwrap_or_else(String::new);
+        match meetings::retrieve_today_unattended().await {
+            Ok(day) => {
+                let now = clock::now();
+                let result = meetings::upcoming(&day, now)
+                    .map(|m| {
+                        let mut value = serde_json::json!(m);
+                        value["recompute_at"] =
+                            meetings::recompute_at(&day, now).to_rfc3339().into();
+                        value["stale"] = cache::stale().is_some().into();
+                        value.to_string()
+                    })
+                    .unwrap_or_default();
 
-            println!("{result}");
-            std::process::exit(0);
+                println!("{result}");
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
         }
-
-        eprintln!("Error: Could not refresh tokens");
-        std::process::exit(1);
     }
 
     if additional_links {
-        let tokens = tokens::Tokens::load();
+        match meetings::retrieve_unattended().await {
+            Ok(meeting) => {
+                let result = meeting
+                    .map(|m| m.get_other_links().join(" "))
+                    .unwrap_or_else(String::new);
 
-        if let Ok(tokens) = tokens.and_then(|t| t.refresh()) {
-            let result = meetings::retrieve_with_tokens(false, tokens)
-                .await?
-                .map(|m| m.get_other_links().join(" "))
-                .unwrap_or_else(String::new);
+                println!("{result}");
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
 
-            println!("{result}");
-            std::process::exit(0);
+    if let Some(selection) = join.as_ref().filter(|_| at_start) {
+        let meeting = join::at_start(selection, early).await?;
+        open_meeting(&meeting)?;
+        std::process::exit(0);
+    }
+
+    if let Some(selection) = join {
+        let meetings = meetings::retrieve_agenda(from, to.unwrap_or(from)).await?;
+        match join::select(&selection, &meetings, clock::now())?.filter(|m| m.get_link().is_some())
+        {
+            Some(meeting) => {
+                open_meeting(&meeting)?;
+                std::process::exit(0);
+            }
+            None => {
+                eprintln!("No meeting to join");
+                std::process::exit(1);
+            }
         }
+    }
 
-        eprintln!("Error: Could not refresh tokens");
-        std::process::exit(1);
+    if let Some(template) = template {
+        let meetings = meetings::retrieve_agenda(from, to.unwrap_or(from)).await?;
+        println!(
+            "{}",
+            template::render_file(&template, &meetings, clock::now())?
+        );
+        std::process::exit(0);
+    }
+
+    if cost
+        && !matches!(
+            options.format,
+            output::Format::Text | output::Format::Ndjson
+        )
+    {
+        return Err("--cost works with the text and ndjson outputs only".into());
+    }
+    if cost {
+        options.cost = Some(settings::Settings::load()?.cost);
+    }
+
+    if options.format.is_agenda() {
+        let meetings = meetings::retrieve_range(from, to.unwrap_or(from)).await?;
+        options.locations = output::locations(&meetings, from);
+        let meetings = meetings::agenda(meetings);
+        stale(&mut options);
+        println!("{}", output::agenda(&options, &meetings, clock::now()));
+        std::process::exit(0);
     }
 
     if all_meets {
-        for meet in meetings::retrieve_all().await? {
-            println!("{}\n", meet);
+        let meetings = meetings::retrieve_range(from, to.unwrap_or(from)).await?;
+        options.locations = output::locations(&meetings, from);
+        let meetings = meetings::agenda(meetings);
+        stale(&mut options);
+        let list = output::list(&options, &meetings, clock::now());
+        if !list.is_empty() {
+            println!("{list}");
         }
         std::process::exit(0);
     }
 
-    let meeting = meetings::retrieve(debug).await?;
+    let day = meetings::retrieve_today(debug).await?;
+    let meeting = meetings::upcoming(&day, clock::now()).cloned();
+    options.day = day;
+    stale(&mut options);
 
-    if only_link {
-        meeting.and_then(|m| m.get_link()).map(|link| {
+    if qr {
+        if let Some(link) = meeting.and_then(|m| m.get_link()) {
+            println!("{}", output::qr(&link)?);
+            std::process::exit(0);
+        }
+        std::process::exit(1);
+    } else if only_link {
+        if let Some(link) = meeting.and_then(|m| m.get_link()) {
             println!("{}", link);
             std::process::exit(0);
-        });
+        }
         std::process::exit(1);
     } else {
-        match meeting {
-            None => println!("Non ci sono appuntamenti"),
-            Some(meeting) => println!("{}", meeting),
-        };
+        println!("{}", output::next(&options, meeting.as_ref(), clock::now()));
     }
 
     Ok(())
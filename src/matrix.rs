@@ -0,0 +1,104 @@
+use super::notifier::Notifier;
+use super::notifier::Reminder;
+use super::settings;
+use async_trait::async_trait;
+use std::error::Error;
+
+pub struct Matrix {
+    settings: settings::Matrix,
+}
+
+impl Matrix {
+    pub fn new(settings: settings::Matrix) -> Matrix {
+        Matrix { settings }
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn message(reminder: &Reminder) -> serde_json::Value {
+    let mut body = format!("{}: {}", reminder.title, reminder.message);
+    let mut formatted_body = format!(
+        "<b>{}</b>: {}",
+        html_escape(&reminder.title),
+        html_escape(&reminder.message)
+    );
+    if let Some(link) = &reminder.link {
+        body.push_str(&format!(" {link}"));
+        formatted_body.push_str(&format!(r#" <a href="{}">Join</a>"#, html_escape(link)));
+    }
+
+    serde_json::json!({
+        "msgtype": "m.text",
+        "body": body,
+        "format": "org.matrix.custom.html",
+        "formatted_body": formatted_body,
+    })
+}
+
+fn transaction_id() -> String {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| format!("nextmeet-{}", d.as_nanos()))
+        .unwrap_or_default()
+}
+
+#[async_trait(?Send)]
+impl Notifier for Matrix {
+    fn name(&self) -> &'static str {
+        "matrix"
+    }
+
+    fn before(&self) -> &[String] {
+        &self.settings.before
+    }
+
+    async fn send(&self, reminder: &Reminder) -> Result<(), Box<dyn Error>> {
+        let url = format!(
+            "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+            self.settings.homeserver.trim_end_matches('/'),
+            urlencoding::encode(&self.settings.room_id),
+            transaction_id()
+        );
+
+        crate::http::client()
+            .put(url)
+            .bearer_auth(&self.settings.access_token)
+            .header("Content-Type", "application/json")
+            .body(message(reminder).to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatted_message() {
+        let reminder = Reminder {
+            title: "R&D sync".to_string(),
+            message: "Starts at 10:00 (in 5 minutes)".to_string(),
+            link: Some("https://meet.google.com/aaa-bbbb-ccc".to_string()),
+            ..Default::default()
+        };
+
+        let result = message(&reminder);
+        assert_eq!(
+            result["body"],
+            "R&D sync: Starts at 10:00 (in 5 minutes) https://meet.google.com/aaa-bbbb-ccc"
+        );
+        assert_eq!(
+            result["formatted_body"],
+            r#"<b>R&amp;D sync</b>: Starts at 10:00 (in 5 minutes) <a href="https://meet.google.com/aaa-bbbb-ccc">Join</a>"#
+        );
+    }
+}
@@ -1,38 +1,106 @@
+use super::cache;
+use super::clock;
+use super::http;
+use super::settings::Settings;
+use super::snooze;
+use super::store;
 use super::tokens::Tokens;
 use chrono::DateTime;
+use chrono::Duration;
 use chrono::Local;
-use reqwest::header;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use futures::StreamExt;
 use serde::ser::SerializeStruct;
 use serde::Deserialize;
 use serde::Serialize;
+use std::cmp::Ordering;
 use std::error::Error;
 use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic;
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
+use std::sync::OnceLock;
 
 use regex::Regex;
 
+static GATHER_LINK: OnceLock<Regex> = OnceLock::new();
+static ZOOM_LINK: OnceLock<Regex> = OnceLock::new();
+static HREF: OnceLock<Regex> = OnceLock::new();
+static DUMP: Mutex<Option<String>> = Mutex::new(None);
+static DEBUG: AtomicBool = AtomicBool::new(false);
+static REPLAY: Mutex<Option<String>> = Mutex::new(None);
+static ONGOING: Mutex<Ongoing> = Mutex::new(Ongoing::Nearest);
+
+// Compiled on first use; watch mode extracts links from every event on each refresh
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
 fn calendar_url(email: &str, time_min: &str, time_max: &str) -> String {
     let time_min = urlencoding::encode(time_min).into_owned();
     let time_max = urlencoding::encode(time_max).into_owned();
     format!("https://www.googleapis.com/calendar/v3/calendars/{email}/events?timeMin={time_min}&timeMax={time_max}&singleEvents=true&showDeleted=false")
 }
 
-#[derive(Deserialize, Clone, Debug, Default)]
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 struct Attendee {
+    email: Option<String>,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
     #[serde(rename = "responseStatus")]
     response_status: String,
     #[serde(rename = "self")]
     #[serde(default)]
     is_self: bool,
+    #[serde(default)]
+    optional: bool,
+    // meeting rooms and other booked resources
+    #[serde(default)]
+    resource: bool,
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+struct Organizer {
+    email: Option<String>,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+    #[serde(rename = "self")]
+    #[serde(default)]
+    is_self: bool,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
 struct MeetTime {
     #[serde(rename = "dateTime")]
     date_time: Option<String>,
+    // all-day events only have a date
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+struct Label {
+    label: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+struct WorkingLocation {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(rename = "officeLocation")]
+    office_location: Option<Label>,
+    #[serde(rename = "customLocation")]
+    custom_location: Option<Label>,
 }
 
-#[derive(Deserialize, Clone, Debug, Default)]
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Meeting {
+    id: Option<String>,
+    #[serde(rename = "iCalUID")]
+    ical_uid: Option<String>,
     summary: Option<String>,
     start: Option<MeetTime>,
     end: Option<MeetTime>,
@@ -41,6 +109,14 @@ pub struct Meeting {
     description: Option<String>,
     #[serde(default)]
     attendees: Vec<Attendee>,
+    organizer: Option<Organizer>,
+    #[serde(rename = "eventType")]
+    event_type: Option<String>,
+    #[serde(rename = "workingLocationProperties")]
+    working_location: Option<WorkingLocation>,
+    // local only, attached from the store after fetching
+    #[serde(skip)]
+    note: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -99,21 +175,31 @@ impl Display for Meeting {
                 .unwrap_or("No end time".to_string()),
             description,
             link
-        )
+        )?;
+        if let Some(note) = &self.note {
+            write!(f, "\nNote: {}", note)?;
+        }
+        Ok(())
     }
 }
 
 impl Meeting {
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    pub fn set_note(&mut self, note: Option<String>) {
+        self.note = note;
+    }
+
     pub fn get_link(&self) -> Option<String> {
         let description_link = self.description.as_ref().and_then(|description| {
-            let gather_link = Regex::new("https://app.gather.town[^\\s\"]*")
-                .unwrap()
-                .find(&description)
+            let gather_link = regex(&GATHER_LINK, "https://app.gather.town[^\\s\"]*")
+                .find(description)
                 .map(|m| m.as_str().into());
 
-            let zoom_link = Regex::new("https://[^\\s\"]*zoom.us[^\\s\"]*")
-                .unwrap()
-                .find(&description)
+            let zoom_link = regex(&ZOOM_LINK, "https://[^\\s\"]*zoom.us[^\\s\"]*")
+                .find(description)
                 .map(|m| m.as_str().into());
 
             gather_link.or(zoom_link)
@@ -123,7 +209,7 @@ impl Meeting {
     }
 
     pub fn get_other_links(&self) -> Vec<String> {
-        let rx = Regex::new("href=\"([^\"]+)").unwrap();
+        let rx = regex(&HREF, "href=\"([^\"]+)");
 
         self.description
             .as_ref()
@@ -135,25 +221,141 @@ impl Meeting {
             .unwrap_or_default()
     }
 
-    fn start(&self) -> Result<DateTime<Local>, Box<dyn Error>> {
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    pub fn ical_uid(&self) -> Option<&str> {
+        self.ical_uid.as_deref().or(self.id.as_deref())
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn summary(&self) -> &str {
+        self.summary.as_deref().unwrap_or("No summary")
+    }
+
+    pub fn organizer(&self) -> Option<&str> {
+        self.organizer
+            .as_ref()
+            .and_then(|organizer| organizer.display_name.as_ref().or(organizer.email.as_ref()))
+            .map(String::as_str)
+    }
+
+    pub fn organizer_email(&self) -> Option<&str> {
+        self.organizer
+            .as_ref()
+            .and_then(|organizer| organizer.email.as_deref())
+    }
+
+    pub fn organized(&self) -> bool {
+        self.organizer
+            .as_ref()
+            .is_some_and(|organizer| organizer.is_self)
+    }
+
+    // "default", "focusTime", "outOfOffice" or "workingLocation"
+    pub fn event_type(&self) -> &str {
+        self.event_type.as_deref().unwrap_or("default")
+    }
+
+    pub fn out_of_office(&self) -> bool {
+        self.event_type() == "outOfOffice"
+    }
+
+    // "home", the office or a custom place, for workingLocation events
+    pub fn working_location(&self) -> Option<String> {
+        let location = self.working_location.as_ref()?;
+        let label = |place: &Option<Label>| {
+            place
+                .as_ref()
+                .and_then(|place| place.label.clone())
+                .filter(|label| !label.is_empty())
+        };
+        match location.kind.as_deref()? {
+            "homeOffice" => Some("home".to_string()),
+            "officeLocation" => {
+                Some(label(&location.office_location).unwrap_or_else(|| "office".to_string()))
+            }
+            "customLocation" => label(&location.custom_location),
+            _ => None,
+        }
+    }
+
+    // First and last day the event covers, all-day events end the day before their end date
+    fn days(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let start = self.start.as_ref()?;
+        let end = self.end.as_ref()?;
+        match (&start.date, &end.date) {
+            (Some(start), Some(end)) => Some((
+                start.parse().ok()?,
+                end.parse::<NaiveDate>().ok()?.pred_opt()?,
+            )),
+            _ => Some((
+                self.start().ok()?.date_naive(),
+                self.end().ok()?.date_naive(),
+            )),
+        }
+    }
+
+    pub fn optional(&self) -> bool {
+        self.attendees
+            .iter()
+            .any(|attendee| attendee.is_self && attendee.optional)
+    }
+
+    pub fn attendees(&self) -> Vec<&str> {
+        self.attendees
+            .iter()
+            .filter_map(|attendee| {
+                attendee
+                    .display_name
+                    .as_deref()
+                    .or(attendee.email.as_deref())
+            })
+            .collect()
+    }
+
+    // Everyone who hasn't declined, me included but not the rooms; an event without guests
+    // is just me
+    pub fn people(&self) -> usize {
+        self.attendees
+            .iter()
+            .filter(|attendee| !attendee.resource && attendee.response_status != "declined")
+            .count()
+            .max(1)
+    }
+
+    pub fn response_status(&self) -> Option<&str> {
+        self.attendees
+            .iter()
+            .find(|attendee| attendee.is_self)
+            .map(|attendee| attendee.response_status.as_str())
+    }
+
+    pub fn start(&self) -> Result<DateTime<Local>, Box<dyn Error>> {
         match &self.start {
             Some(MeetTime {
                 date_time: Some(date_time),
+                ..
             }) => Ok(date_time.parse()?),
             _ => Err("No start time".into()),
         }
     }
 
-    fn end(&self) -> Result<DateTime<Local>, Box<dyn Error>> {
+    pub fn end(&self) -> Result<DateTime<Local>, Box<dyn Error>> {
         match &self.end {
             Some(MeetTime {
                 date_time: Some(date_time),
+                ..
             }) => Ok(date_time.parse()?),
             _ => Err("No end time".into()),
         }
     }
 
-    fn accepted(&self) -> bool {
+    pub fn accepted(&self) -> bool {
         self.attendees
             .iter()
             .any(|attendee| attendee.is_self && attendee.response_status == "accepted")
@@ -162,102 +364,518 @@ impl Meeting {
 
 #[derive(Deserialize)]
 struct Response {
+    #[serde(deserialize_with = "tolerant_items")]
     items: Vec<Meeting>,
 }
 
-fn retrieve_tokens() -> Result<Tokens, Box<dyn Error>> {
-    Ok(Tokens::load()
-        .or_else(|_| Tokens::do_login())?
-        .refresh()
-        .or_else(|_| Tokens::do_login())?)
+// An event Google sends in an unexpected shape is skipped instead of failing the whole agenda
+fn tolerant_items<'de, D>(deserializer: D) -> Result<Vec<Meeting>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let items = Vec::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(from_items(items))
 }
 
-async fn today_meetings_json(token: &str) -> Result<String, Box<dyn Error>> {
-    let now = Local::now().date_naive();
-    let local_timezone = Local::now().timezone();
-    let beginning_of_day = now
-        .and_hms_opt(0, 0, 0)
-        .and_then(|t| t.and_local_timezone(local_timezone).single())
-        .unwrap()
-        .to_rfc3339();
-    let end_of_day = now
-        .and_hms_opt(23, 59, 59)
-        .and_then(|t| t.and_local_timezone(local_timezone).single())
-        .unwrap()
-        .to_rfc3339();
-
-    let mut headers = header::HeaderMap::new();
-    let token = format!("Bearer {token}");
-    headers.insert("Authorization", header::HeaderValue::from_str(&token)?);
+// The events that parse, the others only mentioned with -d so machine outputs stay clean
+pub fn from_items(items: Vec<serde_json::Value>) -> Vec<Meeting> {
+    parse_items(items, DEBUG.load(atomic::Ordering::Relaxed))
+}
 
-    let url = calendar_url(crate::config::EMAIL, &beginning_of_day, &end_of_day);
-    let client = reqwest::Client::builder()
-        .default_headers(headers)
-        .build()?;
+fn parse_items(items: Vec<serde_json::Value>, warn: bool) -> Vec<Meeting> {
+    items
+        .into_iter()
+        .filter_map(|item| {
+            let id = item["id"].as_str().unwrap_or("without id").to_string();
+            serde_json::from_value(item)
+                .map_err(|err| {
+                    if warn {
+                        eprintln!("Warning: skipped event {}: {}", id, err)
+                    }
+                })
+                .ok()
+        })
+        .collect()
+}
 
-    Ok(client.get(url).send().await?.text().await?)
+async fn retrieve_tokens() -> Result<Tokens, Box<dyn Error>> {
+    let tokens = match Tokens::load() {
+        Ok(tokens) => tokens,
+        Err(_) => Tokens::do_login()?,
+    };
+    match tokens.refresh().await {
+        Ok(tokens) => Ok(tokens),
+        Err(_) => Tokens::do_login(),
+    }
 }
 
-async fn today_meetings(token: &str, debug: bool) -> Result<Response, Box<dyn Error>> {
-    let response = today_meetings_json(&token).await?;
-    if debug {
-        println!("{}", response);
+async fn meetings_json(
+    token: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<String, Box<dyn Error>> {
+    if let Some(response) = replayed() {
+        return Ok(response);
     }
 
-    serde_json::from_str::<Response>(&response).map_err(Into::into)
+    let responses = calendars_json(token, from, to).await?;
+    dump(&responses);
+    merge(responses)
 }
 
-fn next_meeting(meetings: &Vec<Meeting>, now: DateTime<Local>) -> Option<&Meeting> {
-    meetings
+// One response per configured calendar, straight from Google
+async fn calendars_json(
+    token: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let (beginning_of_day, end_of_day) = window(from, to, &Local);
+    let beginning_of_day = beginning_of_day.to_rfc3339();
+    let end_of_day = end_of_day.to_rfc3339();
+
+    let calendars = Settings::load()?.calendars();
+    let responses: Vec<String> = futures::stream::iter(calendars)
+        .map(|calendar| {
+            let url = calendar_url(&calendar, &beginning_of_day, &end_of_day);
+            async move {
+                http::client()
+                    .get(url)
+                    .bearer_auth(token)
+                    .send()
+                    .await?
+                    .text()
+                    .await
+            }
+        })
+        .buffered(MAX_CONCURRENT_CALENDARS)
+        .collect::<Vec<_>>()
+        .await
         .into_iter()
+        .collect::<Result<_, _>>()?;
+    Ok(responses)
+}
+
+// Local midnight, or when the clocks skip it the first moment of the day that exists;
+// a midnight that happens twice counts from the first one
+pub fn start_of_day<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> DateTime<Tz> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    // transitions move the clocks by an hour or two at most
+    (0..=12)
+        .find_map(|quarter| {
+            tz.from_local_datetime(&(midnight + Duration::minutes(15 * quarter)))
+                .earliest()
+        })
+        .unwrap_or_else(|| tz.from_utc_datetime(&midnight))
+}
+
+// The days from `from` to `to` as [midnight, next midnight), Google's timeMax being exclusive
+fn window<Tz: TimeZone>(from: NaiveDate, to: NaiveDate, tz: &Tz) -> (DateTime<Tz>, DateTime<Tz>) {
+    (
+        start_of_day(from, tz),
+        start_of_day(to + chrono::Days::new(1), tz),
+    )
+}
+
+// --dump-response: the payloads exactly as Google sent them, one calendar after another
+#[doc(hidden)]
+pub fn dump_to(path: String) {
+    *DUMP.lock().unwrap() = Some(path);
+}
+
+fn dump(responses: &[String]) {
+    if let Some(path) = DUMP.lock().unwrap().as_ref() {
+        if let Err(err) = std::fs::write(path, responses.join("\n")) {
+            eprintln!("Warning: could not write {}: {}", path, err);
+        }
+    }
+}
+
+// --from-file and --stdin: a saved response answers instead of Google, as written by
+// --dump-response, so several calendars one after another are merged the same way
+#[doc(hidden)]
+pub fn replay(response: &str) -> Result<(), Box<dyn Error>> {
+    *REPLAY.lock().unwrap() = Some(replay_json(response)?);
+    Ok(())
+}
+
+fn replay_json(response: &str) -> Result<String, Box<dyn Error>> {
+    let responses = serde_json::Deserializer::from_str(response)
+        .into_iter::<serde_json::Value>()
+        .map(|value| value.map(|value| value.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if responses.is_empty() {
+        return Err("Empty response to replay".into());
+    }
+    let response = merge(responses)?;
+    serde_json::from_str::<Response>(&response)?;
+    Ok(response)
+}
+
+fn replayed() -> Option<String> {
+    REPLAY.lock().unwrap().clone()
+}
+
+const MAX_CONCURRENT_CALENDARS: usize = 4;
+
+// Joins the items of every calendar into one response; an event shared between
+// calendars keeps its first copy
+fn merge(mut responses: Vec<String>) -> Result<String, Box<dyn Error>> {
+    if responses.len() == 1 {
+        return Ok(responses.remove(0));
+    }
+
+    let mut ids = std::collections::HashSet::new();
+    let mut items = vec![];
+    for response in responses {
+        let value: serde_json::Value = serde_json::from_str(&response)?;
+        let calendar_items = match value.get("items").and_then(|items| items.as_array()) {
+            Some(calendar_items) => calendar_items,
+            None => return Err(format!("Unexpected calendar response: {response}").into()),
+        };
+        for item in calendar_items {
+            let id = item.get("id").and_then(|id| id.as_str()).map(str::to_owned);
+            if id.map(|id| ids.insert(id)).unwrap_or(true) {
+                items.push(item.clone());
+            }
+        }
+    }
+
+    Ok(serde_json::json!({ "items": items }).to_string())
+}
+
+// -d, which also reports the events that could not be read
+#[doc(hidden)]
+pub fn set_debug() {
+    DEBUG.store(true, atomic::Ordering::Relaxed);
+}
+
+// Meetings starting at the same time come shorter first, then by summary and then by id,
+// so outputs never depend on the order Google lists them in
+pub fn order(a: &Meeting, b: &Meeting) -> Ordering {
+    let length = |meeting: &Meeting| Some(meeting.end().ok()? - meeting.start().ok()?);
+    a.start()
+        .ok()
+        .cmp(&b.start().ok())
+        .then_with(|| length(a).cmp(&length(b)))
+        .then_with(|| a.summary().cmp(b.summary()))
+        .then_with(|| a.id().cmp(&b.id()))
+}
+
+// What to do about a meeting that has already started when looking for the next one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Ongoing {
+    // whichever starts or started closest to now
+    #[default]
+    Nearest,
+    // the ongoing meeting while there is one, the upcoming one otherwise
+    Prefer,
+    // only meetings that haven't started yet
+    Skip,
+    // only meetings already in progress
+    Only,
+}
+
+impl FromStr for Ongoing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Ongoing::Nearest),
+            "prefer" => Ok(Ongoing::Prefer),
+            "skip" => Ok(Ongoing::Skip),
+            "only" => Ok(Ongoing::Only),
+            _ => Err(format!(
+                "Unknown --ongoing: {s}, use nearest, prefer, skip or only"
+            )),
+        }
+    }
+}
+
+// --ongoing, for every command that picks the next meeting
+#[doc(hidden)]
+pub fn set_ongoing(ongoing: Ongoing) {
+    *ONGOING.lock().unwrap() = ongoing;
+}
+
+pub fn next_meeting(meetings: &[Meeting], now: DateTime<Local>) -> Option<&Meeting> {
+    select(meetings, now, *ONGOING.lock().unwrap())
+}
+
+pub fn select(meetings: &[Meeting], now: DateTime<Local>, ongoing: Ongoing) -> Option<&Meeting> {
+    let distance = |meeting: &Meeting| {
+        meeting
+            .start()
+            .map(|st| (st - now).num_seconds().abs())
+            .unwrap()
+    };
+    let closest =
+        |a: &&Meeting, b: &&Meeting| distance(a).cmp(&distance(b)).then_with(|| order(a, b));
+    let (started, upcoming): (Vec<&Meeting>, Vec<&Meeting>) = meetings
+        .iter()
         .filter(|meeting| {
             meeting.get_link().is_some()
                 && meeting.start().is_ok()
                 && meeting.end().map(|se| se > now).unwrap_or(false)
                 && meeting.accepted()
         })
-        .min_by_key(|meeting| {
-            meeting
-                .start()
-                .map(|st| (st - now).num_seconds().abs())
-                .unwrap()
+        .partition(|meeting| meeting.start().is_ok_and(|start| start <= now));
+
+    let started = started.into_iter().min_by(closest);
+    let upcoming = upcoming.into_iter().min_by(closest);
+    match ongoing {
+        Ongoing::Nearest => started.into_iter().chain(upcoming).min_by(closest),
+        Ongoing::Prefer => started.or(upcoming),
+        Ongoing::Skip => upcoming,
+        Ongoing::Only => started,
+    }
+}
+
+pub fn ongoing_meeting(meetings: &[Meeting], now: DateTime<Local>) -> Option<&Meeting> {
+    meetings.iter().find(|meeting| {
+        meeting.start().map(|start| start <= now).unwrap_or(false)
+            && meeting.end().map(|end| end > now).unwrap_or(false)
+    })
+}
+
+pub fn out_of_office(meetings: &[Meeting], now: DateTime<Local>) -> Option<&Meeting> {
+    meetings.iter().find(|meeting| {
+        meeting.out_of_office()
+            && meeting.start().is_ok_and(|start| start <= now)
+            && meeting.end().is_ok_and(|end| end > now)
+    })
+}
+
+// Invitations I have not declined yet whose title contains the text, ignoring case
+pub fn invitations_matching<'a>(meetings: &'a [Meeting], text: &str) -> Vec<&'a Meeting> {
+    let text = text.to_lowercase();
+    meetings
+        .iter()
+        .filter(|meeting| {
+            !meeting.organized()
+                && matches!(meeting.response_status(), Some(status) if status != "declined")
+                && meeting.summary().to_lowercase().contains(&text)
+        })
+        .collect()
+}
+
+// Working location events for the day, mine and those of colleagues whose calendars are read
+pub fn working_locations(meetings: &[Meeting], day: NaiveDate) -> Vec<&Meeting> {
+    meetings
+        .iter()
+        .filter(|meeting| {
+            meeting.event_type() == "workingLocation"
+                && meeting
+                    .days()
+                    .is_some_and(|(first, last)| first <= day && day <= last)
+        })
+        .collect()
+}
+
+// While out of office that is what comes next, not the meetings still on the calendar
+pub fn upcoming(meetings: &[Meeting], now: DateTime<Local>) -> Option<&Meeting> {
+    out_of_office(meetings, now).or_else(|| next_meeting(meetings, now))
+}
+
+// The accepted meeting that ended most recently
+pub fn last_meeting(meetings: &[Meeting], now: DateTime<Local>) -> Option<&Meeting> {
+    meetings
+        .iter()
+        .filter(|meeting| meeting.accepted() && meeting.end().is_ok_and(|end| end <= now))
+        .max_by_key(|meeting| meeting.end().ok())
+}
+
+// Status bars color a meeting this close to its start
+pub const SOON_MINUTES: i64 = 15;
+pub const NOW_MINUTES: i64 = 5;
+
+// The moment a status derived from these meetings can next change: a color threshold, a
+// start, an end or the following midnight, when the day's agenda rolls over
+pub fn recompute_at(meetings: &[Meeting], now: DateTime<Local>) -> DateTime<Local> {
+    let midnight = start_of_day(now.date_naive() + chrono::Days::new(1), &Local);
+
+    meetings
+        .iter()
+        .flat_map(|meeting| {
+            let start = meeting.start().ok();
+            [
+                start.map(|start| start - Duration::minutes(SOON_MINUTES)),
+                start.map(|start| start - Duration::minutes(NOW_MINUTES)),
+                start,
+                meeting.end().ok(),
+            ]
         })
+        .flatten()
+        .filter(|boundary| *boundary > now)
+        .fold(midnight, DateTime::min)
 }
 
-pub async fn retrieve(debug: bool) -> Result<Option<Meeting>, Box<dyn Error>> {
-    let tokens = retrieve_tokens()?;
+// Today's meetings, snoozed ones left out
+pub async fn retrieve_today(debug: bool) -> Result<Vec<Meeting>, Box<dyn Error>> {
+    let today = clock::now().date_naive();
+    let response = range_json(today, today).await?;
+    if debug {
+        eprintln!("{}", response);
+    }
 
-    retrieve_with_tokens(debug, tokens).await
+    let meetings = serde_json::from_str::<Response>(&response)?.items;
+    Ok(snooze::apply(
+        annotate(meetings),
+        &store::overrides(),
+        clock::now(),
+    ))
 }
 
-pub async fn retrieve_all() -> Result<Vec<Meeting>, Box<dyn Error>> {
-    let tokens = retrieve_tokens()?;
-    let meets = today_meetings(&tokens.access_token, false).await?;
-    let mut meets: Vec<_> = meets
-        .items
+// The next actual meeting, for commands that act on it: never the out of office block
+pub async fn retrieve_next(debug: bool) -> Result<Option<Meeting>, Box<dyn Error>> {
+    let meetings = retrieve_today(debug).await?;
+    Ok(next_meeting(&meetings, clock::now()).cloned())
+}
+
+fn annotate(mut meetings: Vec<Meeting>) -> Vec<Meeting> {
+    let notes = store::notes();
+    for meeting in meetings.iter_mut() {
+        let note = meeting.id().and_then(|id| notes.get(id)).cloned();
+        meeting.set_note(note);
+    }
+    meetings
+}
+
+// Never starts the interactive login, falling back to the cache instead
+async fn unattended_json(
+    from: NaiveDate,
+    to: NaiveDate,
+    fetch: cache::Fetch,
+) -> Result<String, Box<dyn Error>> {
+    if let Some(response) = replayed().or_else(|| cache::recent(from, to, fetch)) {
+        return Ok(response);
+    }
+    match fetch_json(from, to).await {
+        Ok(response) => Ok(response),
+        Err(err) => Ok(cache::load(from, to).ok_or(err)?),
+    }
+}
+
+// For machine outputs, where nobody could finish a login
+pub async fn retrieve_today_unattended() -> Result<Vec<Meeting>, Box<dyn Error>> {
+    let today = clock::now().date_naive();
+    let response = unattended_json(today, today, cache::Fetch::Cached).await?;
+
+    let meetings = serde_json::from_str::<Response>(&response)?.items;
+    Ok(snooze::apply(
+        annotate(meetings),
+        &store::overrides(),
+        clock::now(),
+    ))
+}
+
+pub async fn retrieve_unattended() -> Result<Option<Meeting>, Box<dyn Error>> {
+    let meetings = retrieve_today_unattended().await?;
+    Ok(upcoming(&meetings, clock::now()).cloned())
+}
+
+pub fn agenda(meetings: Vec<Meeting>) -> Vec<Meeting> {
+    meetings
         .into_iter()
         .filter(|m| m.start().is_ok() && m.accepted() && m.get_link().is_some())
-        .collect();
-    meets.sort_by_key(|m| m.start().unwrap());
+        .collect()
+}
+
+pub async fn retrieve_agenda(
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<Meeting>, Box<dyn Error>> {
+    Ok(agenda(retrieve_range(from, to).await?))
+}
+
+// For the watch daemon: asks Google every time and never logs in, nobody is there to finish it
+pub async fn retrieve_range_unattended(
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<Meeting>, Box<dyn Error>> {
+    let response = unattended_json(from, to, cache::Fetch::Fresh).await?;
+    let mut meets = serde_json::from_str::<Response>(&response)?.items;
+    meets.sort_by(order);
     Ok(meets)
 }
 
-pub async fn retrieve_with_tokens(
-    debug: bool,
-    tokens: Tokens,
-) -> Result<Option<Meeting>, Box<dyn Error>> {
-    let now = Local::now();
+// The agenda for callers that own stdin and stdout, like the browser's native host
+pub async fn retrieve_agenda_unattended(
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<Meeting>, Box<dyn Error>> {
+    let response = unattended_json(from, to, cache::Fetch::Cached).await?;
+    let mut meets = annotate(serde_json::from_str::<Response>(&response)?.items);
+    meets.sort_by(order);
+    Ok(agenda(meets))
+}
+
+async fn fetch_json(from: NaiveDate, to: NaiveDate) -> Result<String, Box<dyn Error>> {
+    let tokens = Tokens::load()?.refresh().await?;
+    let response = meetings_json(&tokens.access_token, from, to).await?;
+    serde_json::from_str::<Response>(&response)?;
 
-    let today_meetings = today_meetings(&tokens.access_token, debug).await?;
-    let meeting = next_meeting(&today_meetings.items, now).cloned();
-    Ok(meeting)
+    cache::store(from, to, &response);
+    Ok(response)
 }
 
-pub async fn json() -> Result<String, Box<dyn Error>> {
-    let tokens = retrieve_tokens()?;
-    let today_meetings = today_meetings_json(&tokens.access_token).await?;
+// Falls back to the last good response for the range when Google can't be reached
+async fn range_json(from: NaiveDate, to: NaiveDate) -> Result<String, Box<dyn Error>> {
+    if let Some(response) = replayed().or_else(|| cache::recent(from, to, cache::Fetch::Cached)) {
+        return Ok(response);
+    }
+
+    match fetch_json(from, to).await {
+        Ok(response) => Ok(response),
+        Err(_) => match cache::load(from, to) {
+            Some(response) => Ok(response),
+            // no cache to fall back on, so log in again if the tokens are the problem
+            None => {
+                let tokens = retrieve_tokens().await?;
+                meetings_json(&tokens.access_token, from, to).await
+            }
+        },
+    }
+}
 
-    Ok(today_meetings)
+pub async fn retrieve_range(
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<Meeting>, Box<dyn Error>> {
+    let response = range_json(from, to).await?;
+    let mut meets = annotate(serde_json::from_str::<Response>(&response)?.items);
+    meets.sort_by(order);
+    Ok(meets)
+}
+
+pub async fn retrieve_range_with_tokens(
+    tokens: &Tokens,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<Meeting>, Box<dyn Error>> {
+    let response = meetings_json(&tokens.access_token, from, to).await?;
+    let mut meets = serde_json::from_str::<Response>(&response)?.items;
+    meets.sort_by(order);
+    Ok(meets)
+}
+
+// For the library's Client: none of the binary's process-wide switches (--replay,
+// --dump-response, -d) apply
+pub(crate) async fn fetch_range(
+    tokens: &Tokens,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<Meeting>, Box<dyn Error>> {
+    let response = merge(calendars_json(&tokens.access_token, from, to).await?)?;
+    let response = serde_json::from_str::<serde_json::Value>(&response)?;
+    let items = response["items"].as_array().cloned().unwrap_or_default();
+    let mut meets = parse_items(items, false);
+    meets.sort_by(order);
+    Ok(meets)
+}
+
+pub async fn json() -> Result<String, Box<dyn Error>> {
+    let today = clock::now().date_naive();
+    range_json(today, today).await
 }
 
 #[cfg(test)]
@@ -300,6 +918,7 @@ mod tests {
             attendees: vec![Attendee {
                 is_self: true,
                 response_status: "declined".to_string(),
+                ..Default::default()
             }],
             ..Default::default()
         };
@@ -310,6 +929,7 @@ mod tests {
             attendees: vec![Attendee {
                 is_self: true,
                 response_status: "pending".to_string(),
+                ..Default::default()
             }],
             ..Default::default()
         };
@@ -320,6 +940,7 @@ mod tests {
             attendees: vec![Attendee {
                 is_self: true,
                 response_status: "accepted".to_string(),
+                ..Default::default()
             }],
             ..Default::default()
         };
@@ -327,6 +948,23 @@ mod tests {
         assert!(m.accepted());
     }
 
+    #[test]
+    fn organizer_and_response() {
+        let m: Meeting = serde_json::from_str(
+            r#"{
+                "organizer": { "email": "boss@example.org", "displayName": "The Boss" },
+                "attendees": [
+                    { "email": "boss@example.org", "responseStatus": "accepted" },
+                    { "self": true, "responseStatus": "tentative" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(m.organizer(), Some("The Boss"));
+        assert_eq!(m.response_status(), Some("tentative"));
+    }
+
     #[test]
     fn test_other_links_without_description() {
         let m = Meeting::default();
@@ -357,10 +995,327 @@ mod tests {
         };
         let result = m.get_other_links();
         assert_eq!(result.len(), 2);
-        assert_eq!(result.get(0).unwrap().as_str(), "http://some-card-link.ext");
+        assert_eq!(
+            result.first().unwrap().as_str(),
+            "http://some-card-link.ext"
+        );
         assert_eq!(
             result.get(1).unwrap().as_str(),
             "http://some-other-link.ext"
         );
     }
+
+    #[test]
+    fn recomputes_at_next_boundary() {
+        let m: Meeting = serde_json::from_str(
+            r#"{
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:30:00+01:00" }
+            }"#,
+        )
+        .unwrap();
+        let at = |date_time: &str| date_time.parse::<DateTime<Local>>().unwrap();
+
+        // the colors change before the meeting starts
+        assert_eq!(
+            recompute_at(std::slice::from_ref(&m), at("2023-03-01T09:00:00+01:00")),
+            at("2023-03-01T09:45:00+01:00")
+        );
+        assert_eq!(
+            recompute_at(std::slice::from_ref(&m), at("2023-03-01T09:50:00+01:00")),
+            at("2023-03-01T09:55:00+01:00")
+        );
+        assert_eq!(
+            recompute_at(std::slice::from_ref(&m), at("2023-03-01T09:55:00+01:00")),
+            at("2023-03-01T10:00:00+01:00")
+        );
+        assert_eq!(
+            recompute_at(std::slice::from_ref(&m), at("2023-03-01T10:00:00+01:00")),
+            at("2023-03-01T10:30:00+01:00")
+        );
+
+        let now = at("2023-03-01T11:00:00+01:00");
+        let midnight = recompute_at(&[m], now);
+        assert!(midnight > now);
+        assert_eq!(
+            midnight.date_naive(),
+            now.date_naive() + chrono::Days::new(1)
+        );
+    }
+
+    #[test]
+    fn breaks_ties() {
+        let meeting = |id: &str, summary: &str, end: &str| -> Meeting {
+            serde_json::from_value(serde_json::json!({
+                "id": id,
+                "summary": summary,
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": end },
+                "attendees": [{ "self": true, "responseStatus": "accepted" }]
+            }))
+            .unwrap()
+        };
+        let mut meetings = vec![
+            meeting("d", "Sync", "2023-03-01T11:00:00+01:00"),
+            meeting("c", "Sync", "2023-03-01T10:30:00+01:00"),
+            meeting("b", "Sync", "2023-03-01T10:30:00+01:00"),
+            meeting("a", "Review", "2023-03-01T10:30:00+01:00"),
+        ];
+        let now = "2023-03-01T09:00:00+01:00".parse().unwrap();
+        assert_eq!(next_meeting(&meetings, now).and_then(|m| m.id()), Some("a"));
+
+        meetings.sort_by(order);
+        let ids: Vec<_> = meetings.iter().map(|m| m.id().unwrap()).collect();
+        assert_eq!(ids, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn selects_by_ongoing_policy() {
+        let meeting = |id: &str, start: &str, end: &str| -> Meeting {
+            serde_json::from_value(serde_json::json!({
+                "id": id,
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                "start": { "dateTime": start },
+                "end": { "dateTime": end },
+                "attendees": [{ "self": true, "responseStatus": "accepted" }]
+            }))
+            .unwrap()
+        };
+        let meetings = vec![
+            meeting(
+                "running",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T11:00:00+01:00",
+            ),
+            meeting(
+                "later",
+                "2023-03-01T10:45:00+01:00",
+                "2023-03-01T11:30:00+01:00",
+            ),
+        ];
+        let id = |now: &str, ongoing: &str| {
+            select(&meetings, now.parse().unwrap(), ongoing.parse().unwrap()).and_then(|m| m.id())
+        };
+
+        // five minutes in, with the next one 40 minutes away
+        let early = "2023-03-01T10:05:00+01:00";
+        assert_eq!(id(early, "nearest"), Some("running"));
+        assert_eq!(id(early, "prefer"), Some("running"));
+        assert_eq!(id(early, "skip"), Some("later"));
+        assert_eq!(id(early, "only"), Some("running"));
+
+        let late = "2023-03-01T10:40:00+01:00";
+        assert_eq!(id(late, "nearest"), Some("later"));
+        assert_eq!(id(late, "prefer"), Some("running"));
+
+        let before = "2023-03-01T09:00:00+01:00";
+        assert_eq!(id(before, "prefer"), Some("running"));
+        assert_eq!(id(before, "only"), None);
+        assert!("sometimes".parse::<Ongoing>().is_err());
+    }
+
+    #[test]
+    fn handles_dst_midnights() {
+        use chrono_tz::America::Havana;
+        use chrono_tz::America::Santiago;
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        // Chile skips midnight when DST starts, the day begins at 01:00
+        let start = start_of_day(day(2023, 9, 3), &Santiago);
+        assert_eq!(start.to_rfc3339(), "2023-09-03T01:00:00-03:00");
+
+        // Cuba goes from 01:00 back to midnight when DST ends, the day begins at the first one
+        let start = start_of_day(day(2023, 11, 5), &Havana);
+        assert_eq!(start.to_rfc3339(), "2023-11-05T00:00:00-04:00");
+
+        // the whole last second of the day is inside the window
+        let (from, to) = window(day(2023, 11, 4), day(2023, 11, 4), &Havana);
+        assert_eq!(from.to_rfc3339(), "2023-11-04T00:00:00-04:00");
+        assert_eq!(to.to_rfc3339(), "2023-11-05T00:00:00-04:00");
+        assert_eq!(to - from, Duration::hours(24));
+        let (_, to) = window(day(2023, 11, 5), day(2023, 11, 5), &Havana);
+        assert_eq!(to - start, Duration::hours(25));
+    }
+
+    #[test]
+    fn finds_last_meeting() {
+        let meetings: Vec<Meeting> = serde_json::from_str(
+            r#"[
+                { "id": "a", "end": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                  "attendees": [{ "self": true, "responseStatus": "accepted" }] },
+                { "id": "b", "end": { "dateTime": "2023-03-01T11:00:00+01:00" },
+                  "attendees": [{ "self": true, "responseStatus": "accepted" }] },
+                { "id": "c", "end": { "dateTime": "2023-03-01T11:30:00+01:00" },
+                  "attendees": [{ "self": true, "responseStatus": "declined" }] },
+                { "id": "d", "end": { "dateTime": "2023-03-01T13:00:00+01:00" },
+                  "attendees": [{ "self": true, "responseStatus": "accepted" }] }
+            ]"#,
+        )
+        .unwrap();
+        let now = "2023-03-01T12:00:00+01:00".parse().unwrap();
+        assert_eq!(last_meeting(&meetings, now).and_then(|m| m.id()), Some("b"));
+    }
+
+    #[test]
+    fn matches_invitations() {
+        let meetings: Vec<Meeting> = serde_json::from_str(
+            r#"[
+                { "id": "a", "summary": "Sprint Review",
+                  "attendees": [{ "self": true, "responseStatus": "accepted" }] },
+                { "id": "b", "summary": "sprint review (optional)",
+                  "attendees": [{ "self": true, "responseStatus": "needsAction" }] },
+                { "id": "c", "summary": "Sprint Review",
+                  "attendees": [{ "self": true, "responseStatus": "declined" }] },
+                { "id": "d", "summary": "Sprint Review",
+                  "organizer": { "email": "me@example.org", "self": true },
+                  "attendees": [{ "self": true, "responseStatus": "accepted" }] },
+                { "id": "e", "summary": "Planning",
+                  "attendees": [{ "self": true, "responseStatus": "accepted" }] }
+            ]"#,
+        )
+        .unwrap();
+        let ids: Vec<_> = invitations_matching(&meetings, "Sprint Review")
+            .iter()
+            .map(|m| m.id())
+            .collect();
+        assert_eq!(ids, [Some("a"), Some("b")]);
+    }
+
+    #[test]
+    fn finds_working_locations() {
+        let meetings: Vec<Meeting> = serde_json::from_str(
+            r#"[
+                { "id": "mine", "eventType": "workingLocation",
+                  "organizer": { "email": "me@example.org", "self": true },
+                  "start": { "date": "2023-03-01" }, "end": { "date": "2023-03-02" },
+                  "workingLocationProperties": { "type": "homeOffice", "homeOffice": {} } },
+                { "id": "theirs", "eventType": "workingLocation",
+                  "organizer": { "email": "ana@example.org" },
+                  "start": { "date": "2023-03-01" }, "end": { "date": "2023-03-03" },
+                  "workingLocationProperties": {
+                      "type": "officeLocation", "officeLocation": { "label": "Milano" } } }
+            ]"#,
+        )
+        .unwrap();
+        let day = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let found = working_locations(&meetings, day);
+        assert_eq!(found.len(), 2);
+        assert!(found[0].organized());
+        assert_eq!(found[0].working_location().as_deref(), Some("home"));
+        assert_eq!(found[1].working_location().as_deref(), Some("Milano"));
+
+        let next = NaiveDate::from_ymd_opt(2023, 3, 2).unwrap();
+        let found = working_locations(&meetings, next);
+        assert_eq!(
+            found.iter().map(|m| m.id()).collect::<Vec<_>>(),
+            [Some("theirs")]
+        );
+    }
+
+    #[test]
+    fn out_of_office_hides_meetings() {
+        let meetings: Vec<Meeting> = serde_json::from_str(
+            r#"[
+                { "id": "ooo", "eventType": "outOfOffice",
+                  "start": { "dateTime": "2023-03-01T00:00:00+01:00" },
+                  "end": { "dateTime": "2023-03-02T00:00:00+01:00" } },
+                { "id": "meet", "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                  "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                  "end": { "dateTime": "2023-03-01T11:00:00+01:00" },
+                  "attendees": [{ "self": true, "responseStatus": "accepted" }] }
+            ]"#,
+        )
+        .unwrap();
+        let now = "2023-03-01T09:00:00+01:00".parse().unwrap();
+        assert_eq!(upcoming(&meetings, now).and_then(|m| m.id()), Some("ooo"));
+        let back = "2023-02-28T09:00:00+01:00".parse().unwrap();
+        assert_eq!(upcoming(&meetings, back).and_then(|m| m.id()), Some("meet"));
+    }
+
+    #[test]
+    fn skips_malformed_events() {
+        let response: Response = serde_json::from_str(
+            r#"{ "items": [
+                { "id": "a", "summary": "Standup" },
+                { "id": "b", "summary": 42 },
+                { "id": "c", "attendees": "everyone" },
+                { "id": "d" }
+            ] }"#,
+        )
+        .unwrap();
+        let ids: Vec<_> = response.items.iter().map(|m| m.id().unwrap()).collect();
+        assert_eq!(ids, vec!["a", "d"]);
+
+        assert!(serde_json::from_str::<Response>(r#"{ "error": { "code": 401 } }"#).is_err());
+    }
+
+    #[test]
+    fn replays_dumped_responses() {
+        let replayed =
+            replay_json("{ \"items\": [{ \"id\": \"a\" }] }\n{ \"items\": [{ \"id\": \"b\" }] }")
+                .unwrap();
+        let ids: Vec<_> = serde_json::from_str::<Response>(&replayed)
+            .unwrap()
+            .items
+            .iter()
+            .map(|m| m.id().unwrap().to_string())
+            .collect();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        assert!(replay_json("").is_err());
+        assert!(replay_json("{ \"error\": { \"code\": 401 } }").is_err());
+        assert!(replay_json("{ \"items\": [] ").is_err());
+    }
+
+    #[test]
+    fn merges_calendars() {
+        let merged = merge(vec![
+            r#"{ "items": [{ "id": "a" }, { "id": "b" }] }"#.to_string(),
+            r#"{ "items": [{ "id": "b" }, { "id": "c" }] }"#.to_string(),
+        ])
+        .unwrap();
+        let ids: Vec<_> = serde_json::from_str::<Response>(&merged)
+            .unwrap()
+            .items
+            .iter()
+            .map(|m| m.id().unwrap().to_string())
+            .collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+
+        assert!(merge(vec![
+            r#"{ "items": [] }"#.to_string(),
+            r#"{ "error": { "code": 401 } }"#.to_string(),
+        ])
+        .is_err());
+    }
+
+    // cargo test --release -- --ignored --nocapture bench_links
+    #[test]
+    #[ignore]
+    fn bench_links() {
+        let meetings: Vec<Meeting> = (0..500)
+            .map(|i| Meeting {
+                description: Some(format!(
+                    "Agenda <a href=\"https://docs.example.org/{i}\">doc</a> https://us02web.zoom.us/j/{i}"
+                )),
+                ..Default::default()
+            })
+            .collect();
+
+        let started = std::time::Instant::now();
+        for _ in 0..20 {
+            for m in &meetings {
+                assert!(m.get_link().is_some());
+                assert_eq!(m.get_other_links().len(), 1);
+            }
+        }
+        let elapsed = started.elapsed();
+        println!(
+            "{} events in {:?} ({:?} per event)",
+            meetings.len() * 20,
+            elapsed,
+            elapsed / (meetings.len() as u32 * 20)
+        );
+    }
 }
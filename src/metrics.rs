@@ -0,0 +1,90 @@
+use super::meetings::ongoing_meeting;
+use super::server::State;
+use chrono::DateTime;
+use chrono::Local;
+
+fn metric(lines: &mut Vec<String>, name: &str, kind: &str, help: &str, value: Option<f64>) {
+    lines.push(format!("# HELP {name} {help}"));
+    lines.push(format!("# TYPE {name} {kind}"));
+    if let Some(value) = value {
+        lines.push(format!("{name} {value}"));
+    }
+}
+
+pub fn render(state: &State, now: DateTime<Local>) -> String {
+    let next_start = state
+        .meetings
+        .iter()
+        .filter_map(|meeting| meeting.start().ok())
+        .filter(|start| *start > now)
+        .min();
+
+    let mut lines = vec![];
+    metric(
+        &mut lines,
+        "nextmeet_seconds_until_next_meeting",
+        "gauge",
+        "Seconds until the next accepted meeting starts",
+        next_start.map(|start| (start - now).num_seconds() as f64),
+    );
+    metric(
+        &mut lines,
+        "nextmeet_meetings_today",
+        "gauge",
+        "Accepted meetings with a link today",
+        Some(state.meetings.len() as f64),
+    );
+    metric(
+        &mut lines,
+        "nextmeet_in_meeting",
+        "gauge",
+        "1 while a meeting is in progress",
+        Some(ongoing_meeting(&state.meetings, now).is_some() as u8 as f64),
+    );
+    metric(
+        &mut lines,
+        "nextmeet_api_errors_total",
+        "counter",
+        "Failed calendar API requests",
+        Some(state.api_errors as f64),
+    );
+    metric(
+        &mut lines,
+        "nextmeet_last_sync_timestamp_seconds",
+        "gauge",
+        "Unix time of the last successful sync",
+        state.refreshed_at.map(|at| at.timestamp() as f64),
+    );
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_gauges() {
+        let mut state = State::default();
+        state.meetings = vec![serde_json::from_str(
+            r#"{
+                "summary": "Standup",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" }
+            }"#,
+        )
+        .unwrap()];
+        state.api_errors = 2;
+
+        let result = render(&state, "2023-03-01T09:59:00+01:00".parse().unwrap());
+        let lines: Vec<_> = result.lines().collect();
+
+        assert!(lines.contains(&"nextmeet_seconds_until_next_meeting 60"));
+        assert!(lines.contains(&"nextmeet_meetings_today 1"));
+        assert!(lines.contains(&"nextmeet_in_meeting 0"));
+        assert!(lines.contains(&"nextmeet_api_errors_total 2"));
+        assert!(!lines
+            .iter()
+            .any(|line| line.starts_with("nextmeet_last_sync_timestamp_seconds")));
+    }
+}
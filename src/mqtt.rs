@@ -0,0 +1,148 @@
+use super::meetings::next_meeting;
+use super::meetings::ongoing_meeting;
+use super::meetings::Meeting;
+use super::settings::Mqtt;
+use chrono::DateTime;
+use chrono::Local;
+use rumqttc::AsyncClient;
+use rumqttc::MqttOptions;
+use rumqttc::QoS;
+use std::error::Error;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusyState {
+    pub busy: bool,
+    pub next_start: Option<i64>,
+    pub summary: Option<String>,
+}
+
+pub fn busy_state(meetings: &[Meeting], now: DateTime<Local>) -> BusyState {
+    let current = ongoing_meeting(meetings, now);
+    let next = meetings
+        .iter()
+        .filter(|meeting| meeting.start().map(|start| start > now).unwrap_or(false))
+        .min_by_key(|meeting| meeting.start().ok());
+
+    BusyState {
+        busy: current.is_some(),
+        next_start: next
+            .and_then(|m| m.start().ok())
+            .map(|start| start.timestamp()),
+        summary: current
+            .or_else(|| next_meeting(meetings, now))
+            .map(|m| m.summary().to_string()),
+    }
+}
+
+pub struct Publisher {
+    client: AsyncClient,
+    prefix: String,
+}
+
+impl Publisher {
+    pub fn connect(settings: &Mqtt) -> Publisher {
+        let mut options = MqttOptions::new(
+            settings.client_id.as_deref().unwrap_or("nextmeet"),
+            &settings.host,
+            settings.port.unwrap_or(1883),
+        );
+        options.set_keep_alive(Duration::from_secs(30));
+        if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+            options.set_credentials(username, password);
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, 10);
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = eventloop.poll().await {
+                    eprintln!("Error: MQTT: {}", err);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        });
+
+        Publisher {
+            client,
+            prefix: settings
+                .topic_prefix
+                .clone()
+                .unwrap_or_else(|| "nextmeet".to_string()),
+        }
+    }
+
+    async fn publish_topic(&self, topic: &str, payload: String) -> Result<(), Box<dyn Error>> {
+        self.client
+            .publish(
+                format!("{}/{}", self.prefix, topic),
+                QoS::AtLeastOnce,
+                true,
+                payload,
+            )
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn publish(&self, state: &BusyState) -> Result<(), Box<dyn Error>> {
+        let busy = if state.busy { "busy" } else { "free" };
+        self.publish_topic("state", busy.to_string()).await?;
+        self.publish_topic(
+            "next_start",
+            state.next_start.map(|t| t.to_string()).unwrap_or_default(),
+        )
+        .await?;
+        self.publish_topic("summary", state.summary.clone().unwrap_or_default())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(summary: &str, start: &str, end: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "summary": summary,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+            "attendees": [{ "self": true, "responseStatus": "accepted" }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn busy_during_meeting() {
+        let meetings = [
+            meeting(
+                "First",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:30:00+01:00",
+            ),
+            meeting(
+                "Second",
+                "2023-03-01T11:00:00+01:00",
+                "2023-03-01T11:30:00+01:00",
+            ),
+        ];
+        let second_start = meetings[1].start().unwrap().timestamp();
+
+        let state = busy_state(&meetings, "2023-03-01T10:10:00+01:00".parse().unwrap());
+        assert_eq!(
+            state,
+            BusyState {
+                busy: true,
+                next_start: Some(second_start),
+                summary: Some("First".to_string()),
+            }
+        );
+
+        let state = busy_state(&meetings, "2023-03-01T10:40:00+01:00".parse().unwrap());
+        assert!(!state.busy);
+        assert_eq!(state.summary.as_deref(), Some("Second"));
+
+        let state = busy_state(&meetings, "2023-03-01T12:00:00+01:00".parse().unwrap());
+        assert_eq!(state.next_start, None);
+        assert_eq!(state.summary, None);
+    }
+}
@@ -0,0 +1,145 @@
+use super::meetings;
+use super::meetings::Meeting;
+use super::store;
+use chrono::DateTime;
+use chrono::Local;
+use serde_json::Value;
+use std::error::Error;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+// Browsers refuse anything larger coming from the host, and requests are far smaller
+const MAX_MESSAGE: usize = 1024 * 1024;
+
+// Each message is its length as a native-endian u32 followed by that much JSON;
+// None once the browser closes stdin
+async fn read_message<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<Value>, Box<dyn Error>> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length).await {
+        Ok(_) => (),
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let length = u32::from_ne_bytes(length) as usize;
+    if length > MAX_MESSAGE {
+        return Err(format!("Message of {length} bytes is too large").into());
+    }
+    let mut message = vec![0; length];
+    reader.read_exact(&mut message).await?;
+    Ok(Some(serde_json::from_slice(&message)?))
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Value,
+) -> Result<(), Box<dyn Error>> {
+    let message = serde_json::to_vec(message)?;
+    if message.len() > MAX_MESSAGE {
+        return Err("Response too large for the browser".into());
+    }
+    writer
+        .write_all(&(message.len() as u32).to_ne_bytes())
+        .await?;
+    writer.write_all(&message).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+fn meeting_json(meeting: &Meeting) -> Value {
+    let mut value = serde_json::json!(meeting);
+    value["id"] = meeting.id().into();
+    value["link"] = meeting.get_link().into();
+    value
+}
+
+// {"type": "next"} and {"type": "join"} answer with the next meeting, "join" also counts it
+// as joined since the extension is about to open it; {"type": "today"} lists the agenda
+pub fn answer(request: &Value, meetings: &[Meeting], now: DateTime<Local>) -> Value {
+    match request["type"].as_str() {
+        Some("next") | Some("join") => {
+            let meeting = meetings::next_meeting(meetings, now);
+            if let Some(meeting) = meeting.filter(|_| request["type"] == "join") {
+                store::record_join(meeting);
+            }
+            serde_json::json!({ "meeting": meeting.map(meeting_json) })
+        }
+        Some("today") => {
+            serde_json::json!({ "meetings": meetings.iter().map(meeting_json).collect::<Vec<_>>() })
+        }
+        _ => serde_json::json!({ "error": format!("Unknown request: {request}") }),
+    }
+}
+
+// `nextmeet native-host`, started by the browser for the companion extension
+pub async fn run() -> Result<(), Box<dyn Error>> {
+    let mut stdin = tokio::io::stdin();
+    let mut stdout = tokio::io::stdout();
+    while let Some(request) = read_message(&mut stdin).await? {
+        let today = Local::now().date_naive();
+        // a login would need stdin and stdout, which belong to the browser
+        let response = match meetings::retrieve_agenda_unattended(today, today).await {
+            Ok(meetings) => answer(&request, &meetings, Local::now()),
+            Err(err) => serde_json::json!({ "error": err.to_string() }),
+        };
+        write_message(&mut stdout, &response).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn frames_messages() {
+        let mut buffer = vec![];
+        write_message(&mut buffer, &serde_json::json!({ "type": "next" }))
+            .await
+            .unwrap();
+        assert_eq!(&buffer[..4], &15u32.to_ne_bytes());
+
+        let mut reader = &buffer[..];
+        assert_eq!(
+            read_message(&mut reader).await.unwrap(),
+            Some(serde_json::json!({ "type": "next" }))
+        );
+        assert_eq!(read_message(&mut reader).await.unwrap(), None);
+
+        let huge = u32::MAX.to_ne_bytes();
+        assert!(read_message(&mut &huge[..]).await.is_err());
+    }
+
+    #[test]
+    fn answers_requests() {
+        let meetings: Vec<Meeting> = serde_json::from_str(
+            r#"[
+                { "id": "standup", "summary": "Standup",
+                  "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                  "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                  "end": { "dateTime": "2023-03-01T10:30:00+01:00" },
+                  "attendees": [{ "self": true, "responseStatus": "accepted" }] }
+            ]"#,
+        )
+        .unwrap();
+        let now = "2023-03-01T09:00:00+01:00".parse().unwrap();
+
+        let next = answer(&serde_json::json!({ "type": "next" }), &meetings, now);
+        assert_eq!(next["meeting"]["id"], "standup");
+        assert_eq!(
+            next["meeting"]["link"],
+            "https://meet.google.com/aaa-bbbb-ccc"
+        );
+        let later = "2023-03-01T11:00:00+01:00".parse().unwrap();
+        let none = answer(&serde_json::json!({ "type": "next" }), &meetings, later);
+        assert!(none["meeting"].is_null());
+        let today = answer(&serde_json::json!({ "type": "today" }), &meetings, now);
+        assert_eq!(today["meetings"].as_array().map(Vec::len), Some(1));
+        assert!(
+            answer(&serde_json::json!({ "type": "weather" }), &meetings, now)["error"].is_string()
+        );
+    }
+}
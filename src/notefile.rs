@@ -0,0 +1,116 @@
+use super::meetings::Meeting;
+use super::settings;
+use super::template::meeting_value;
+use std::error::Error;
+use std::path::PathBuf;
+
+const DEFAULT_TEMPLATE: &str = "# {{ meeting.summary }}
+
+{{ meeting.start.date }} {{ meeting.start.time }} - {{ meeting.end.time }}
+{% if meeting.organizer %}Organizer: {{ meeting.organizer }}
+{% endif %}{% if meeting.attendees %}
+## Attendees
+
+{% for attendee in meeting.attendees %}- {{ attendee }}
+{% endfor %}{% endif %}{% if meeting.link or meeting.other_links %}
+## Links
+
+{% if meeting.link %}- {{ meeting.link }}
+{% endif %}{% for link in meeting.other_links %}- {{ link }}
+{% endfor %}{% endif %}
+## Notes
+
+";
+
+fn slug(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+pub fn expand(dir: &str) -> PathBuf {
+    match (dir.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(dir),
+    }
+}
+
+pub fn file_name(meeting: &Meeting) -> String {
+    let date = meeting
+        .start()
+        .map(|start| start.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    format!("{date}-{}.md", slug(meeting.summary()))
+}
+
+pub fn render(template: &str, meeting: &Meeting) -> Result<String, Box<dyn Error>> {
+    let mut context = tera::Context::new();
+    context.insert("meeting", &meeting_value(meeting));
+    Ok(tera::Tera::one_off(template, &context, false)?)
+}
+
+// Existing files are left alone, they may already hold notes
+pub fn create(
+    settings: &settings::NotesFile,
+    meeting: &Meeting,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = expand(settings.dir.as_deref().unwrap_or("~/notes"));
+    let path = dir.join(file_name(meeting));
+
+    if !path.exists() {
+        let template = match &settings.template {
+            Some(template) => std::fs::read_to_string(expand(template))
+                .map_err(|_| format!("Template not found: {template}"))?,
+            None => DEFAULT_TEMPLATE.to_string(),
+        };
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(&path, render(&template, meeting)?)?;
+    }
+
+    if settings.open {
+        super::open::open(&path.to_string_lossy())?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting() -> Meeting {
+        serde_json::from_str(
+            r#"{
+                "summary": "Standup: team A/B",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" },
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                "organizer": { "email": "boss@example.org" },
+                "attendees": [
+                    { "email": "me@example.org", "self": true, "responseStatus": "accepted" },
+                    { "email": "anna@example.org", "displayName": "Anna", "responseStatus": "accepted" }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn names_file_after_date_and_summary() {
+        let name = file_name(&meeting());
+        assert!(name.ends_with("-standup-team-a-b.md"));
+        assert_eq!(name.len(), "2023-03-01-standup-team-a-b.md".len());
+    }
+
+    #[test]
+    fn renders_default_template() {
+        let result = render(DEFAULT_TEMPLATE, &meeting()).unwrap();
+
+        assert!(result.starts_with("# Standup: team A/B\n"));
+        assert!(result.contains("Organizer: boss@example.org\n"));
+        assert!(result.contains("- Anna\n"));
+        assert!(result.contains("- https://meet.google.com/aaa-bbbb-ccc\n"));
+        assert!(result.ends_with("## Notes\n\n"));
+    }
+}
@@ -0,0 +1,237 @@
+#[cfg(feature = "desktop")]
+use super::desktop::Desktop;
+use super::diff::Change;
+use super::duration;
+use super::gotify::Gotify;
+use super::matrix::Matrix;
+use super::meetings::Meeting;
+use super::ntfy::Ntfy;
+use super::pushover::Pushover;
+use super::settings::NotifierSettings;
+use super::telegram::Telegram;
+use super::watch::Event;
+use async_trait::async_trait;
+use std::error::Error;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reminder {
+    pub title: String,
+    pub message: String,
+    pub link: Option<String>,
+    pub minutes_before: Option<i64>,
+    pub meeting: Meeting,
+    // Sent with the highest priority the service has, for changes to the next meeting
+    pub urgent: bool,
+}
+
+impl Reminder {
+    pub fn new(event: Event, meeting: &Meeting) -> Reminder {
+        let start = meeting
+            .start()
+            .map(|start| start.format("%H:%M").to_string())
+            .unwrap_or_default();
+
+        let message = match event {
+            Event::Before(offset) => {
+                format!("Starts at {} (in {} minutes)", start, offset.num_minutes())
+            }
+            Event::Started => format!("Started at {}", start),
+            Event::Ended => "Ended".to_string(),
+        };
+
+        Reminder {
+            title: meeting.summary().to_string(),
+            message,
+            link: meeting.get_link(),
+            minutes_before: match event {
+                Event::Before(offset) => Some(offset.num_minutes()),
+                _ => None,
+            },
+            meeting: meeting.clone(),
+            urgent: false,
+        }
+    }
+
+    pub fn changed(change: &Change) -> Reminder {
+        let time = |meeting: &Meeting| {
+            meeting
+                .start()
+                .map(|start| start.format("%H:%M").to_string())
+                .unwrap_or_default()
+        };
+
+        let (message, meeting) = match change {
+            Change::Cancelled(old) => (format!("Cancelled, was at {}", time(old)), *old),
+            // to another day, the day is part of the news
+            Change::Moved(old, new)
+                if old.start().ok().map(|start| start.date_naive())
+                    != new.start().ok().map(|start| start.date_naive()) =>
+            {
+                let day = new
+                    .start()
+                    .map(|start| start.format("%a %d/%m %H:%M").to_string())
+                    .unwrap_or_default();
+                (format!("Moved from {} to {}", time(old), day), *new)
+            }
+            Change::Moved(old, new) => (format!("Moved from {} to {}", time(old), time(new)), *new),
+            Change::Relinked(_, new) => (format!("New link, still at {}", time(new)), *new),
+            Change::Renamed(old, new) => (
+                format!("Renamed from {}, still at {}", old.summary(), time(new)),
+                *new,
+            ),
+            Change::Added(new) => (format!("Added at {}", time(new)), *new),
+        };
+
+        Reminder {
+            title: meeting.summary().to_string(),
+            message,
+            link: match change {
+                Change::Cancelled(_) => None,
+                _ => meeting.get_link(),
+            },
+            minutes_before: None,
+            meeting: meeting.clone(),
+            urgent: true,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+pub trait Notifier {
+    fn name(&self) -> &'static str;
+
+    fn before(&self) -> &[String];
+
+    async fn send(&self, reminder: &Reminder) -> Result<(), Box<dyn Error>>;
+
+    fn wants(&self, event: Event) -> bool {
+        match event {
+            Event::Before(offset) => self
+                .before()
+                .iter()
+                .any(|before| duration::parse(before) == Ok(offset)),
+            _ => false,
+        }
+    }
+}
+
+pub fn from_settings(settings: &NotifierSettings) -> Result<Box<dyn Notifier>, String> {
+    Ok(match settings {
+        NotifierSettings::Ntfy(settings) => Box::new(Ntfy::new(settings.clone())),
+        NotifierSettings::Pushover(settings) => Box::new(Pushover::new(settings.clone())),
+        NotifierSettings::Gotify(settings) => Box::new(Gotify::new(settings.clone())),
+        NotifierSettings::Telegram(settings) => Box::new(Telegram::new(settings.clone())),
+        NotifierSettings::Matrix(settings) => Box::new(Matrix::new(settings.clone())),
+        #[cfg(feature = "desktop")]
+        NotifierSettings::Desktop(settings) => Box::new(Desktop::new(settings.clone())),
+        #[cfg(not(feature = "desktop"))]
+        NotifierSettings::Desktop(_) => {
+            return Err("Desktop notifications need the desktop feature".to_string())
+        }
+    })
+}
+
+pub async fn notify_all(notifiers: &[Box<dyn Notifier>], event: Event, meeting: &Meeting) {
+    let reminder = Reminder::new(event, meeting);
+
+    for notifier in notifiers.iter().filter(|notifier| notifier.wants(event)) {
+        if let Err(err) = notifier.send(&reminder).await {
+            eprintln!("Error: {}: {}", notifier.name(), err);
+        }
+    }
+}
+
+// Every notifier gets these, whatever reminders it is set up for
+pub async fn notify_change(notifiers: &[Box<dyn Notifier>], change: &Change<'_>) {
+    let reminder = Reminder::changed(change);
+
+    for notifier in notifiers {
+        if let Err(err) = notifier.send(&reminder).await {
+            eprintln!("Error: {}: {}", notifier.name(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings;
+    use chrono::Duration;
+
+    #[test]
+    fn reminder_message() {
+        let m: Meeting = serde_json::from_str(
+            r#"{ "summary": "Standup", "start": { "dateTime": "2023-03-01T10:00:00+01:00" } }"#,
+        )
+        .unwrap();
+        let start = m.start().unwrap().format("%H:%M").to_string();
+
+        let reminder = Reminder::new(Event::Before(Duration::minutes(5)), &m);
+        assert_eq!(reminder.title, "Standup");
+        assert_eq!(
+            reminder.message,
+            format!("Starts at {start} (in 5 minutes)")
+        );
+        assert_eq!(reminder.link, None);
+        assert_eq!(reminder.minutes_before, Some(5));
+        assert!(!reminder.urgent);
+    }
+
+    #[test]
+    fn change_message() {
+        let meeting = |start: &str| -> Meeting {
+            serde_json::from_value(serde_json::json!({
+                "summary": "Standup",
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                "start": { "dateTime": start }
+            }))
+            .unwrap()
+        };
+        let old = meeting("2023-03-01T10:00:00+01:00");
+        let new = meeting("2023-03-01T11:00:00+01:00");
+        let hour = |m: &Meeting| m.start().unwrap().format("%H:%M").to_string();
+
+        let moved = Reminder::changed(&Change::Moved(&old, &new));
+        assert!(moved.urgent);
+        assert_eq!(moved.title, "Standup");
+        assert_eq!(
+            moved.message,
+            format!("Moved from {} to {}", hour(&old), hour(&new))
+        );
+        assert!(moved.link.is_some());
+
+        let tomorrow = meeting("2023-03-02T10:00:00+01:00");
+        let moved = Reminder::changed(&Change::Moved(&old, &tomorrow));
+        assert_eq!(
+            moved.message,
+            format!(
+                "Moved from {} to {}",
+                hour(&old),
+                tomorrow.start().unwrap().format("%a %d/%m %H:%M")
+            )
+        );
+
+        let cancelled = Reminder::changed(&Change::Cancelled(&old));
+        assert_eq!(
+            cancelled.message,
+            format!("Cancelled, was at {}", hour(&old))
+        );
+        assert_eq!(cancelled.link, None);
+    }
+
+    #[test]
+    fn only_reminders_for_configured_offsets() {
+        let notifier = from_settings(&NotifierSettings::Ntfy(settings::Ntfy {
+            server: None,
+            topic: "meetings".to_string(),
+            token: None,
+            priority: None,
+            before: vec!["5m".to_string()],
+        }))
+        .unwrap();
+
+        assert!(notifier.wants(Event::Before(Duration::minutes(5))));
+        assert!(!notifier.wants(Event::Before(Duration::minutes(10))));
+        assert!(!notifier.wants(Event::Started));
+    }
+}
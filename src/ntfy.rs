@@ -0,0 +1,54 @@
+use super::notifier::Notifier;
+use super::notifier::Reminder;
+use super::settings;
+use async_trait::async_trait;
+use std::error::Error;
+
+pub struct Ntfy {
+    settings: settings::Ntfy,
+}
+
+impl Ntfy {
+    pub fn new(settings: settings::Ntfy) -> Ntfy {
+        Ntfy { settings }
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for Ntfy {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    fn before(&self) -> &[String] {
+        &self.settings.before
+    }
+
+    async fn send(&self, reminder: &Reminder) -> Result<(), Box<dyn Error>> {
+        let server = self.settings.server.as_deref().unwrap_or("https://ntfy.sh");
+        let url = format!("{}/{}", server.trim_end_matches('/'), self.settings.topic);
+
+        let mut request = crate::http::client()
+            .post(url)
+            .header("Title", &reminder.title)
+            .header("Tags", "calendar")
+            .body(reminder.message.clone());
+
+        if let Some(link) = &reminder.link {
+            request = request
+                .header("Click", link)
+                .header("Actions", format!("view, Join, {link}"));
+        }
+        if reminder.urgent {
+            request = request.header("Priority", "urgent");
+        } else if let Some(priority) = &self.settings.priority {
+            request = request.header("Priority", priority);
+        }
+        if let Some(token) = &self.settings.token {
+            request = request.bearer_auth(token);
+        }
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use std::error::Error;
+use std::io::Write;
+use std::process::Command;
+
+fn default_opener() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    }
+}
+
+pub fn opener() -> String {
+    std::env::var("NEXTMEET_OPENER").unwrap_or_else(|_| default_opener().to_string())
+}
+
+pub fn open(url: &str) -> Result<(), Box<dyn Error>> {
+    let opener = opener();
+    let mut parts = opener.split_whitespace();
+    let program = parts.next().ok_or("Empty opener command")?;
+
+    Command::new(program)
+        .args(parts)
+        .arg(url)
+        .spawn()
+        .map_err(|_| format!("Failed to run {program}"))?;
+
+    Ok(())
+}
+
+fn default_copier() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "pbcopy"
+    } else if cfg!(target_os = "windows") {
+        "clip"
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        "wl-copy"
+    } else {
+        "xclip -selection clipboard"
+    }
+}
+
+pub fn copy(text: &str) -> Result<(), Box<dyn Error>> {
+    let copier = std::env::var("NEXTMEET_COPY").unwrap_or_else(|_| default_copier().to_string());
+    let mut parts = copier.split_whitespace();
+    let program = parts.next().ok_or("Empty copy command")?;
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|_| format!("Failed to run {program}"))?;
+    child
+        .stdin
+        .take()
+        .ok_or("No stdin for copy command")?
+        .write_all(text.as_bytes())?;
+    child.wait()?;
+
+    Ok(())
+}
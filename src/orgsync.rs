@@ -0,0 +1,184 @@
+use super::meetings::Meeting;
+use super::notefile::expand;
+use super::output::org_entry;
+use chrono::NaiveDate;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub const DEFAULT_FILE: &str = "~/org/meetings.org";
+
+// A top-level heading and everything under it
+struct Entry {
+    lines: Vec<String>,
+}
+
+impl Entry {
+    fn property(&self, name: &str) -> Option<&str> {
+        let prefix = format!(":{name}:");
+        self.lines
+            .iter()
+            .find_map(|line| line.trim().strip_prefix(&prefix))
+            .map(str::trim)
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.property("NEXTMEET_ID")
+    }
+
+    fn date(&self) -> Option<NaiveDate> {
+        self.lines.iter().find_map(|line| {
+            let date = line.trim().strip_prefix("SCHEDULED: <")?.get(..10)?;
+            date.parse().ok()
+        })
+    }
+
+    // Whatever was written below the property drawer, kept across updates
+    fn notes(&self) -> &[String] {
+        match self.lines.iter().position(|line| line.trim() == ":END:") {
+            Some(end) => &self.lines[end + 1..],
+            None => &[],
+        }
+    }
+}
+
+// The text before the first heading, then one entry per top-level heading
+fn parse(text: &str) -> (Vec<String>, Vec<Entry>) {
+    let mut preamble = vec![];
+    let mut entries: Vec<Entry> = vec![];
+    for line in text.lines() {
+        if line.starts_with("* ") {
+            entries.push(Entry {
+                lines: vec![line.to_string()],
+            });
+        } else if let Some(entry) = entries.last_mut() {
+            entry.lines.push(line.to_string());
+        } else {
+            preamble.push(line.to_string());
+        }
+    }
+    (preamble, entries)
+}
+
+fn entry(meeting: &Meeting, notes: &[String]) -> Vec<String> {
+    org_entry(meeting)
+        .lines()
+        .map(str::to_string)
+        .chain(notes.iter().cloned())
+        .collect()
+}
+
+// Entries are matched on :NEXTMEET_ID:; those scheduled within [from, to] whose meeting is
+// gone are removed, anything else in the file is left as it is
+pub fn sync(text: &str, meetings: &[Meeting], from: NaiveDate, to: NaiveDate) -> String {
+    let (mut lines, entries) = parse(text);
+    let mut synced = HashSet::new();
+
+    for existing in &entries {
+        let Some(id) = existing.id() else {
+            lines.extend(existing.lines.iter().cloned());
+            continue;
+        };
+        match meetings.iter().find(|meeting| meeting.id() == Some(id)) {
+            Some(meeting) => {
+                synced.insert(id);
+                lines.extend(entry(meeting, existing.notes()));
+            }
+            // gone from the calendar
+            None if existing
+                .date()
+                .is_some_and(|date| from <= date && date <= to) => {}
+            None => lines.extend(existing.lines.iter().cloned()),
+        }
+    }
+    for meeting in meetings {
+        if meeting.id().is_some_and(|id| !synced.contains(id)) {
+            lines.extend(entry(meeting, &[]));
+        }
+    }
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+pub fn path(file: Option<&str>) -> PathBuf {
+    expand(file.unwrap_or(DEFAULT_FILE))
+}
+
+// Written next to the file and renamed over it, so Emacs never reads half of it
+pub fn write(
+    path: &Path,
+    meetings: &[Meeting],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<(), Box<dyn Error>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temporary = path.with_extension("org.tmp");
+    std::fs::write(&temporary, sync(&text, meetings, from, to))?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(id: &str, summary: &str, start: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "summary": summary,
+            "start": { "dateTime": start },
+            "end": { "dateTime": start }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn updates_entries_by_id() {
+        let day: NaiveDate = "2023-03-01".parse().unwrap();
+        let existing = sync(
+            "#+TITLE: Meetings\n* Buy milk\n",
+            &[
+                meeting("standup", "Standup", "2023-03-01T10:00:00+01:00"),
+                meeting("retro", "Retro", "2023-03-01T15:00:00+01:00"),
+            ],
+            day,
+            day,
+        );
+        assert!(existing.starts_with("#+TITLE: Meetings\n* Buy milk\n* Standup\n"));
+        let existing = existing.replace(
+            "  :END:\n* Retro",
+            "  :END:\n  ask about the budget\n* Retro",
+        );
+
+        let updated = sync(
+            &existing,
+            &[
+                meeting("standup", "Standup (moved)", "2023-03-01T11:00:00+01:00"),
+                meeting("review", "Review", "2023-03-01T16:00:00+01:00"),
+            ],
+            day,
+            day,
+        );
+        let headings: Vec<_> = updated
+            .lines()
+            .filter(|line| line.starts_with("* "))
+            .collect();
+        assert_eq!(headings, ["* Buy milk", "* Standup (moved)", "* Review"]);
+        assert!(updated.contains("  :END:\n  ask about the budget\n* Review"));
+        assert_eq!(updated.matches(":NEXTMEET_ID: standup").count(), 1);
+
+        // outside the synced days nothing is removed
+        let tomorrow = day.succ_opt().unwrap();
+        assert_eq!(sync(&updated, &[], tomorrow, tomorrow), updated);
+    }
+}
@@ -0,0 +1,1086 @@
+use super::meetings::next_meeting;
+use super::meetings::ongoing_meeting;
+use super::meetings::recompute_at;
+use super::meetings::working_locations;
+use super::meetings::Meeting;
+use super::meetings::NOW_MINUTES;
+use super::meetings::SOON_MINUTES;
+use super::settings::Cost;
+use super::stats;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    I3blocks,
+    I3bar,
+    Xmobar,
+    Tmux,
+    Xbar,
+    Argos,
+    Alfred,
+    Raycast,
+    Widget,
+    Ndjson,
+    Markdown,
+    Org,
+    Html,
+    Gcalcli,
+}
+
+impl Format {
+    pub fn is_agenda(&self) -> bool {
+        matches!(
+            self,
+            Format::Xbar
+                | Format::Argos
+                | Format::Alfred
+                | Format::Raycast
+                | Format::Widget
+                | Format::Markdown
+                | Format::Org
+                | Format::Html
+                | Format::Gcalcli
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub format: Format,
+    pub max_width: Option<usize>,
+    pub stale: bool,
+    // where I and the colleagues whose calendars are read work from, shown above the agenda
+    pub locations: Vec<String>,
+    // with --cost, what each meeting costs: under it in the text list, a field in ndjson
+    pub cost: Option<Cost>,
+    // all of today's meetings, for when a bar showing one of them needs updating
+    pub day: Vec<Meeting>,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "i3blocks" => Ok(Format::I3blocks),
+            "i3bar" => Ok(Format::I3bar),
+            "xmobar" => Ok(Format::Xmobar),
+            "tmux" => Ok(Format::Tmux),
+            "xbar" => Ok(Format::Xbar),
+            "argos" => Ok(Format::Argos),
+            "alfred" => Ok(Format::Alfred),
+            "raycast" => Ok(Format::Raycast),
+            "widget" => Ok(Format::Widget),
+            "ndjson" => Ok(Format::Ndjson),
+            "markdown" => Ok(Format::Markdown),
+            "org" => Ok(Format::Org),
+            "html" => Ok(Format::Html),
+            "gcalcli" => Ok(Format::Gcalcli),
+            _ => Err(format!("Unknown output format: {s}")),
+        }
+    }
+}
+
+const NO_MEETINGS: &str = "Non ci sono appuntamenti";
+
+const COLOR_NOW: &str = "#FF5555";
+const COLOR_SOON: &str = "#F1FA8C";
+
+// Changes exactly at the thresholds recompute_at reports
+fn color(meeting: &Meeting, now: DateTime<Local>) -> Option<&'static str> {
+    let until = meeting.start().ok()? - now;
+
+    if until <= Duration::minutes(NOW_MINUTES) {
+        Some(COLOR_NOW)
+    } else if until <= Duration::minutes(SOON_MINUTES) {
+        Some(COLOR_SOON)
+    } else {
+        None
+    }
+}
+
+fn truncate(text: String, max_width: Option<usize>) -> String {
+    match max_width {
+        Some(max_width) if text.chars().count() > max_width => {
+            let mut text: String = text.chars().take(max_width.saturating_sub(1)).collect();
+            text.push('…');
+            text
+        }
+        _ => text,
+    }
+}
+
+fn full_text(meeting: &Meeting, max_width: Option<usize>) -> String {
+    let start = meeting
+        .start()
+        .map(|date| date.format("%H:%M").to_string())
+        .unwrap_or_default();
+
+    truncate(format!("{} {}", start, meeting.summary()), max_width)
+}
+
+fn short_text(meeting: &Meeting) -> String {
+    meeting
+        .start()
+        .map(|date| date.format("%H:%M").to_string())
+        .unwrap_or_default()
+}
+
+fn i3blocks(options: &Options, meeting: Option<&Meeting>, now: DateTime<Local>) -> String {
+    match meeting {
+        None => String::new(),
+        Some(meeting) => format!(
+            "{}\n{}\n{}",
+            full_text(meeting, options.max_width),
+            short_text(meeting),
+            color(meeting, now).unwrap_or_default()
+        ),
+    }
+}
+
+// Another meeting of the day may start, or take over as next, before the shown ones change
+fn recompute(options: &Options, meetings: &[Meeting], now: DateTime<Local>) -> DateTime<Local> {
+    let mut day = options.day.clone();
+    day.extend_from_slice(meetings);
+    recompute_at(&day, now)
+}
+
+fn i3bar(options: &Options, meeting: Option<&Meeting>, now: DateTime<Local>) -> String {
+    let mut block = serde_json::Map::new();
+
+    match meeting {
+        None => {
+            block.insert("full_text".into(), "".into());
+        }
+        Some(meeting) => {
+            block.insert(
+                "full_text".into(),
+                full_text(meeting, options.max_width).into(),
+            );
+            block.insert("short_text".into(), short_text(meeting).into());
+            if let Some(color) = color(meeting, now) {
+                block.insert("color".into(), color.into());
+            }
+        }
+    }
+    // i3bar ignores keys starting with an underscore
+    let meetings = meeting.map(std::slice::from_ref).unwrap_or_default();
+    block.insert(
+        "_recompute_at".into(),
+        recompute(options, meetings, now).to_rfc3339().into(),
+    );
+    if options.stale {
+        block.insert("_stale".into(), true.into());
+    }
+
+    serde_json::Value::Object(block).to_string()
+}
+
+fn xmobar(options: &Options, meeting: Option<&Meeting>, now: DateTime<Local>) -> String {
+    match meeting {
+        None => String::new(),
+        Some(meeting) => {
+            // raw keeps xmobar from reading tags or actions in the summary
+            let text = full_text(meeting, options.max_width);
+            let text = format!("<raw={}:{text}/>", text.chars().count());
+            match color(meeting, now) {
+                Some(color) => format!("<fc={color}>{text}</fc>"),
+                None => text,
+            }
+        }
+    }
+}
+
+fn tmux(options: &Options, meeting: Option<&Meeting>, now: DateTime<Local>) -> String {
+    match meeting {
+        None => String::new(),
+        Some(meeting) => {
+            let text = full_text(meeting, options.max_width).replace('#', "##");
+            match color(meeting, now) {
+                Some(color) => format!("#[fg={color}]{text}#[default]"),
+                None => text,
+            }
+        }
+    }
+}
+
+fn menu_line(text: String, params: &[String]) -> String {
+    let text = text.replace('|', "¦");
+    if params.is_empty() {
+        text
+    } else {
+        format!("{} | {}", text, params.join(" "))
+    }
+}
+
+fn menu(
+    options: &Options,
+    meetings: &[Meeting],
+    now: DateTime<Local>,
+    title_params: &[&str],
+    item_params: &[&str],
+) -> String {
+    let mut params: Vec<String> = title_params.iter().map(|p| p.to_string()).collect();
+    let title = match next_meeting(meetings, now) {
+        None => menu_line(NO_MEETINGS.to_string(), &params),
+        Some(meeting) => {
+            if let Some(color) = color(meeting, now) {
+                params.push(format!("color={color}"));
+            }
+            menu_line(full_text(meeting, options.max_width), &params)
+        }
+    };
+
+    let mut lines = vec![title, "---".to_string()];
+    for meeting in meetings {
+        let mut params: Vec<String> = item_params.iter().map(|p| p.to_string()).collect();
+        if let Some(link) = meeting.get_link() {
+            params.push(format!("href={link}"));
+        }
+        lines.push(menu_line(full_text(meeting, None), &params));
+    }
+
+    lines.join("\n")
+}
+
+fn xbar(options: &Options, meetings: &[Meeting], now: DateTime<Local>) -> String {
+    menu(options, meetings, now, &[], &[])
+}
+
+fn argos(options: &Options, meetings: &[Meeting], now: DateTime<Local>) -> String {
+    menu(
+        options,
+        meetings,
+        now,
+        &["iconName=x-office-calendar-symbolic"],
+        &["useMarkup=false"],
+    )
+}
+
+pub fn time_range(meeting: &Meeting) -> String {
+    let format = |date: DateTime<Local>| date.format("%H:%M").to_string();
+
+    format!(
+        "{} - {}",
+        meeting.start().map(format).unwrap_or_default(),
+        meeting.end().map(format).unwrap_or_default()
+    )
+}
+
+fn alfred(meetings: &[Meeting]) -> String {
+    let items: Vec<_> = meetings
+        .iter()
+        .map(|meeting| {
+            let link = meeting.get_link();
+            serde_json::json!({
+                "title": meeting.summary(),
+                "subtitle": time_range(meeting),
+                "arg": link,
+                "valid": link.is_some(),
+                "mods": {
+                    "cmd": {
+                        "subtitle": "Copy link",
+                        "arg": link,
+                        "valid": link.is_some(),
+                        "variables": { "action": "copy" },
+                    },
+                },
+            })
+        })
+        .collect();
+
+    serde_json::json!({ "items": items }).to_string()
+}
+
+fn countdown(meeting: &Meeting, now: DateTime<Local>) -> String {
+    match meeting.start().map(|start| (start - now).num_minutes()) {
+        Ok(minutes) if minutes > 60 => format!("in {}h {}m", minutes / 60, minutes % 60),
+        Ok(minutes) if minutes > 0 => format!("in {minutes}m"),
+        Ok(_) => "now".to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+fn raycast(options: &Options, meetings: &[Meeting], now: DateTime<Local>) -> String {
+    let title = match next_meeting(meetings, now) {
+        None => NO_MEETINGS.to_string(),
+        Some(meeting) => truncate(
+            format!("{} {}", meeting.summary(), countdown(meeting, now)),
+            options.max_width,
+        ),
+    };
+
+    let mut lines = vec![title];
+    for meeting in meetings {
+        lines.push(format!("{} {}", time_range(meeting), meeting.summary()));
+    }
+
+    lines.join("\n")
+}
+
+fn widget(options: &Options, meetings: &[Meeting], now: DateTime<Local>) -> String {
+    let current = ongoing_meeting(meetings, now);
+    let next = meetings
+        .iter()
+        .find(|meeting| meeting.start().map(|start| start > now).unwrap_or(false));
+
+    serde_json::json!({
+        "now": now.to_rfc3339(),
+        "meetings": meetings,
+        "current": current,
+        "current_ends_in_seconds": current
+            .and_then(|meeting| meeting.end().ok())
+            .map(|end| (end - now).num_seconds()),
+        "next": next,
+        "next_starts_in_seconds": next
+            .and_then(|meeting| meeting.start().ok())
+            .map(|start| (start - now).num_seconds()),
+        "recompute_at": recompute(options, meetings, now).to_rfc3339(),
+        "stale": options.stale,
+    })
+    .to_string()
+}
+
+fn markdown(locations: &[String], meetings: &[Meeting]) -> String {
+    let mut lines: Vec<String> = locations
+        .iter()
+        .map(|location| format!("_{location}_"))
+        .collect();
+    let mut current_date = None;
+
+    for meeting in meetings {
+        let date = meeting.start().ok().map(|start| start.date_naive());
+        if date != current_date {
+            if let Some(date) = date {
+                if !lines.is_empty() {
+                    lines.push(String::new());
+                }
+                lines.push(format!("## {}", date.format("%A %d/%m/%Y")));
+            }
+            current_date = date;
+        }
+
+        lines.push(String::new());
+        lines.push(format!("### {} {}", time_range(meeting), meeting.summary()));
+        if let Some(link) = meeting.get_link() {
+            lines.push(String::new());
+            lines.push(format!("[Join]({link})"));
+        }
+    }
+
+    lines.join("\n")
+}
+
+pub fn org_entry(meeting: &Meeting) -> String {
+    let mut lines = vec![format!("* {}", meeting.summary())];
+
+    if let (Ok(start), Ok(end)) = (meeting.start(), meeting.end()) {
+        lines.push(format!(
+            "  SCHEDULED: <{}-{}>",
+            start.format("%Y-%m-%d %a %H:%M"),
+            end.format("%H:%M")
+        ));
+    }
+
+    lines.push("  :PROPERTIES:".to_string());
+    if let Some(id) = meeting.id() {
+        lines.push(format!("  :NEXTMEET_ID: {id}"));
+    }
+    if let Some(link) = meeting.get_link() {
+        lines.push(format!("  :LINK: {link}"));
+    }
+    if let Some(organizer) = meeting.organizer() {
+        lines.push(format!("  :ORGANIZER: {organizer}"));
+    }
+    lines.push("  :END:".to_string());
+
+    lines.join("\n")
+}
+
+fn org(meetings: &[Meeting]) -> String {
+    meetings
+        .iter()
+        .map(org_entry)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Laid out like `gcalcli agenda`: the date on the first meeting of each day, then a 12-hour
+// start time and the title, with a blank line above and below
+fn gcalcli(meetings: &[Meeting]) -> String {
+    let mut lines = vec![String::new()];
+    let mut current_date = None;
+
+    for meeting in meetings {
+        let Ok(start) = meeting.start() else {
+            continue;
+        };
+        let date = start.date_naive();
+        let prefix = if current_date != Some(date) {
+            current_date = Some(date);
+            start.format("%a %b %d").to_string()
+        } else {
+            String::new()
+        };
+        let time = start.format("%-I:%M%P").to_string();
+        lines.push(format!("{prefix:10}  {time:<7}  {}", meeting.summary()));
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Agenda</title>
+<style>
+body { font-family: sans-serif; max-width: 40em; margin: 2em auto; }
+li { display: flex; align-items: center; gap: 1em; margin: .5em 0; }
+time { font-variant-numeric: tabular-nums; }
+a.join { margin-left: auto; padding: .3em .8em; border-radius: 4px; background: #1a73e8; color: white; text-decoration: none; }
+</style>
+</head>
+<body>"#;
+
+fn html(meetings: &[Meeting], now: DateTime<Local>) -> String {
+    let mut lines = vec![HTML_HEAD.to_string()];
+    let mut current_date = None;
+
+    for meeting in meetings {
+        let date = meeting.start().ok().map(|start| start.date_naive());
+        if date != current_date {
+            if current_date.is_some() {
+                lines.push("</ul>".to_string());
+            }
+            if let Some(date) = date {
+                lines.push(format!("<h2>{}</h2>", date.format("%A %d/%m/%Y")));
+            }
+            lines.push("<ul>".to_string());
+            current_date = date;
+        }
+
+        let join = meeting
+            .get_link()
+            .map(|link| format!(r#"<a class="join" href="{}">Join</a>"#, html_escape(&link)))
+            .unwrap_or_default();
+        lines.push(format!(
+            "<li><time>{}</time> <span>{}</span>{}</li>",
+            time_range(meeting),
+            html_escape(meeting.summary()),
+            join
+        ));
+    }
+
+    if current_date.is_some() {
+        lines.push("</ul>".to_string());
+    }
+    if meetings.is_empty() {
+        lines.push(format!("<p>{NO_MEETINGS}</p>"));
+    }
+    lines.push(format!(
+        "<footer>Updated {}</footer>",
+        now.format("%d/%m/%Y %H:%M")
+    ));
+    lines.push("</body>\n</html>".to_string());
+
+    lines.join("\n")
+}
+
+pub fn agenda(options: &Options, meetings: &[Meeting], now: DateTime<Local>) -> String {
+    match options.format {
+        Format::Xbar => xbar(options, meetings, now),
+        Format::Argos => argos(options, meetings, now),
+        Format::Alfred => alfred(meetings),
+        Format::Raycast => raycast(options, meetings, now),
+        Format::Widget => widget(options, meetings, now),
+        Format::Ndjson => list(options, meetings, now),
+        Format::Markdown => markdown(&options.locations, meetings),
+        Format::Org => org(meetings),
+        Format::Html => html(meetings, now),
+        Format::Gcalcli => gcalcli(meetings),
+        _ => next(options, next_meeting(meetings, now), now),
+    }
+}
+
+pub fn locations(meetings: &[Meeting], day: NaiveDate) -> Vec<String> {
+    working_locations(meetings, day)
+        .into_iter()
+        .filter_map(|meeting| {
+            let location = meeting.working_location()?;
+            Some(
+                match meeting.organizer_email().filter(|_| !meeting.organized()) {
+                    Some(colleague) => format!("{colleague} works from {location}"),
+                    None => format!("Working from {location}"),
+                },
+            )
+        })
+        .collect()
+}
+
+pub fn qr(link: &str) -> Result<String, qrcode::types::QrError> {
+    Ok(qrcode::QrCode::new(link)?
+        .render::<qrcode::render::unicode::Dense1x2>()
+        .dark_color(qrcode::render::unicode::Dense1x2::Light)
+        .light_color(qrcode::render::unicode::Dense1x2::Dark)
+        .build())
+}
+
+// The meeting, with what it costs under it for --cost
+fn text(options: &Options, meeting: &Meeting) -> String {
+    match &options.cost {
+        Some(rate) => format!(
+            "{meeting}\nCost: {}",
+            rate.format(stats::cost(meeting, rate.hourly_rate()))
+        ),
+        None => meeting.to_string(),
+    }
+}
+
+fn ndjson(options: &Options, meeting: &Meeting) -> String {
+    let mut value = serde_json::json!(meeting);
+    if let Some(rate) = &options.cost {
+        value["cost"] = stats::cost(meeting, rate.hourly_rate()).into();
+    }
+    value.to_string()
+}
+
+pub fn list(options: &Options, meetings: &[Meeting], now: DateTime<Local>) -> String {
+    match options.format {
+        Format::Text => options
+            .locations
+            .iter()
+            .map(|location| format!("{location}\n"))
+            .chain(
+                meetings
+                    .iter()
+                    .map(|meeting| format!("{}\n", text(options, meeting))),
+            )
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Format::Ndjson => meetings
+            .iter()
+            .map(|meeting| ndjson(options, meeting))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => agenda(options, meetings, now),
+    }
+}
+
+pub fn next(options: &Options, meeting: Option<&Meeting>, now: DateTime<Local>) -> String {
+    match options.format {
+        Format::Text => match meeting {
+            None => NO_MEETINGS.to_string(),
+            Some(meeting) if meeting.out_of_office() => out_of_office(meeting),
+            Some(meeting) => text(options, meeting),
+        },
+        Format::I3blocks => i3blocks(options, meeting, now),
+        Format::I3bar => i3bar(options, meeting, now),
+        Format::Xmobar => xmobar(options, meeting, now),
+        Format::Tmux => tmux(options, meeting, now),
+        Format::Ndjson => meeting
+            .map(|meeting| ndjson(options, meeting))
+            .unwrap_or_default(),
+        Format::Xbar
+        | Format::Argos
+        | Format::Alfred
+        | Format::Raycast
+        | Format::Widget
+        | Format::Markdown
+        | Format::Org
+        | Format::Html
+        | Format::Gcalcli => agenda(
+            options,
+            meeting.map(std::slice::from_ref).unwrap_or_default(),
+            now,
+        ),
+    }
+}
+
+fn out_of_office(meeting: &Meeting) -> String {
+    match meeting.end() {
+        Ok(end) => format!("You're out of office until {}", end.format("%d/%m %H:%M")),
+        Err(_) => "You're out of office".to_string(),
+    }
+}
+
+pub fn now(meeting: Option<&Meeting>) -> String {
+    match meeting {
+        Some(meeting) => match meeting.end() {
+            Ok(end) => format!("{} until {}", meeting.summary(), end.format("%H:%M")),
+            Err(_) => meeting.summary().to_string(),
+        },
+        None => "free".to_string(),
+    }
+}
+
+pub fn free_at(until: Option<DateTime<Local>>) -> String {
+    match until {
+        Some(until) => format!("free at {}", until.format("%H:%M")),
+        None => "free".to_string(),
+    }
+}
+
+pub fn gap(gap: (DateTime<Local>, DateTime<Local>)) -> String {
+    let (start, end) = gap;
+    format!(
+        "{} - {} ({})",
+        start.format("%H:%M"),
+        end.format("%H:%M"),
+        stats::hours(end - start)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting() -> Meeting {
+        serde_json::from_str(
+            r#"{
+                "summary": "Standup",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" },
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                "attendees": [{ "self": true, "responseStatus": "accepted" }]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn at(date_time: &str) -> DateTime<Local> {
+        date_time.parse().unwrap()
+    }
+
+    fn options(format: Format) -> Options {
+        Options {
+            format,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!("i3blocks".parse(), Ok(Format::I3blocks));
+        assert_eq!("i3bar".parse(), Ok(Format::I3bar));
+        assert_eq!("xmobar".parse(), Ok(Format::Xmobar));
+        assert_eq!("tmux".parse(), Ok(Format::Tmux));
+        assert_eq!("xbar".parse(), Ok(Format::Xbar));
+        assert_eq!("argos".parse(), Ok(Format::Argos));
+        assert_eq!("alfred".parse(), Ok(Format::Alfred));
+        assert_eq!("raycast".parse(), Ok(Format::Raycast));
+        assert_eq!("widget".parse(), Ok(Format::Widget));
+        assert_eq!("ndjson".parse(), Ok(Format::Ndjson));
+        assert_eq!("markdown".parse(), Ok(Format::Markdown));
+        assert_eq!("org".parse(), Ok(Format::Org));
+        assert_eq!("html".parse(), Ok(Format::Html));
+        assert_eq!("gcalcli".parse(), Ok(Format::Gcalcli));
+        assert!("unknown".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn i3blocks_three_lines() {
+        let m = meeting();
+        let start = m.start().unwrap().format("%H:%M").to_string();
+        let result = next(
+            &options(Format::I3blocks),
+            Some(&m),
+            at("2023-03-01T09:00:00+01:00"),
+        );
+
+        assert_eq!(result, format!("{start} Standup\n{start}\n"));
+    }
+
+    #[test]
+    fn i3blocks_color_when_imminent() {
+        let m = meeting();
+        let result = next(
+            &options(Format::I3blocks),
+            Some(&m),
+            at("2023-03-01T09:50:00+01:00"),
+        );
+
+        assert_eq!(result.lines().nth(2), Some(COLOR_SOON));
+
+        let result = next(
+            &options(Format::I3blocks),
+            Some(&m),
+            at("2023-03-01T10:05:00+01:00"),
+        );
+
+        assert_eq!(result.lines().nth(2), Some(COLOR_NOW));
+    }
+
+    #[test]
+    fn i3blocks_without_meeting() {
+        assert_eq!(next(&options(Format::I3blocks), None, Local::now()), "");
+    }
+
+    #[test]
+    fn i3bar_block() {
+        let m = meeting();
+        let result = next(
+            &options(Format::I3bar),
+            Some(&m),
+            at("2023-03-01T09:58:00+01:00"),
+        );
+        let block: serde_json::Value = serde_json::from_str(&result).unwrap();
+
+        assert!(block["full_text"].as_str().unwrap().ends_with("Standup"));
+        assert_eq!(block["color"], COLOR_NOW);
+        assert_eq!(
+            block["_recompute_at"]
+                .as_str()
+                .map(|at| at.parse::<DateTime<Local>>().unwrap()),
+            m.start().ok()
+        );
+    }
+
+    #[test]
+    fn xmobar_markup() {
+        let m = meeting();
+        let start = m.start().unwrap().format("%H:%M").to_string();
+
+        let result = next(
+            &options(Format::Xmobar),
+            Some(&m),
+            at("2023-03-01T09:00:00+01:00"),
+        );
+        assert_eq!(result, format!("<raw=13:{start} Standup/>"));
+
+        let result = next(
+            &options(Format::Xmobar),
+            Some(&m),
+            at("2023-03-01T09:58:00+01:00"),
+        );
+        assert_eq!(
+            result,
+            format!("<fc={COLOR_NOW}><raw=13:{start} Standup/></fc>")
+        );
+
+        let hostile: Meeting = serde_json::from_str(
+            r#"{
+                "summary": "<action=`rm -rf ~`>x</action>",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" }
+            }"#,
+        )
+        .unwrap();
+        let result = next(
+            &options(Format::Xmobar),
+            Some(&hostile),
+            at("2023-03-01T09:00:00+01:00"),
+        );
+        assert_eq!(
+            result,
+            format!("<raw=35:{start} <action=`rm -rf ~`>x</action>/>")
+        );
+    }
+
+    #[test]
+    fn tmux_segment() {
+        let m = meeting();
+        let start = m.start().unwrap().format("%H:%M").to_string();
+
+        let result = next(
+            &options(Format::Tmux),
+            Some(&m),
+            at("2023-03-01T09:58:00+01:00"),
+        );
+        assert_eq!(
+            result,
+            format!("#[fg={COLOR_NOW}]{start} Standup#[default]")
+        );
+    }
+
+    #[test]
+    fn truncates_to_max_width() {
+        let m = meeting();
+        let options = Options {
+            format: Format::Tmux,
+            max_width: Some(8),
+            ..Default::default()
+        };
+
+        let result = next(&options, Some(&m), at("2023-03-01T09:00:00+01:00"));
+        assert_eq!(result.chars().count(), 8);
+        assert!(result.ends_with('…'));
+    }
+
+    #[test]
+    fn xbar_menu() {
+        let m = meeting();
+        let start = m.start().unwrap().format("%H:%M").to_string();
+
+        let result = agenda(
+            &options(Format::Xbar),
+            &[m],
+            at("2023-03-01T09:00:00+01:00"),
+        );
+        assert_eq!(
+            result,
+            format!(
+                "{start} Standup\n---\n{start} Standup | href=https://meet.google.com/aaa-bbbb-ccc"
+            )
+        );
+    }
+
+    #[test]
+    fn xbar_without_meetings() {
+        let result = agenda(&options(Format::Xbar), &[], Local::now());
+        assert_eq!(result, format!("{NO_MEETINGS}\n---"));
+    }
+
+    #[test]
+    fn argos_menu() {
+        let m = meeting();
+        let start = m.start().unwrap().format("%H:%M").to_string();
+
+        let result = agenda(
+            &options(Format::Argos),
+            &[m],
+            at("2023-03-01T09:58:00+01:00"),
+        );
+        assert_eq!(
+            result,
+            format!(
+                "{start} Standup | iconName=x-office-calendar-symbolic color={COLOR_NOW}\n---\n{start} Standup | useMarkup=false href=https://meet.google.com/aaa-bbbb-ccc"
+            )
+        );
+    }
+
+    #[test]
+    fn alfred_items() {
+        let m = meeting();
+        let result = agenda(&options(Format::Alfred), &[m], Local::now());
+        let json: serde_json::Value = serde_json::from_str(&result).unwrap();
+        let item = &json["items"][0];
+
+        assert_eq!(item["title"], "Standup");
+        assert_eq!(item["arg"], "https://meet.google.com/aaa-bbbb-ccc");
+        assert_eq!(item["mods"]["cmd"]["variables"]["action"], "copy");
+    }
+
+    #[test]
+    fn raycast_inline_first_line() {
+        let m = meeting();
+        let range = time_range(&m);
+
+        let result = agenda(
+            &options(Format::Raycast),
+            &[m],
+            at("2023-03-01T09:48:00+01:00"),
+        );
+        assert_eq!(result, format!("Standup in 12m\n{range} Standup"));
+    }
+
+    #[test]
+    fn widget_current_and_next() {
+        let m = meeting();
+
+        let result = agenda(
+            &options(Format::Widget),
+            std::slice::from_ref(&m),
+            at("2023-03-01T09:59:00+01:00"),
+        );
+        let json: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert!(json["current"].is_null());
+        assert_eq!(json["next"]["summary"], "Standup");
+        assert_eq!(json["next_starts_in_seconds"], 60);
+        assert_eq!(json["stale"], false);
+
+        let result = agenda(
+            &options(Format::Widget),
+            &[m],
+            at("2023-03-01T10:05:00+01:00"),
+        );
+        let json: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(json["current"]["summary"], "Standup");
+        assert_eq!(json["current_ends_in_seconds"], 600);
+        assert!(json["next"].is_null());
+    }
+
+    #[test]
+    fn ndjson_one_meeting_per_line() {
+        let m = meeting();
+        let result = list(&options(Format::Ndjson), &[m.clone(), m], Local::now());
+        let lines: Vec<_> = result.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            let json: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(json["summary"], "Standup");
+        }
+    }
+
+    #[test]
+    fn markdown_agenda() {
+        let m = meeting();
+        let date = m.start().unwrap().format("%A %d/%m/%Y").to_string();
+        let range = time_range(&m);
+
+        let result = agenda(&options(Format::Markdown), &[m], Local::now());
+        assert_eq!(
+            result,
+            format!(
+                "## {date}\n\n### {range} Standup\n\n[Join](https://meet.google.com/aaa-bbbb-ccc)"
+            )
+        );
+    }
+
+    #[test]
+    fn gcalcli_agenda() {
+        let m = meeting();
+        let later: Meeting = serde_json::from_str(
+            r#"{
+                "summary": "Retro",
+                "start": { "dateTime": "2023-03-01T15:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T16:00:00+01:00" }
+            }"#,
+        )
+        .unwrap();
+        let date = m.start().unwrap().format("%a %b %d").to_string();
+        let hour = |m: &Meeting| m.start().unwrap().format("%-I:%M%P").to_string();
+
+        let result = agenda(
+            &options(Format::Gcalcli),
+            &[m.clone(), later.clone()],
+            Local::now(),
+        );
+        assert_eq!(
+            result,
+            format!(
+                "\n{date}  {:<7}  Standup\n            {:<7}  Retro\n",
+                hour(&m),
+                hour(&later)
+            )
+        );
+    }
+
+    #[test]
+    fn org_entries() {
+        let m = meeting();
+        let start = m.start().unwrap().format("%Y-%m-%d %a %H:%M").to_string();
+        let end = m.end().unwrap().format("%H:%M").to_string();
+
+        let result = agenda(&options(Format::Org), &[m], Local::now());
+        assert_eq!(
+            result,
+            format!(
+                "* Standup\n  SCHEDULED: <{start}-{end}>\n  :PROPERTIES:\n  :LINK: https://meet.google.com/aaa-bbbb-ccc\n  :END:"
+            )
+        );
+    }
+
+    #[test]
+    fn html_page() {
+        let m: Meeting = serde_json::from_str(
+            r#"{
+                "summary": "Q&A <all hands>",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" },
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc"
+            }"#,
+        )
+        .unwrap();
+
+        let result = agenda(&options(Format::Html), &[m], Local::now());
+        assert!(result.starts_with("<!DOCTYPE html>"));
+        assert!(result.ends_with("</html>"));
+        assert!(result.contains("Q&amp;A &lt;all hands&gt;"));
+        assert!(result.contains(r#"href="https://meet.google.com/aaa-bbbb-ccc""#));
+    }
+
+    #[test]
+    fn qr_code_for_link() {
+        let result = qr("https://meet.google.com/aaa-bbbb-ccc").unwrap();
+        let lines: Vec<_> = result.lines().collect();
+
+        assert!(lines.len() > 10);
+        assert!(lines
+            .iter()
+            .all(|line| line.chars().count() == lines[0].chars().count()));
+    }
+
+    #[test]
+    fn now_status() {
+        assert!(now(Some(&meeting())).starts_with("Standup until "));
+        assert_eq!(now(None), "free");
+    }
+
+    #[test]
+    fn lists_working_locations() {
+        let meetings: Vec<Meeting> = serde_json::from_str(
+            r#"[
+                { "eventType": "workingLocation",
+                  "organizer": { "email": "me@example.org", "self": true },
+                  "start": { "date": "2023-03-01" }, "end": { "date": "2023-03-02" },
+                  "workingLocationProperties": { "type": "homeOffice", "homeOffice": {} } },
+                { "eventType": "workingLocation",
+                  "organizer": { "email": "ana@example.org" },
+                  "start": { "date": "2023-03-01" }, "end": { "date": "2023-03-02" },
+                  "workingLocationProperties": { "type": "officeLocation", "officeLocation": {} } }
+            ]"#,
+        )
+        .unwrap();
+        let day = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let options = Options {
+            locations: locations(&meetings, day),
+            ..Default::default()
+        };
+        assert_eq!(
+            list(&options, &[meeting()], at("2023-03-01T09:00:00+01:00"))
+                .lines()
+                .take(3)
+                .collect::<Vec<_>>(),
+            ["Working from home", "", "ana@example.org works from office"]
+        );
+    }
+
+    #[test]
+    fn lists_cost() {
+        let options = Options {
+            cost: Some(Cost {
+                hourly_rate: Some(100.0),
+                currency: None,
+            }),
+            ..Default::default()
+        };
+        let now = at("2023-03-01T09:00:00+01:00");
+        let text = list(&options, &[meeting()], now);
+        assert!(text.ends_with("\nCost: $25\n"));
+
+        let options = Options {
+            format: Format::Ndjson,
+            ..options
+        };
+        let line: serde_json::Value =
+            serde_json::from_str(&list(&options, &[meeting()], now)).unwrap();
+        assert_eq!(line["cost"], 25.0);
+
+        // the next meeting alone, without -a
+        let line: serde_json::Value =
+            serde_json::from_str(&next(&options, Some(&meeting()), now)).unwrap();
+        assert_eq!(line["cost"], 25.0);
+        let options = Options {
+            format: Format::Text,
+            ..options
+        };
+        assert!(next(&options, Some(&meeting()), now).ends_with("\nCost: $25"));
+    }
+}
@@ -0,0 +1,176 @@
+use super::meetings::Meeting;
+use super::output::time_range;
+use crossterm::cursor;
+use crossterm::event::Event;
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEventKind;
+use crossterm::event::KeyModifiers;
+use crossterm::terminal;
+use std::error::Error;
+use std::io::Write;
+
+const MAX_ROWS: usize = 10;
+
+// Subsequence match where runs of consecutive characters and word starts count for more
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|c| *c == wanted)?;
+        score += 1;
+        if previous
+            .map(|previous| previous + 1 == found)
+            .unwrap_or(false)
+        {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (found - position) as i64;
+        previous = Some(found);
+        position = found + 1;
+    }
+
+    Some(score)
+}
+
+fn label(meeting: &Meeting) -> String {
+    format!("{}  {}", time_range(meeting), meeting.summary())
+}
+
+pub fn filter<'a>(query: &str, meetings: &'a [Meeting]) -> Vec<&'a Meeting> {
+    let mut matches: Vec<_> = meetings
+        .iter()
+        .filter_map(|meeting| score(query, &label(meeting)).map(|score| (score, meeting)))
+        .collect();
+    // stable, so equally good matches keep the agenda order
+    matches.sort_by_key(|(score, _)| -score);
+    matches.into_iter().map(|(_, meeting)| meeting).collect()
+}
+
+fn draw(
+    stderr: &mut std::io::Stderr,
+    query: &str,
+    matches: &[&Meeting],
+    selected: usize,
+) -> Result<u16, Box<dyn Error>> {
+    crossterm::queue!(
+        stderr,
+        cursor::MoveToColumn(0),
+        terminal::Clear(terminal::ClearType::FromCursorDown)
+    )?;
+    write!(stderr, "> {query}")?;
+
+    let rows = matches.len().min(MAX_ROWS);
+    for (index, meeting) in matches.iter().take(rows).enumerate() {
+        let marker = if index == selected { "▶" } else { " " };
+        write!(stderr, "\r\n{marker} {}", label(meeting))?;
+    }
+    if rows > 0 {
+        crossterm::queue!(stderr, cursor::MoveToPreviousLine(rows as u16))?;
+    }
+    crossterm::queue!(
+        stderr,
+        cursor::MoveToColumn(2 + query.chars().count() as u16)
+    )?;
+    stderr.flush()?;
+    Ok(rows as u16)
+}
+
+fn select(meetings: &[Meeting]) -> Result<Option<&Meeting>, Box<dyn Error>> {
+    let mut stderr = std::io::stderr();
+    let mut query = String::new();
+    let mut selected = 0;
+
+    loop {
+        let matches = filter(&query, meetings);
+        selected = selected.min(matches.len().min(MAX_ROWS).saturating_sub(1));
+        draw(&mut stderr, &query, &matches, selected)?;
+
+        let Event::Key(key) = crossterm::event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if control => return Ok(None),
+            KeyCode::Enter => return Ok(matches.get(selected).copied()),
+            KeyCode::Down | KeyCode::Tab => selected += 1,
+            KeyCode::Char('n') if control => selected += 1,
+            KeyCode::Up | KeyCode::BackTab => selected = selected.saturating_sub(1),
+            KeyCode::Char('p') if control => selected = selected.saturating_sub(1),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(c) => {
+                query.push(c);
+                selected = 0;
+            }
+            _ => (),
+        }
+    }
+}
+
+pub fn pick(meetings: &[Meeting]) -> Result<Option<&Meeting>, Box<dyn Error>> {
+    terminal::enable_raw_mode()?;
+    let result = select(meetings);
+
+    let mut stderr = std::io::stderr();
+    crossterm::execute!(
+        stderr,
+        cursor::MoveToColumn(0),
+        terminal::Clear(terminal::ClearType::FromCursorDown)
+    )?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(summary: &str, start: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "summary": summary,
+            "start": { "dateTime": start },
+            "end": { "dateTime": start }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn scores_subsequences() {
+        assert!(score("sup", "Standup").is_some());
+        assert!(score("pus", "Standup").is_none());
+        assert!(score("", "Standup").is_some());
+        assert!(score("stand", "Standup") > score("stand", "Sprint planning and demo"));
+        assert!(score("re", "Design review") > score("re", "Quarterly planning"));
+    }
+
+    #[test]
+    fn filters_and_ranks() {
+        let meetings = [
+            meeting("Sprint planning", "2023-03-01T09:00:00+01:00"),
+            meeting("Standup", "2023-03-01T10:00:00+01:00"),
+            meeting("1:1 with Anna", "2023-03-01T11:00:00+01:00"),
+        ];
+
+        let summaries = |query| {
+            filter(query, &meetings)
+                .iter()
+                .map(|m| m.summary().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(summaries("stand"), vec!["Standup"]);
+        assert_eq!(summaries("sp"), vec!["Sprint planning", "Standup"]);
+        assert_eq!(summaries("anna"), vec!["1:1 with Anna"]);
+        assert_eq!(summaries("").len(), 3);
+    }
+}
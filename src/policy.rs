@@ -0,0 +1,179 @@
+use super::meetings::Meeting;
+use super::settings::Match;
+use super::settings::Policy;
+use super::template::meeting_value;
+use std::error::Error;
+
+const DEFAULT_COMMENT: &str = "Declined automatically, this conflicts with {{ protected.summary }}";
+
+pub struct Decision<'a> {
+    pub meeting: &'a Meeting,
+    pub protected: &'a Meeting,
+    pub policy: &'a Policy,
+}
+
+pub fn matches(rule: &Match, meeting: &Meeting) -> bool {
+    let summary = rule.summary.as_ref().is_none_or(|summary| {
+        meeting
+            .summary()
+            .to_lowercase()
+            .contains(&summary.to_lowercase())
+    });
+    let organizer = rule
+        .organizer
+        .as_ref()
+        .is_none_or(|organizer| meeting.organizer_email() == Some(organizer.as_str()));
+    let event_type = rule
+        .event_type
+        .as_ref()
+        .is_none_or(|event_type| meeting.event_type() == event_type);
+    let optional = rule
+        .optional
+        .is_none_or(|optional| meeting.optional() == optional);
+    summary && organizer && event_type && optional
+}
+
+fn overlap(a: &Meeting, b: &Meeting) -> bool {
+    match (a.start(), a.end(), b.start(), b.end()) {
+        (Ok(a_start), Ok(a_end), Ok(b_start), Ok(b_end)) => a_start < b_end && b_start < a_end,
+        _ => false,
+    }
+}
+
+// Only invitations still waiting for an answer, or tentatively accepted, are declined;
+// what I accepted myself is never undone
+fn undecided(meeting: &Meeting) -> bool {
+    !meeting.organized() && matches!(meeting.response_status(), Some("needsAction" | "tentative"))
+}
+
+fn committed(meeting: &Meeting) -> bool {
+    meeting.organized() || meeting.accepted()
+}
+
+pub fn evaluate<'a>(policies: &'a [Policy], meetings: &'a [Meeting]) -> Vec<Decision<'a>> {
+    let mut decisions: Vec<Decision> = vec![];
+    for meeting in meetings.iter().filter(|meeting| undecided(meeting)) {
+        let decision = policies.iter().find_map(|policy| {
+            if !matches(&policy.decline, meeting) {
+                return None;
+            }
+            meetings
+                .iter()
+                .filter(|other| committed(other) && other.id() != meeting.id())
+                .find(|other| matches(&policy.protect, other) && overlap(meeting, other))
+                .map(|protected| Decision {
+                    meeting,
+                    protected,
+                    policy,
+                })
+        });
+        decisions.extend(decision);
+    }
+    decisions
+}
+
+pub fn comment(decision: &Decision) -> Result<String, Box<dyn Error>> {
+    let template = decision
+        .policy
+        .comment
+        .as_deref()
+        .unwrap_or(DEFAULT_COMMENT);
+    let mut context = tera::Context::new();
+    context.insert("meeting", &meeting_value(decision.meeting));
+    context.insert("protected", &meeting_value(decision.protected));
+    context.insert("policy", &decision.policy.name);
+    Ok(tera::Tera::one_off(template, &context, false)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(id: &str, summary: &str, start: &str, end: &str, response: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "summary": summary,
+            "organizer": { "email": "boss@example.org" },
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "attendees": [{ "self": true, "responseStatus": response }]
+        }))
+        .unwrap()
+    }
+
+    fn policy() -> Policy {
+        serde_json::from_value(serde_json::json!({
+            "name": "1:1 first",
+            "protect": { "summary": "1:1", "organizer": "boss@example.org" },
+            "comment": "Sorry, I have {{ protected.summary }} then"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn declines_conflicting_invitations() {
+        let meetings = [
+            meeting(
+                "one",
+                "1:1 Ana / Boss",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:30:00+01:00",
+                "accepted",
+            ),
+            meeting(
+                "overlapping",
+                "All hands",
+                "2023-03-01T10:15:00+01:00",
+                "2023-03-01T11:00:00+01:00",
+                "needsAction",
+            ),
+            meeting(
+                "accepted",
+                "Standup",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:15:00+01:00",
+                "accepted",
+            ),
+            meeting(
+                "later",
+                "Review",
+                "2023-03-01T10:30:00+01:00",
+                "2023-03-01T11:00:00+01:00",
+                "tentative",
+            ),
+        ];
+        let policies = [policy()];
+
+        let decisions = evaluate(&policies, &meetings);
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].meeting.id(), Some("overlapping"));
+        assert_eq!(decisions[0].protected.id(), Some("one"));
+        assert_eq!(
+            comment(&decisions[0]).unwrap(),
+            "Sorry, I have 1:1 Ana / Boss then"
+        );
+    }
+
+    #[test]
+    fn matches_rules() {
+        let m = meeting(
+            "x",
+            "Weekly 1:1",
+            "2023-03-01T10:00:00+01:00",
+            "2023-03-01T10:30:00+01:00",
+            "accepted",
+        );
+        assert!(matches(&Match::default(), &m));
+        assert!(matches(&policy().protect, &m));
+        let focus = Match {
+            event_type: Some("focusTime".to_string()),
+            ..Default::default()
+        };
+        assert!(!matches(&focus, &m));
+        let optional = Match {
+            optional: Some(true),
+            ..Default::default()
+        };
+        assert!(!matches(&optional, &m));
+    }
+}
@@ -0,0 +1,163 @@
+use super::settings;
+use super::tokens::Tokens;
+use chrono::DateTime;
+use chrono::Local;
+use chrono::TimeZone;
+use hyper::HeaderMap;
+use serde::Deserialize;
+use std::error::Error;
+
+const CALENDAR_URL: &str = "https://www.googleapis.com/calendar/v3";
+
+#[derive(Debug, Clone)]
+pub struct Channel {
+    id: String,
+    resource_id: String,
+    pub expiration: Option<DateTime<Local>>,
+}
+
+#[derive(Deserialize)]
+struct ChannelResponse {
+    id: String,
+    #[serde(rename = "resourceId")]
+    resource_id: String,
+    expiration: Option<String>,
+}
+
+impl ChannelResponse {
+    fn channel(self) -> Channel {
+        let expiration = self
+            .expiration
+            .and_then(|ms| ms.parse().ok())
+            .and_then(|ms| Local.timestamp_millis_opt(ms).single());
+        Channel {
+            id: self.id,
+            resource_id: self.resource_id,
+            expiration,
+        }
+    }
+}
+
+async fn post(
+    tokens: &Tokens,
+    url: String,
+    body: serde_json::Value,
+) -> Result<reqwest::Response, Box<dyn Error>> {
+    let response = crate::http::client()
+        .post(url)
+        .bearer_auth(&tokens.access_token)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Google returned {}", response.status()).into());
+    }
+    Ok(response)
+}
+
+async fn watch(
+    tokens: &Tokens,
+    push: &settings::Push,
+    calendar: &str,
+) -> Result<Channel, Box<dyn Error>> {
+    let id = format!("nextmeet-{}", Local::now().timestamp_nanos());
+    let mut body = serde_json::json!({
+        "id": id,
+        "type": "web_hook",
+        "address": push.address,
+    });
+    if let Some(token) = &push.token {
+        body["token"] = token.clone().into();
+    }
+
+    let url = format!("{CALENDAR_URL}/calendars/{calendar}/events/watch");
+    let response = post(tokens, url, body).await?.text().await?;
+    Ok(serde_json::from_str::<ChannelResponse>(&response)?.channel())
+}
+
+// A channel for every calendar that is read, all of them or none
+pub async fn watch_all(
+    tokens: &Tokens,
+    push: &settings::Push,
+    calendars: &[String],
+) -> Result<Vec<Channel>, Box<dyn Error>> {
+    let mut channels = vec![];
+    for calendar in calendars {
+        match watch(tokens, push, calendar).await {
+            Ok(channel) => channels.push(channel),
+            Err(err) => {
+                for channel in &channels {
+                    // they expire on their own if this fails too
+                    let _ = stop(tokens, channel).await;
+                }
+                return Err(format!("{calendar}: {err}").into());
+            }
+        }
+    }
+    Ok(channels)
+}
+
+pub async fn stop(tokens: &Tokens, channel: &Channel) -> Result<(), Box<dyn Error>> {
+    let body = serde_json::json!({ "id": channel.id, "resourceId": channel.resource_id });
+    post(tokens, format!("{CALENDAR_URL}/channels/stop"), body).await?;
+    Ok(())
+}
+
+// Google sends a "sync" message when the channel is created, every other state means
+// the calendar changed
+pub fn changed(headers: &HeaderMap, token: Option<&str>) -> Result<bool, String> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if token.is_some() && header("X-Goog-Channel-Token") != token {
+        return Err("Invalid channel token".into());
+    }
+    match header("X-Goog-Resource-State") {
+        Some("sync") => Ok(false),
+        Some(_) => Ok(true),
+        None => Err("Missing resource state".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn detects_changes() {
+        let sync = headers(&[("X-Goog-Resource-State", "sync")]);
+        assert_eq!(changed(&sync, None), Ok(false));
+
+        let exists = headers(&[
+            ("X-Goog-Resource-State", "exists"),
+            ("X-Goog-Channel-Token", "secret"),
+        ]);
+        assert_eq!(changed(&exists, Some("secret")), Ok(true));
+        assert!(changed(&exists, Some("other")).is_err());
+        assert!(changed(&headers(&[]), None).is_err());
+    }
+
+    #[test]
+    fn parses_expiration() {
+        let channel = serde_json::from_str::<ChannelResponse>(
+            r#"{ "id": "nextmeet-1", "resourceId": "abc", "expiration": "1677661200000" }"#,
+        )
+        .unwrap()
+        .channel();
+
+        assert_eq!(channel.resource_id, "abc");
+        assert_eq!(
+            channel.expiration.map(|at| at.timestamp()),
+            Some(1_677_661_200)
+        );
+    }
+}
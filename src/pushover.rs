@@ -0,0 +1,55 @@
+use super::notifier::Notifier;
+use super::notifier::Reminder;
+use super::settings;
+use async_trait::async_trait;
+use std::error::Error;
+
+const API_URL: &str = "https://api.pushover.net/1/messages.json";
+
+pub struct Pushover {
+    settings: settings::Pushover,
+}
+
+impl Pushover {
+    pub fn new(settings: settings::Pushover) -> Pushover {
+        Pushover { settings }
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for Pushover {
+    fn name(&self) -> &'static str {
+        "pushover"
+    }
+
+    fn before(&self) -> &[String] {
+        &self.settings.before
+    }
+
+    async fn send(&self, reminder: &Reminder) -> Result<(), Box<dyn Error>> {
+        let mut form = vec![
+            ("token", self.settings.token.as_str()),
+            ("user", self.settings.user.as_str()),
+            ("title", reminder.title.as_str()),
+            ("message", reminder.message.as_str()),
+        ];
+        if let Some(device) = &self.settings.device {
+            form.push(("device", device));
+        }
+        if reminder.urgent {
+            form.push(("priority", "1"));
+        }
+        if let Some(link) = &reminder.link {
+            form.push(("url", link));
+            form.push(("url_title", "Join"));
+        }
+
+        crate::http::client()
+            .post(API_URL)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use super::meetings::Meeting;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use std::error::Error;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+
+// Reminders left to the OS instead of a running `watch`: transient systemd timers where
+// there is a user manager, `at` jobs elsewhere
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheduler {
+    Systemd,
+    At,
+}
+
+impl Scheduler {
+    pub fn detect() -> Result<Scheduler, Box<dyn Error>> {
+        let works = |program: &str, args: &[&str]| {
+            Command::new(program)
+                .args(args)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        };
+        if works("systemctl", &["--user", "show-environment"]) {
+            Ok(Scheduler::Systemd)
+        } else if works("atq", &[]) {
+            Ok(Scheduler::At)
+        } else {
+            Err("Neither systemd-run nor at is available".into())
+        }
+    }
+}
+
+// What --starts takes, which duration::parse_at reads back
+pub const STARTS_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+// When no hook, webhook or notifier asks for reminders at other times
+pub const DEFAULT_BEFORE_MINUTES: i64 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub at: DateTime<Local>,
+    pub start: DateTime<Local>,
+    pub id: String,
+    pub summary: String,
+}
+
+// at jobs go in a queue of their own, so the ones from an earlier run can be found
+const AT_QUEUE: &str = "n";
+
+impl Job {
+    // Stable, so scheduling again replaces the timer or at job rather than adding a second one
+    fn unit(&self, before: Duration) -> String {
+        let id: String = self
+            .id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("nextmeet-remind-{}-{}m", id, before.num_minutes())
+    }
+}
+
+// One job per meeting and offset that is still ahead of now
+pub fn jobs(
+    meetings: &[Meeting],
+    now: DateTime<Local>,
+    before: &[Duration],
+) -> Vec<(Job, Duration)> {
+    let mut jobs = vec![];
+    for meeting in meetings {
+        let (Some(id), Ok(start)) = (meeting.id(), meeting.start()) else {
+            continue;
+        };
+        for offset in before {
+            let at = start - *offset;
+            if at > now {
+                let job = Job {
+                    at,
+                    start,
+                    id: id.to_string(),
+                    summary: meeting.summary().to_string(),
+                };
+                jobs.push((job, *offset));
+            }
+        }
+    }
+    jobs.sort_by_key(|(job, _)| job.at);
+    jobs
+}
+
+// What runs when the job fires; --starts lets it skip a meeting moved since
+fn notify_command(exe: &str, job: &Job) -> Vec<String> {
+    vec![
+        exe.to_string(),
+        "notify".to_string(),
+        "--event".to_string(),
+        job.id.clone(),
+        "--starts".to_string(),
+        job.start.format(STARTS_FORMAT).to_string(),
+    ]
+}
+
+pub fn command(scheduler: Scheduler, exe: &str, job: &Job, before: Duration) -> Vec<String> {
+    match scheduler {
+        Scheduler::Systemd => [
+            "systemd-run".to_string(),
+            "--user".to_string(),
+            "--collect".to_string(),
+            format!("--unit={}", job.unit(before)),
+            format!("--on-calendar={}", job.at.format("%Y-%m-%d %H:%M:%S")),
+            "--timer-property=AccuracySec=1s".to_string(),
+        ]
+        .into_iter()
+        .chain(notify_command(exe, job))
+        .collect(),
+        // at reads the command from stdin, see schedule()
+        Scheduler::At => vec![
+            "at".to_string(),
+            "-q".to_string(),
+            AT_QUEUE.to_string(),
+            "-t".to_string(),
+            job.at.format("%Y%m%d%H%M.%S").to_string(),
+        ],
+    }
+}
+
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+// The command as it would be typed, for --dry-run
+pub fn describe(scheduler: Scheduler, exe: &str, job: &Job, before: Duration) -> String {
+    let command: Vec<_> = command(scheduler, exe, job, before)
+        .iter()
+        .map(|arg| quote(arg))
+        .collect();
+    match scheduler {
+        Scheduler::Systemd => command.join(" "),
+        Scheduler::At => {
+            let line: Vec<_> = notify_command(exe, job)
+                .iter()
+                .map(|arg| quote(arg))
+                .collect();
+            format!("echo {} | {}", quote(&line.join(" ")), command.join(" "))
+        }
+    }
+}
+
+// at jobs have no name, so each carries its unit as a comment that `at -c` shows again
+fn remove_at_jobs(unit: &str) -> Result<(), Box<dyn Error>> {
+    let queue = Command::new("atq").args(["-q", AT_QUEUE]).output()?;
+    let marker = format!("# {unit}\n");
+    for line in String::from_utf8_lossy(&queue.stdout).lines() {
+        let Some(id) = line.split_whitespace().next() else {
+            continue;
+        };
+        let script = Command::new("at").args(["-c", id]).output()?;
+        if String::from_utf8_lossy(&script.stdout).contains(&marker) {
+            Command::new("atrm").arg(id).status()?;
+        }
+    }
+    Ok(())
+}
+
+pub fn schedule(
+    scheduler: Scheduler,
+    exe: &str,
+    job: &Job,
+    before: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let command = command(scheduler, exe, job, before);
+    let mut child = Command::new(&command[0]);
+    child.args(&command[1..]).stdout(Stdio::null());
+
+    let status = match scheduler {
+        Scheduler::Systemd => {
+            // a timer left from an earlier run would make systemd-run fail
+            let _ = Command::new("systemctl")
+                .args(["--user", "stop", &format!("{}.timer", job.unit(before))])
+                .stderr(Stdio::null())
+                .status();
+            child.status()?
+        }
+        Scheduler::At => {
+            remove_at_jobs(&job.unit(before))?;
+            let mut child = child.stdin(Stdio::piped()).stderr(Stdio::null()).spawn()?;
+            let line: Vec<_> = notify_command(exe, job)
+                .iter()
+                .map(|arg| quote(arg))
+                .collect();
+            writeln!(
+                child.stdin.take().ok_or("at has no stdin")?,
+                "# {}\n{}",
+                job.unit(before),
+                line.join(" ")
+            )?;
+            child.wait()?
+        }
+    };
+    if !status.success() {
+        return Err(format!("{} failed: {}", command[0], status).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedules_upcoming_reminders() {
+        let meetings: Vec<Meeting> = serde_json::from_str(
+            r#"[
+                { "id": "standup", "summary": "Standup",
+                  "start": { "dateTime": "2023-03-01T10:00:00+01:00" } },
+                { "id": "retro_20230301T140000Z", "summary": "Retro",
+                  "start": { "dateTime": "2023-03-01T15:00:00+01:00" } }
+            ]"#,
+        )
+        .unwrap();
+        let now = "2023-03-01T09:57:00+01:00".parse().unwrap();
+        let jobs = jobs(
+            &meetings,
+            now,
+            &[Duration::minutes(1), Duration::minutes(5)],
+        );
+
+        assert_eq!(jobs.len(), 3);
+        assert_eq!(jobs[0].0.id, "standup");
+        assert_eq!(jobs[0].1, Duration::minutes(1));
+
+        let (job, before) = &jobs[2];
+        let command = command(Scheduler::Systemd, "/usr/bin/nextmeet", job, *before);
+        assert_eq!(
+            command[3],
+            "--unit=nextmeet-remind-retro_20230301T140000Z-1m"
+        );
+        assert_eq!(
+            command[6..],
+            [
+                "/usr/bin/nextmeet",
+                "notify",
+                "--event",
+                "retro_20230301T140000Z",
+                "--starts",
+                &job.start.format(STARTS_FORMAT).to_string()
+            ]
+        );
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        let at = describe(Scheduler::At, "nextmeet", job, *before);
+        assert!(at.starts_with("echo ''\\''nextmeet'\\'' '\\''notify'\\''"));
+        assert!(at.ends_with(&format!(
+            "| 'at' '-q' 'n' '-t' '{}'",
+            job.at.format("%Y%m%d%H%M.%S")
+        )));
+    }
+}
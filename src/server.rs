@@ -0,0 +1,408 @@
+use super::meetings;
+use super::meetings::next_meeting;
+use super::meetings::Meeting;
+use super::metrics;
+use super::push;
+use super::settings::Settings;
+use super::tokens::Tokens;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+const CACHE_SECONDS: i64 = 60;
+// /events re-reads today's agenda this often when nothing else refreshes it
+pub const EVENTS_REFRESH_SECONDS: u64 = 60;
+// Google access tokens last an hour
+const TOKEN_MINUTES: i64 = 45;
+
+#[derive(Default)]
+pub struct State {
+    pub meetings: Vec<Meeting>,
+    pub refreshed_at: Option<DateTime<Local>>,
+    pub api_errors: u64,
+    pub consecutive_errors: u32,
+    pub push_token: Option<String>,
+    // set by a push notification, only the watch loop that refetches clears it
+    pub pushed: bool,
+    // today's agenda, subscribers of /events are woken up when it changes
+    pub updates: tokio::sync::watch::Sender<Vec<Meeting>>,
+    cache: HashMap<NaiveDate, (DateTime<Local>, Vec<Meeting>)>,
+    tokens: Option<(DateTime<Local>, Arc<Tokens>)>,
+    // whether the loop re-reading the agenda for /events is running
+    refreshing: bool,
+}
+
+impl State {
+    // Expired days go first, so asking for arbitrary dates can't grow the cache for good
+    fn remember(&mut self, date: NaiveDate, meetings: Vec<Meeting>, now: DateTime<Local>) {
+        self.cache
+            .retain(|_, (at, _)| now - *at < Duration::seconds(CACHE_SECONDS));
+        self.cache.insert(date, (now, meetings));
+    }
+
+    pub fn update_today(&mut self, meetings: Vec<Meeting>, now: DateTime<Local>) {
+        self.remember(now.date_naive(), meetings.clone(), now);
+        self.updates.send_if_modified(|current| {
+            let changed = *current != meetings;
+            if changed {
+                *current = meetings.clone();
+            }
+            changed
+        });
+        self.meetings = meetings;
+        self.refreshed_at = Some(now);
+        self.consecutive_errors = 0;
+    }
+
+    pub fn failed(&mut self) {
+        self.api_errors += 1;
+        self.consecutive_errors += 1;
+    }
+}
+
+// Healthy until syncing has failed for longer than alert_after
+pub fn health(
+    state: &State,
+    token_expires_at: Option<DateTime<Local>>,
+    alert_after: Duration,
+    now: DateTime<Local>,
+) -> (bool, serde_json::Value) {
+    let healthy = state.consecutive_errors == 0
+        || state.refreshed_at.is_some_and(|at| now - at <= alert_after);
+    let value = serde_json::json!({
+        "status": if healthy { "ok" } else { "failing" },
+        "last_sync": state.refreshed_at.map(|at| at.to_rfc3339()),
+        "token_expires_at": token_expires_at.map(|at| at.to_rfc3339()),
+        "consecutive_errors": state.consecutive_errors,
+    });
+    (healthy, value)
+}
+
+pub type Shared = Arc<Mutex<State>>;
+
+pub async fn tokens(shared: &Shared) -> Result<Arc<Tokens>, String> {
+    let now = Local::now();
+    if let Some((at, tokens)) = &shared.lock().unwrap().tokens {
+        if now - *at < Duration::minutes(TOKEN_MINUTES) {
+            return Ok(tokens.clone());
+        }
+    }
+
+    let tokens = Tokens::load().map_err(|err| err.to_string())?;
+    let tokens = tokens.refresh().await.map_err(|err| err.to_string())?;
+
+    let tokens = Arc::new(tokens);
+    shared.lock().unwrap().tokens = Some((now, tokens.clone()));
+    Ok(tokens)
+}
+
+pub async fn agenda(shared: &Shared, date: NaiveDate) -> Result<Vec<Meeting>, String> {
+    let now = Local::now();
+    if let Some((at, meetings)) = shared.lock().unwrap().cache.get(&date) {
+        if now - *at < Duration::seconds(CACHE_SECONDS) {
+            return Ok(meetings.clone());
+        }
+    }
+
+    let tokens = tokens(shared).await?;
+    let result = meetings::retrieve_range_with_tokens(&tokens, date, date)
+        .await
+        .map(meetings::agenda)
+        .map_err(|err| err.to_string());
+
+    let mut state = shared.lock().unwrap();
+    match &result {
+        Ok(meetings) if date == now.date_naive() => state.update_today(meetings.clone(), now),
+        Ok(meetings) => state.remember(date, meetings.clone(), now),
+        Err(_) => state.failed(),
+    }
+    result
+}
+
+fn respond(status: StatusCode, content_type: &str, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", content_type)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn json(result: Result<serde_json::Value, String>) -> Response<Body> {
+    match result {
+        Ok(value) => respond(StatusCode::OK, "application/json", value.to_string()),
+        Err(err) => respond(
+            StatusCode::BAD_GATEWAY,
+            "application/json",
+            serde_json::json!({ "error": err }).to_string(),
+        ),
+    }
+}
+
+fn sse(meetings: &[Meeting]) -> String {
+    format!("event: agenda\ndata: {}\n\n", serde_json::json!(meetings))
+}
+
+// A single loop for however many /events clients are connected, ending with the last one
+fn refresh_for_events(shared: &Shared) {
+    {
+        let mut state = shared.lock().unwrap();
+        if state.refreshing {
+            return;
+        }
+        state.refreshing = true;
+    }
+
+    let shared = shared.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(EVENTS_REFRESH_SECONDS)).await;
+            {
+                let mut state = shared.lock().unwrap();
+                if state.updates.receiver_count() == 0 {
+                    state.refreshing = false;
+                    return;
+                }
+            }
+            let _ = agenda(&shared, Local::now().date_naive()).await;
+        }
+    });
+}
+
+// A snapshot of today's agenda on connect and then on every change, with a keepalive
+// comment whenever a while passes with nothing new
+fn events(shared: Shared) -> Response<Body> {
+    let mut updates = shared.lock().unwrap().updates.subscribe();
+    let (mut sender, body) = Body::channel();
+    refresh_for_events(&shared);
+
+    tokio::spawn(async move {
+        let _ = agenda(&shared, Local::now().date_naive()).await;
+        loop {
+            let snapshot = sse(&updates.borrow_and_update());
+            if sender.send_data(snapshot.into()).await.is_err() {
+                return;
+            }
+            loop {
+                let keepalive = std::time::Duration::from_secs(EVENTS_REFRESH_SECONDS);
+                tokio::select! {
+                    changed = updates.changed() => match changed {
+                        Ok(_) => break,
+                        Err(_) => return,
+                    },
+                    _ = tokio::time::sleep(keepalive) => {
+                        if sender.send_data(": keepalive\n\n".into()).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    Response::builder()
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .body(body)
+        .unwrap()
+}
+
+fn query_date(request: &Request<Body>) -> Result<NaiveDate, String> {
+    let query = request.uri().query().unwrap_or_default();
+    match query.split('&').find_map(|pair| pair.strip_prefix("date=")) {
+        Some(date) => date.parse().map_err(|_| format!("Invalid date: {date}")),
+        None => Ok(Local::now().date_naive()),
+    }
+}
+
+async fn handle(request: Request<Body>, shared: Shared) -> Result<Response<Body>, Infallible> {
+    let now = Local::now();
+    let today = now.date_naive();
+
+    Ok(match request.uri().path() {
+        "/healthz" => {
+            // both are file reads, kept off the server's threads
+            let (token_expires_at, alert_after) = tokio::task::spawn_blocking(|| {
+                let token_expires_at = Tokens::load().ok().and_then(|tokens| tokens.expires_at);
+                let alert_after = Settings::load()
+                    .ok()
+                    .and_then(|settings| settings.polling.alert_after().ok());
+                (token_expires_at, alert_after)
+            })
+            .await
+            .unwrap_or_default();
+            let alert_after = alert_after.unwrap_or_else(|| Duration::hours(1));
+            let (healthy, value) =
+                health(&shared.lock().unwrap(), token_expires_at, alert_after, now);
+            let status = if healthy {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            respond(status, "application/json", value.to_string())
+        }
+        "/metrics" => respond(
+            StatusCode::OK,
+            "text/plain; version=0.0.4",
+            metrics::render(&shared.lock().unwrap(), now),
+        ),
+        "/next" => json(
+            agenda(&shared, today)
+                .await
+                .map(|meetings| serde_json::json!(next_meeting(&meetings, now))),
+        ),
+        "/events" => events(shared.clone()),
+        "/today" => json(agenda(&shared, today).await.map(|m| serde_json::json!(m))),
+        "/agenda" => match query_date(&request) {
+            Ok(date) => json(agenda(&shared, date).await.map(|m| serde_json::json!(m))),
+            Err(err) => respond(
+                StatusCode::BAD_REQUEST,
+                "application/json",
+                serde_json::json!({ "error": err }).to_string(),
+            ),
+        },
+        "/push" => {
+            let mut state = shared.lock().unwrap();
+            match push::changed(request.headers(), state.push_token.as_deref()) {
+                Ok(changed) => {
+                    if changed {
+                        state.pushed = true;
+                        state.cache.clear();
+                    }
+                    respond(StatusCode::OK, "text/plain", String::new())
+                }
+                Err(err) => respond(StatusCode::FORBIDDEN, "text/plain", err + "\n"),
+            }
+        }
+        _ => respond(StatusCode::NOT_FOUND, "text/plain", "Not found\n".into()),
+    })
+}
+
+pub fn spawn(listen: &str, shared: Shared) -> Result<(), Box<dyn Error>> {
+    let addr: SocketAddr = listen
+        .parse()
+        .map_err(|_| format!("Invalid listen address: {listen}"))?;
+
+    let make_service = make_service_fn(move |_| {
+        let shared = shared.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(request, shared.clone()))) }
+    });
+    #[cfg(unix)]
+    let builder = match crate::systemd::listener() {
+        Some(listener) => {
+            listener.set_nonblocking(true)?;
+            hyper::Server::from_tcp(listener)?
+        }
+        None => hyper::Server::try_bind(&addr)?,
+    };
+    #[cfg(not(unix))]
+    let builder = hyper::Server::try_bind(&addr)?;
+    let server = builder.serve(make_service);
+
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            eprintln!("Error: server: {}", err);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_events() {
+        let meeting: Meeting = serde_json::from_str(r#"{ "summary": "Standup" }"#).unwrap();
+        let event = sse(&[meeting]);
+        assert!(event.starts_with("event: agenda\ndata: [{"));
+        assert!(event.contains("\"Standup\""));
+        assert!(event.ends_with("}]\n\n"));
+    }
+
+    #[test]
+    fn notifies_changed_agenda() {
+        let mut state = State::default();
+        let mut updates = state.updates.subscribe();
+        let meeting: Meeting = serde_json::from_str(r#"{ "summary": "Standup" }"#).unwrap();
+
+        state.update_today(vec![], Local::now());
+        assert!(!updates.has_changed().unwrap());
+
+        state.update_today(vec![meeting.clone()], Local::now());
+        assert!(updates.has_changed().unwrap());
+        assert_eq!(*updates.borrow_and_update(), vec![meeting]);
+    }
+
+    #[test]
+    fn evicts_expired_days() {
+        let at = |date_time: &str| date_time.parse::<DateTime<Local>>().unwrap();
+        let mut state = State::default();
+        for day in 1..=28 {
+            let date = NaiveDate::from_ymd_opt(2023, 2, day).unwrap();
+            state.remember(date, vec![], at("2023-03-01T09:00:00+01:00"));
+        }
+        assert_eq!(state.cache.len(), 28);
+
+        state.update_today(vec![], at("2023-03-01T09:05:00+01:00"));
+        assert_eq!(state.cache.len(), 1);
+    }
+
+    #[test]
+    fn reports_health() {
+        let at = |date_time: &str| date_time.parse::<DateTime<Local>>().unwrap();
+        let mut state = State::default();
+        state.update_today(vec![], at("2023-03-01T09:00:00+01:00"));
+        state.failed();
+
+        let (healthy, value) = health(
+            &state,
+            None,
+            Duration::hours(1),
+            at("2023-03-01T09:30:00+01:00"),
+        );
+        assert!(healthy);
+        assert_eq!(value["consecutive_errors"], 1);
+        assert_eq!(value["token_expires_at"], serde_json::Value::Null);
+
+        let (healthy, value) = health(
+            &state,
+            None,
+            Duration::hours(1),
+            at("2023-03-01T10:30:00+01:00"),
+        );
+        assert!(!healthy);
+        assert_eq!(value["status"], "failing");
+    }
+
+    #[test]
+    fn parses_agenda_date() {
+        let request = Request::get("/agenda?date=2023-03-01")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            query_date(&request),
+            Ok(NaiveDate::from_ymd_opt(2023, 3, 1).unwrap())
+        );
+
+        let request = Request::get("/agenda?date=tomorrow")
+            .body(Body::empty())
+            .unwrap();
+        assert!(query_date(&request).is_err());
+
+        let request = Request::get("/agenda").body(Body::empty()).unwrap();
+        assert_eq!(query_date(&request), Ok(Local::now().date_naive()));
+    }
+}
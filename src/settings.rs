@@ -0,0 +1,451 @@
+use super::duration;
+use chrono::Duration;
+use chrono::NaiveTime;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Hooks {
+    pub on_meeting_start: Option<String>,
+    pub on_meeting_end: Option<String>,
+    pub before_meeting: Option<String>,
+    #[serde(default)]
+    pub before: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Discord {
+    pub client_id: String,
+}
+
+// Kept without the mqtt feature so configs stay valid across builds
+#[cfg_attr(not(feature = "mqtt"), allow(dead_code))]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Mqtt {
+    pub host: String,
+    pub port: Option<u16>,
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: Option<String>,
+}
+
+// The daily agenda mail watch sends at `at` (HH:MM), with the email feature; port 465 means
+// TLS from the start, anything else STARTTLS. The body is the digest unless `template`
+// names a file rendered like --template
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Email {
+    pub server: String,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    pub at: String,
+    pub subject: Option<String>,
+    pub template: Option<String>,
+}
+
+impl Email {
+    pub fn at(&self) -> Result<NaiveTime, String> {
+        NaiveTime::parse_from_str(&self.at, "%H:%M")
+            .map_err(|_| format!("Invalid time: {}", self.at))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub before: Vec<String>,
+    pub payload: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Ntfy {
+    pub server: Option<String>,
+    pub topic: String,
+    pub token: Option<String>,
+    pub priority: Option<String>,
+    #[serde(default)]
+    pub before: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Pushover {
+    pub token: String,
+    pub user: String,
+    pub device: Option<String>,
+    #[serde(default)]
+    pub before: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Gotify {
+    pub server: String,
+    pub token: String,
+    pub priority: Option<u8>,
+    #[serde(default)]
+    pub before: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Telegram {
+    pub bot_token: String,
+    pub chat_id: String,
+    #[serde(default)]
+    pub before: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub homeserver: String,
+    pub access_token: String,
+    pub room_id: String,
+    #[serde(default)]
+    pub before: Vec<String>,
+}
+
+#[cfg_attr(not(feature = "desktop"), allow(dead_code))]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Sound {
+    pub file: Option<String>,
+    #[serde(default = "default_escalate")]
+    pub escalate: bool,
+}
+
+fn default_escalate() -> bool {
+    true
+}
+
+#[cfg_attr(not(feature = "desktop"), allow(dead_code))]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Desktop {
+    pub snooze: Option<String>,
+    pub sound: Option<Sound>,
+    #[serde(default)]
+    pub before: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NotifierSettings {
+    Ntfy(Ntfy),
+    Pushover(Pushover),
+    Gotify(Gotify),
+    Telegram(Telegram),
+    Matrix(Matrix),
+    Desktop(Desktop),
+}
+
+impl NotifierSettings {
+    pub fn before(&self) -> &[String] {
+        match self {
+            NotifierSettings::Ntfy(settings) => &settings.before,
+            NotifierSettings::Pushover(settings) => &settings.before,
+            NotifierSettings::Gotify(settings) => &settings.before,
+            NotifierSettings::Telegram(settings) => &settings.before,
+            NotifierSettings::Matrix(settings) => &settings.before,
+            NotifierSettings::Desktop(settings) => &settings.before,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Push {
+    pub address: String,
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Polling {
+    pub interval: Option<String>,
+    pub jitter: Option<String>,
+    pub max_backoff: Option<String>,
+    pub alert_after: Option<String>,
+}
+
+impl Polling {
+    fn parse(value: &Option<String>, default: Duration) -> Result<Duration, String> {
+        value.as_deref().map(duration::parse).unwrap_or(Ok(default))
+    }
+
+    pub fn interval(&self) -> Result<Duration, String> {
+        Polling::parse(&self.interval, Duration::minutes(5))
+    }
+
+    pub fn jitter(&self) -> Result<Duration, String> {
+        Polling::parse(&self.jitter, Duration::seconds(30))
+    }
+
+    pub fn max_backoff(&self) -> Result<Duration, String> {
+        Polling::parse(&self.max_backoff, Duration::minutes(30))
+    }
+
+    // How long syncing may fail before the daemon says so with a notification
+    pub fn alert_after(&self) -> Result<Duration, String> {
+        Polling::parse(&self.alert_after, Duration::hours(1))
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct NotesFile {
+    pub dir: Option<String>,
+    pub template: Option<String>,
+    #[serde(default = "default_on_join")]
+    pub on_join: bool,
+    #[serde(default)]
+    pub on_start: bool,
+    #[serde(default)]
+    pub open: bool,
+}
+
+fn default_on_join() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct WorkingHours {
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+impl WorkingHours {
+    fn parse(value: &Option<String>, default: &str) -> Result<NaiveTime, String> {
+        let value = value.as_deref().unwrap_or(default);
+        NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| format!("Invalid time: {value}"))
+    }
+
+    // Monday to Friday, 09:00 to 17:00 unless configured otherwise
+    pub fn bounds(&self) -> Result<(NaiveTime, NaiveTime), String> {
+        Ok((
+            WorkingHours::parse(&self.start, "09:00")?,
+            WorkingHours::parse(&self.end, "17:00")?,
+        ))
+    }
+}
+
+// What an hour of one person's time costs, for --cost; the currency goes before the amount
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Cost {
+    pub hourly_rate: Option<f64>,
+    pub currency: Option<String>,
+}
+
+const DEFAULT_HOURLY_RATE: f64 = 50.0;
+
+impl Cost {
+    pub fn hourly_rate(&self) -> f64 {
+        self.hourly_rate.unwrap_or(DEFAULT_HOURLY_RATE)
+    }
+
+    pub fn format(&self, amount: f64) -> String {
+        format!("{}{:.0}", self.currency.as_deref().unwrap_or("$"), amount)
+    }
+}
+
+// An event `add --template` creates in one go; the conference is "meet" (default) or "none"
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct EventTemplate {
+    pub title: Option<String>,
+    pub duration: Option<String>,
+    #[serde(default)]
+    pub attendees: Vec<String>,
+    pub description: Option<String>,
+    pub conference: Option<String>,
+}
+
+impl EventTemplate {
+    pub fn duration(&self) -> Result<Option<Duration>, String> {
+        self.duration.as_deref().map(duration::parse).transpose()
+    }
+}
+
+// Every field that is set has to match; summary is a case-insensitive substring
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Match {
+    pub summary: Option<String>,
+    pub organizer: Option<String>,
+    pub event_type: Option<String>,
+    pub optional: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Policy {
+    pub name: String,
+    pub protect: Match,
+    #[serde(default)]
+    pub decline: Match,
+    pub comment: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Settings {
+    #[serde(default)]
+    pub calendars: Vec<String>,
+    #[serde(default)]
+    pub polling: Polling,
+    #[serde(default)]
+    pub hooks: Hooks,
+    pub discord: Option<Discord>,
+    pub mqtt: Option<Mqtt>,
+    pub email: Option<Email>,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    #[serde(default)]
+    pub notifiers: Vec<NotifierSettings>,
+    // deprecated, moved into notifiers on load
+    pub ntfy: Option<Ntfy>,
+    pub listen: Option<String>,
+    // serve also answers gRPC here, with the grpc feature
+    pub grpc_listen: Option<String>,
+    pub push: Option<Push>,
+    #[serde(default)]
+    pub working_hours: WorkingHours,
+    #[serde(default)]
+    pub cost: Cost,
+    pub notes_file: Option<NotesFile>,
+    // a vdir that watch mirrors the day into and vdir-sync defaults to
+    pub vdir: Option<String>,
+    // how `propose` answers the invitation: "decline" (default) or "tentative"
+    pub propose: Option<String>,
+    #[serde(default)]
+    pub policies: Vec<Policy>,
+    #[serde(default)]
+    pub templates: HashMap<String, EventTemplate>,
+}
+
+pub fn settings_path() -> String {
+    std::env::var_os("HOME")
+        .map(|var| var.to_str().unwrap().to_owned())
+        .unwrap()
+        + "/.nextmeet.json"
+}
+
+impl Settings {
+    pub fn calendars(&self) -> Vec<String> {
+        if self.calendars.is_empty() {
+            vec![crate::config::EMAIL.to_string()]
+        } else {
+            self.calendars.clone()
+        }
+    }
+
+    pub fn reminder_offsets(&self) -> Result<Vec<Duration>, String> {
+        let mut offsets = self
+            .hooks
+            .before
+            .iter()
+            .chain(self.webhooks.iter().flat_map(|webhook| &webhook.before))
+            .chain(self.notifiers.iter().flat_map(|notifier| notifier.before()))
+            .map(|offset| duration::parse(offset))
+            .collect::<Result<Vec<_>, _>>()?;
+        offsets.sort();
+        offsets.dedup();
+        Ok(offsets)
+    }
+
+    // The top-level ntfy key predates notifiers
+    fn upgrade(mut self) -> Settings {
+        if let Some(ntfy) = self.ntfy.take() {
+            eprintln!(
+                "Warning: the \"ntfy\" setting is deprecated, \
+                 move it into \"notifiers\" with \"type\": \"ntfy\""
+            );
+            self.notifiers.push(NotifierSettings::Ntfy(ntfy));
+        }
+        self
+    }
+
+    pub fn load() -> Result<Settings, Box<dyn Error>> {
+        match std::fs::read_to_string(settings_path()) {
+            Ok(settings) => serde_json::from_str::<Settings>(&settings)
+                .map(Settings::upgrade)
+                .map_err(|err| format!("Failed to parse settings: {err}").into()),
+            Err(_) => Ok(Settings::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hooks() {
+        let settings: Settings = serde_json::from_str(
+            r#"{
+                "hooks": {
+                    "on_meeting_start": "playerctl pause",
+                    "before_meeting": "notify-send \"$NEXTMEET_SUMMARY\"",
+                    "before": ["5m", "1m"]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings.hooks.on_meeting_start.as_deref(),
+            Some("playerctl pause")
+        );
+        assert_eq!(settings.hooks.on_meeting_end, None);
+        assert_eq!(settings.hooks.before, vec!["5m", "1m"]);
+    }
+
+    #[test]
+    fn parses_polling() {
+        let settings: Settings =
+            serde_json::from_str(r#"{ "polling": { "interval": "2m", "jitter": "0" } }"#).unwrap();
+
+        assert_eq!(settings.polling.interval(), Ok(Duration::minutes(2)));
+        assert_eq!(settings.polling.jitter(), Ok(Duration::zero()));
+        assert_eq!(settings.polling.max_backoff(), Ok(Duration::minutes(30)));
+    }
+
+    #[test]
+    fn upgrades_ntfy() {
+        let settings: Settings =
+            serde_json::from_str(r#"{ "ntfy": { "topic": "meetings", "before": ["5m"] } }"#)
+                .unwrap();
+        let settings = settings.upgrade();
+
+        assert_eq!(settings.ntfy, None);
+        assert!(matches!(
+            &settings.notifiers[..],
+            [NotifierSettings::Ntfy(ntfy)] if ntfy.topic == "meetings"
+        ));
+        assert_eq!(settings.reminder_offsets(), Ok(vec![Duration::minutes(5)]));
+    }
+
+    #[test]
+    fn empty_settings() {
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+        assert!(settings.hooks.before.is_empty());
+    }
+
+    #[test]
+    fn collects_reminder_offsets() {
+        let settings: Settings = serde_json::from_str(
+            r#"{
+                "hooks": { "before": ["5m", "1m"] },
+                "notifiers": [
+                    { "type": "ntfy", "topic": "meetings", "before": ["5m", "10m"] },
+                    { "type": "gotify", "server": "https://gotify.example.org", "token": "x" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings.reminder_offsets(),
+            Ok(vec![
+                Duration::minutes(1),
+                Duration::minutes(5),
+                Duration::minutes(10)
+            ])
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use super::meetings::Meeting;
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::Days;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::Weekday;
+
+pub type Interval = (DateTime<Local>, DateTime<Local>);
+
+pub fn at(date: NaiveDate, time: NaiveTime) -> Option<DateTime<Local>> {
+    date.and_time(time).and_local_timezone(Local).earliest()
+}
+
+// Overlapping meetings only count once
+pub fn merge(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort();
+    let mut merged: Vec<Interval> = vec![];
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+pub fn busy(meetings: &[Meeting]) -> Vec<Interval> {
+    merge(
+        meetings
+            .iter()
+            .filter(|meeting| meeting.accepted())
+            .filter_map(|meeting| Some((meeting.start().ok()?, meeting.end().ok()?)))
+            .collect(),
+    )
+}
+
+// The gaps between merged busy intervals within [from, to)
+pub fn free(busy: &[Interval], from: DateTime<Local>, to: DateTime<Local>) -> Vec<Interval> {
+    let mut gaps = vec![];
+    let mut cursor = from;
+    for (start, end) in busy {
+        if *end <= cursor {
+            continue;
+        }
+        if *start >= to {
+            break;
+        }
+        if *start > cursor {
+            gaps.push((cursor, *start));
+        }
+        cursor = cursor.max(*end);
+    }
+    if cursor < to {
+        gaps.push((cursor, to));
+    }
+    gaps
+}
+
+// Free time within working hours on weekdays, from now over the next few days
+pub fn working_free(
+    meetings: &[Meeting],
+    now: DateTime<Local>,
+    days: u64,
+    hours: (NaiveTime, NaiveTime),
+) -> Vec<Interval> {
+    let busy = busy(meetings);
+    let today = now.date_naive();
+    (0..days)
+        .map(|offset| today + Days::new(offset))
+        .filter(|day| !matches!(day.weekday(), Weekday::Sat | Weekday::Sun))
+        .filter_map(|day| Some((at(day, hours.0)?.max(now), at(day, hours.1)?)))
+        .filter(|(open, close)| open < close)
+        .flat_map(|(open, close)| free(&busy, open, close))
+        .collect()
+}
+
+// The end of the run of back-to-back or overlapping meetings going on at `now`
+pub fn free_at(meetings: &[Meeting], now: DateTime<Local>) -> Option<DateTime<Local>> {
+    busy(meetings)
+        .into_iter()
+        .find(|(start, end)| *start <= now && now < *end)
+        .map(|(_, end)| end)
+}
+
+// The longest gap, the earliest one among equally long gaps
+pub fn largest(free: &[Interval]) -> Option<Interval> {
+    free.iter().copied().fold(None, |best, gap| match best {
+        Some((start, end)) if end - start >= gap.1 - gap.0 => best,
+        _ => Some(gap),
+    })
+}
+
+pub fn find(free: &[Interval], length: Duration) -> Option<Interval> {
+    free.iter()
+        .find(|(start, end)| *end - *start >= length)
+        .map(|(start, _)| (*start, *start + length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(start: &str, end: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "attendees": [{ "self": true, "responseStatus": "accepted" }]
+        }))
+        .unwrap()
+    }
+
+    fn time(date_time: &str) -> DateTime<Local> {
+        date_time.parse().unwrap()
+    }
+
+    #[test]
+    fn finds_gaps() {
+        let meetings = [
+            meeting("2023-03-01T10:00:00+01:00", "2023-03-01T11:00:00+01:00"),
+            meeting("2023-03-01T10:30:00+01:00", "2023-03-01T11:30:00+01:00"),
+            meeting("2023-03-01T13:00:00+01:00", "2023-03-01T14:00:00+01:00"),
+        ];
+        let gaps = free(
+            &busy(&meetings),
+            time("2023-03-01T09:00:00+01:00"),
+            time("2023-03-01T17:00:00+01:00"),
+        );
+
+        assert_eq!(
+            gaps,
+            vec![
+                (
+                    time("2023-03-01T09:00:00+01:00"),
+                    time("2023-03-01T10:00:00+01:00")
+                ),
+                (
+                    time("2023-03-01T11:30:00+01:00"),
+                    time("2023-03-01T13:00:00+01:00")
+                ),
+                (
+                    time("2023-03-01T14:00:00+01:00"),
+                    time("2023-03-01T17:00:00+01:00")
+                ),
+            ]
+        );
+        assert_eq!(
+            find(&gaps, Duration::hours(2)),
+            Some((
+                time("2023-03-01T14:00:00+01:00"),
+                time("2023-03-01T16:00:00+01:00")
+            ))
+        );
+        assert_eq!(find(&gaps, Duration::hours(4)), None);
+    }
+
+    #[test]
+    fn frees_up_after_back_to_back_meetings() {
+        let meetings = [
+            meeting("2023-03-01T10:00:00+01:00", "2023-03-01T11:00:00+01:00"),
+            meeting("2023-03-01T11:00:00+01:00", "2023-03-01T11:30:00+01:00"),
+            meeting("2023-03-01T11:15:00+01:00", "2023-03-01T12:00:00+01:00"),
+            meeting("2023-03-01T12:30:00+01:00", "2023-03-01T13:00:00+01:00"),
+        ];
+
+        assert_eq!(
+            free_at(&meetings, time("2023-03-01T10:30:00+01:00")),
+            Some(time("2023-03-01T12:00:00+01:00"))
+        );
+        assert_eq!(free_at(&meetings, time("2023-03-01T12:00:00+01:00")), None);
+        assert_eq!(free_at(&meetings, time("2023-03-01T09:00:00+01:00")), None);
+    }
+
+    #[test]
+    fn finds_the_largest_gap() {
+        let meetings = [
+            meeting("2023-03-01T11:30:00+01:00", "2023-03-01T12:00:00+01:00"),
+            meeting("2023-03-01T13:00:00+01:00", "2023-03-01T14:00:00+01:00"),
+        ];
+        let gaps = free(
+            &busy(&meetings),
+            time("2023-03-01T11:00:00+01:00"),
+            time("2023-03-01T14:30:00+01:00"),
+        );
+
+        assert_eq!(
+            largest(&gaps),
+            Some((
+                time("2023-03-01T12:00:00+01:00"),
+                time("2023-03-01T13:00:00+01:00")
+            ))
+        );
+        assert_eq!(largest(&[]), None);
+    }
+}
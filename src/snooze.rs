@@ -0,0 +1,68 @@
+use super::meetings::Meeting;
+use chrono::DateTime;
+use chrono::Local;
+
+// A meeting hidden from the "next meeting" until a moment, or for good when dismissed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Override {
+    pub id: String,
+    pub until: Option<DateTime<Local>>,
+}
+
+impl Override {
+    fn hides(&self, meeting: &Meeting, now: DateTime<Local>) -> bool {
+        meeting.id() == Some(self.id.as_str())
+            && self.until.map(|until| now < until).unwrap_or(true)
+    }
+}
+
+pub fn apply(meetings: Vec<Meeting>, overrides: &[Override], now: DateTime<Local>) -> Vec<Meeting> {
+    meetings
+        .into_iter()
+        .filter(|meeting| !overrides.iter().any(|o| o.hides(meeting, now)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn meeting(id: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({ "id": id, "summary": id })).unwrap()
+    }
+
+    #[test]
+    fn hides_snoozed_and_dismissed() {
+        let now: DateTime<Local> = "2023-03-01T10:00:00+01:00".parse().unwrap();
+        let overrides = [
+            Override {
+                id: "snoozed".to_string(),
+                until: Some(now + Duration::minutes(10)),
+            },
+            Override {
+                id: "expired".to_string(),
+                until: Some(now - Duration::minutes(1)),
+            },
+            Override {
+                id: "dismissed".to_string(),
+                until: None,
+            },
+        ];
+        let meetings = vec![
+            meeting("snoozed"),
+            meeting("expired"),
+            meeting("dismissed"),
+            meeting("other"),
+        ];
+
+        let ids: Vec<_> = apply(meetings.clone(), &overrides, now)
+            .iter()
+            .map(|m| m.summary().to_string())
+            .collect();
+        assert_eq!(ids, vec!["expired", "other"]);
+
+        let later = apply(meetings, &overrides, now + Duration::minutes(10));
+        assert_eq!(later.len(), 3);
+    }
+}
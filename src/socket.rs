@@ -0,0 +1,166 @@
+use super::meetings::next_meeting;
+use super::meetings::Meeting;
+use super::server;
+use super::server::Shared;
+use chrono::DateTime;
+use chrono::Local;
+use std::error::Error;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::fs::PermissionsExt;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::net::UnixListener;
+use tokio::net::UnixStream;
+
+const QUERY_TIMEOUT_SECONDS: u64 = 5;
+
+// $XDG_RUNTIME_DIR is the user's own; without it a 0700 directory in the cache, never the
+// /tmp that every user on the host shares
+fn socket_dir() -> String {
+    std::env::var("XDG_RUNTIME_DIR")
+        .unwrap_or_else(|_| std::env::var("HOME").unwrap_or_default() + "/.cache/nextmeet")
+}
+
+pub fn socket_path() -> String {
+    socket_dir() + "/nextmeet.sock"
+}
+
+fn line(meeting: &Meeting) -> String {
+    let format = |date: DateTime<Local>| date.format("%H:%M").to_string();
+    format!(
+        "{} - {} {}",
+        meeting.start().map(format).unwrap_or_default(),
+        meeting.end().map(format).unwrap_or_default(),
+        meeting.summary()
+    )
+}
+
+pub fn answer(query: &str, meetings: &[Meeting], now: DateTime<Local>) -> Result<String, String> {
+    match query.trim() {
+        "next" => Ok(next_meeting(meetings, now).map(line).unwrap_or_default()),
+        "link" => Ok(next_meeting(meetings, now)
+            .and_then(|meeting| meeting.get_link())
+            .unwrap_or_default()),
+        "today" => Ok(meetings.iter().map(line).collect::<Vec<_>>().join("\n")),
+        query => Err(format!("Unknown query: {query}")),
+    }
+}
+
+async fn serve_client(stream: UnixStream, shared: Shared) -> Result<(), Box<dyn Error>> {
+    let (reader, mut writer) = stream.into_split();
+    let mut query = String::new();
+    BufReader::new(reader).read_line(&mut query).await?;
+
+    let today = Local::now().date_naive();
+    let response = match server::agenda(&shared, today).await {
+        Ok(meetings) => answer(&query, &meetings, Local::now()),
+        Err(err) => Err(err),
+    };
+
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => format!("ERR {err}"),
+    };
+    writer.write_all(response.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    Ok(())
+}
+
+pub fn spawn(shared: Shared) -> Result<(), Box<dyn Error>> {
+    let dir = socket_dir();
+    if std::env::var_os("XDG_RUNTIME_DIR").is_none() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)?;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    let path = socket_path();
+    // a socket someone answers on belongs to a running watch or serve, a stale one from a
+    // previous run would make bind fail
+    if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+        return Err(format!("{path} is in use, is nextmeet already running?").into());
+    }
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let shared = shared.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = serve_client(stream, shared).await {
+                            eprintln!("Error: socket: {}", err);
+                        }
+                    });
+                }
+                Err(err) => eprintln!("Error: socket: {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+pub async fn query(query: &str) -> Result<String, Box<dyn Error>> {
+    let mut stream = UnixStream::connect(socket_path()).await?;
+    stream.write_all(format!("{query}\n").as_bytes()).await?;
+
+    // a wedged daemon must not hang the status bar that asked
+    let mut response = String::new();
+    tokio::time::timeout(
+        std::time::Duration::from_secs(QUERY_TIMEOUT_SECONDS),
+        stream.read_to_string(&mut response),
+    )
+    .await
+    .map_err(|_| "No answer from nextmeet watch")??;
+    match response.strip_prefix("ERR ") {
+        Some(err) => Err(err.trim().to_string().into()),
+        None => Ok(response.trim_end().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(summary: &str, start: &str, end: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "summary": summary,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+            "attendees": [{ "self": true, "responseStatus": "accepted" }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn answers_queries() {
+        let meetings = [
+            meeting(
+                "First",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:30:00+01:00",
+            ),
+            meeting(
+                "Second",
+                "2023-03-01T11:00:00+01:00",
+                "2023-03-01T11:30:00+01:00",
+            ),
+        ];
+        let now = "2023-03-01T10:50:00+01:00".parse().unwrap();
+
+        assert!(answer("next\n", &meetings, now)
+            .unwrap()
+            .ends_with("Second"));
+        assert_eq!(
+            answer("link", &meetings, now),
+            Ok("https://meet.google.com/aaa-bbbb-ccc".to_string())
+        );
+        assert_eq!(answer("today", &meetings, now).unwrap().lines().count(), 2);
+        assert!(answer("weather", &meetings, now).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use super::settings::Sound;
+use std::io::Write;
+use std::process::Command;
+
+const QUIET: f32 = 0.3;
+const LOUD: f32 = 1.0;
+
+// Quiet at ten minutes or more, full volume in the last minute, linear in between
+pub fn volume(minutes_before: Option<i64>) -> f32 {
+    match minutes_before {
+        Some(minutes) if minutes >= 10 => QUIET,
+        Some(minutes) if minutes > 1 => QUIET + (LOUD - QUIET) * (10 - minutes) as f32 / 9.0,
+        _ => LOUD,
+    }
+}
+
+fn player(file: &str, volume: f32) -> Command {
+    if cfg!(target_os = "macos") {
+        let mut command = Command::new("afplay");
+        command.arg("-v").arg(volume.to_string()).arg(file);
+        command
+    } else {
+        let mut command = Command::new("paplay");
+        command
+            .arg(format!("--volume={}", (volume * 65536.0) as u32))
+            .arg(file);
+        command
+    }
+}
+
+pub fn play(sound: &Sound, minutes_before: Option<i64>) {
+    let volume = if sound.escalate {
+        volume(minutes_before)
+    } else {
+        LOUD
+    };
+
+    let played = sound
+        .file
+        .as_deref()
+        .and_then(|file| player(file, volume).spawn().ok())
+        .map(|mut child| {
+            std::thread::spawn(move || child.wait());
+        })
+        .is_some();
+
+    if !played {
+        let mut stderr = std::io::stderr();
+        let _ = stderr.write_all(b"\x07");
+        let _ = stderr.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escalates_with_imminence() {
+        assert_eq!(volume(Some(15)), QUIET);
+        assert_eq!(volume(Some(10)), QUIET);
+        assert!(volume(Some(5)) > QUIET && volume(Some(5)) < LOUD);
+        assert_eq!(volume(Some(1)), LOUD);
+        assert_eq!(volume(None), LOUD);
+    }
+}
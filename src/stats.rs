@@ -0,0 +1,265 @@
+use super::meetings::Meeting;
+use super::settings::Cost;
+use super::slots::at;
+use super::slots::merge;
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::Days;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::Weekday;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+pub struct Stats {
+    pub count: usize,
+    pub total: Duration,
+    pub by_organizer: Vec<(String, usize)>,
+    pub busiest_day: Option<(NaiveDate, Duration)>,
+    pub working: Duration,
+    pub in_working_hours: Duration,
+    // with --cost, overlapping meetings each count in full since different people attend
+    pub cost: Option<String>,
+}
+
+// Length times the people attending times the hourly rate
+pub fn cost(meeting: &Meeting, hourly_rate: f64) -> f64 {
+    match (meeting.start(), meeting.end()) {
+        (Ok(start), Ok(end)) => {
+            (end - start).num_minutes() as f64 / 60.0 * meeting.people() as f64 * hourly_rate
+        }
+        _ => 0.0,
+    }
+}
+
+fn length(intervals: &[(DateTime<Local>, DateTime<Local>)]) -> Duration {
+    intervals
+        .iter()
+        .fold(Duration::zero(), |total, (start, end)| {
+            total + (*end - *start)
+        })
+}
+
+pub fn compute(
+    meetings: &[Meeting],
+    from: NaiveDate,
+    to: NaiveDate,
+    hours: (NaiveTime, NaiveTime),
+    rate: Option<&Cost>,
+) -> Stats {
+    let meetings: Vec<_> = meetings
+        .iter()
+        .filter(|meeting| meeting.accepted())
+        .filter_map(|meeting| Some((meeting, meeting.start().ok()?, meeting.end().ok()?)))
+        .collect();
+    let busy = merge(
+        meetings
+            .iter()
+            .map(|(_, start, end)| (*start, *end))
+            .collect(),
+    );
+
+    let mut organizers: BTreeMap<String, usize> = BTreeMap::new();
+    for (meeting, _, _) in &meetings {
+        let organizer = meeting.organizer().unwrap_or("unknown").to_string();
+        *organizers.entry(organizer).or_default() += 1;
+    }
+    let mut by_organizer: Vec<_> = organizers.into_iter().collect();
+    by_organizer.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut per_day: BTreeMap<NaiveDate, Duration> = BTreeMap::new();
+    for (start, end) in &busy {
+        let day = per_day
+            .entry(start.date_naive())
+            .or_insert(Duration::zero());
+        *day = *day + (*end - *start);
+    }
+    let busiest_day = per_day.into_iter().fold(
+        None,
+        |busiest: Option<(NaiveDate, Duration)>, (day, time)| match busiest {
+            Some((_, most)) if most >= time => busiest,
+            _ => Some((day, time)),
+        },
+    );
+
+    let mut working = Duration::zero();
+    let mut in_working_hours = Duration::zero();
+    let mut day = from;
+    while day <= to {
+        let weekend = matches!(day.weekday(), Weekday::Sat | Weekday::Sun);
+        if let (false, Some(open), Some(close)) = (weekend, at(day, hours.0), at(day, hours.1)) {
+            working = working + (close - open);
+            let clipped: Vec<_> = busy
+                .iter()
+                .map(|(start, end)| ((*start).max(open), (*end).min(close)))
+                .filter(|(start, end)| start < end)
+                .collect();
+            in_working_hours = in_working_hours + length(&clipped);
+        }
+        day = day + Days::new(1);
+    }
+
+    let cost = rate.map(|rate| {
+        let total = meetings
+            .iter()
+            .map(|(meeting, _, _)| cost(meeting, rate.hourly_rate()))
+            .sum();
+        rate.format(total)
+    });
+
+    Stats {
+        count: meetings.len(),
+        total: length(&busy),
+        by_organizer,
+        busiest_day,
+        working,
+        in_working_hours,
+        cost,
+    }
+}
+
+pub fn hours(duration: Duration) -> String {
+    format!(
+        "{}h{:02}m",
+        duration.num_hours(),
+        duration.num_minutes() % 60
+    )
+}
+
+impl Stats {
+    pub fn working_percentage(&self) -> f64 {
+        if self.working.is_zero() {
+            return 0.0;
+        }
+        100.0 * self.in_working_hours.num_seconds() as f64 / self.working.num_seconds() as f64
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Meetings: {}", self.count)?;
+        writeln!(f, "Time in meetings: {}", hours(self.total))?;
+        writeln!(
+            f,
+            "Working hours in meetings: {:.0}% of {}",
+            self.working_percentage(),
+            hours(self.working)
+        )?;
+        if let Some(cost) = &self.cost {
+            writeln!(f, "Cost: {cost}")?;
+        }
+        if let Some((day, time)) = self.busiest_day {
+            writeln!(
+                f,
+                "Busiest day: {} ({})",
+                day.format("%A %d/%m"),
+                hours(time)
+            )?;
+        }
+        write!(f, "By organizer:")?;
+        for (organizer, count) in &self.by_organizer {
+            write!(f, "\n  {count:>3}  {organizer}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(organizer: &str, start: &str, end: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "summary": "Meeting",
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "organizer": { "email": organizer },
+            "attendees": [{ "self": true, "responseStatus": "accepted" }]
+        }))
+        .unwrap()
+    }
+
+    fn local(date_time: &str) -> String {
+        date_time
+            .parse::<chrono::NaiveDateTime>()
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .to_rfc3339()
+    }
+
+    #[test]
+    fn summarizes_week() {
+        let meetings = [
+            meeting(
+                "anna@example.org",
+                &local("2023-03-01T10:00:00"),
+                &local("2023-03-01T11:00:00"),
+            ),
+            // overlaps the previous one by half an hour
+            meeting(
+                "bob@example.org",
+                &local("2023-03-01T10:30:00"),
+                &local("2023-03-01T11:30:00"),
+            ),
+            meeting(
+                "anna@example.org",
+                &local("2023-03-02T08:00:00"),
+                &local("2023-03-02T10:00:00"),
+            ),
+        ];
+        let hours = (
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        );
+        let stats = compute(
+            &meetings,
+            "2023-02-27".parse().unwrap(),
+            "2023-03-05".parse().unwrap(),
+            hours,
+            None,
+        );
+
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.total, Duration::minutes(210));
+        assert_eq!(stats.working, Duration::hours(40));
+        assert_eq!(stats.in_working_hours, Duration::minutes(150));
+        assert_eq!(
+            stats.busiest_day,
+            Some(("2023-03-02".parse().unwrap(), Duration::hours(2)))
+        );
+        assert_eq!(stats.by_organizer[0], ("anna@example.org".to_string(), 2));
+        assert!(stats.to_string().contains("Time in meetings: 3h30m"));
+        assert_eq!(stats.cost, None);
+    }
+
+    #[test]
+    fn estimates_cost() {
+        let meeting: Meeting = serde_json::from_value(serde_json::json!({
+            "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+            "end": { "dateTime": "2023-03-01T10:30:00+01:00" },
+            "attendees": [
+                { "self": true, "responseStatus": "accepted" },
+                { "email": "anna@example.org", "responseStatus": "accepted" },
+                { "email": "bob@example.org", "responseStatus": "needsAction" },
+                { "email": "carl@example.org", "responseStatus": "declined" },
+                { "email": "room@resource.calendar.google.com", "resource": true,
+                  "responseStatus": "accepted" }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(cost(&meeting, 100.0), 150.0);
+
+        let rate = Cost {
+            hourly_rate: Some(80.0),
+            currency: Some("€".to_string()),
+        };
+        let day = "2023-03-01".parse().unwrap();
+        let hours = (NaiveTime::MIN, NaiveTime::MIN);
+        let stats = compute(&[meeting], day, day, hours, Some(&rate));
+        assert_eq!(stats.cost.as_deref(), Some("€120"));
+        assert!(stats.to_string().contains("\nCost: €120\n"));
+    }
+}
@@ -0,0 +1,625 @@
+use super::meetings;
+use super::meetings::Meeting;
+use super::snooze::Override;
+use chrono::DateTime;
+use chrono::Days;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use rusqlite::params;
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    id TEXT NOT NULL,
+    start INTEGER NOT NULL,
+    end INTEGER NOT NULL,
+    summary TEXT,
+    link TEXT,
+    response TEXT,
+    raw TEXT NOT NULL,
+    PRIMARY KEY (id, start)
+);
+CREATE INDEX IF NOT EXISTS events_start ON events (start);
+CREATE TABLE IF NOT EXISTS history (
+    id TEXT NOT NULL,
+    start INTEGER NOT NULL,
+    end INTEGER NOT NULL,
+    summary TEXT,
+    started INTEGER NOT NULL DEFAULT 0,
+    ended INTEGER NOT NULL DEFAULT 0,
+    joined_at INTEGER,
+    PRIMARY KEY (id, start)
+);
+CREATE TABLE IF NOT EXISTS overrides (
+    id TEXT PRIMARY KEY,
+    until INTEGER
+);
+CREATE TABLE IF NOT EXISTS notes (
+    id TEXT PRIMARY KEY,
+    note TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS fetches (
+    from_date TEXT NOT NULL,
+    to_date TEXT NOT NULL,
+    fetched_at INTEGER NOT NULL,
+    PRIMARY KEY (from_date, to_date)
+);
+";
+
+// How long an override is kept once its snooze ran out or its meeting ended
+const OVERRIDE_DAYS: i64 = 30;
+
+pub struct Store {
+    connection: Connection,
+}
+
+pub struct HistoryEntry {
+    pub summary: String,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub joined_at: Option<DateTime<Local>>,
+}
+
+impl Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let joined = match self.joined_at {
+            Some(at) => format!("joined at {}", at.format("%H:%M")),
+            None => "not joined".to_string(),
+        };
+        write!(
+            f,
+            "{} {} - {} {} ({})",
+            self.start.format("%Y-%m-%d"),
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            self.summary,
+            joined
+        )
+    }
+}
+
+fn timestamp(at: i64) -> DateTime<Local> {
+    Local.timestamp_opt(at, 0).single().unwrap_or_default()
+}
+
+pub fn path() -> String {
+    std::env::var_os("HOME")
+        .map(|var| var.to_str().unwrap().to_owned())
+        .unwrap()
+        + "/.nextmeet.db"
+}
+
+// The cached events carry descriptions, guests and links: readable by me only, as SQLite
+// gives its journal files the same permissions
+#[cfg(unix)]
+fn private(path: &str) -> Result<(), Box<dyn Error>> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn private(_path: &str) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
+fn midnight(date: NaiveDate) -> i64 {
+    meetings::start_of_day(date, &Local).timestamp()
+}
+
+// Timestamps covering the whole days from..=to
+fn window(from: NaiveDate, to: NaiveDate) -> (i64, i64) {
+    (midnight(from), midnight(to + Days::new(1)))
+}
+
+// All-day events only carry a date, they span from its midnight to the end date's
+fn bound(time: &Value) -> Option<i64> {
+    if let Some(date_time) = time["dateTime"].as_str() {
+        return DateTime::parse_from_rfc3339(date_time)
+            .ok()
+            .map(|t| t.timestamp());
+    }
+    time["date"]
+        .as_str()
+        .and_then(|date| date.parse().ok())
+        .map(midnight)
+}
+
+impl Store {
+    pub fn open() -> Result<Store, Box<dyn Error>> {
+        let path = path();
+        private(&path)?;
+        Store::with_connection(Connection::open(path)?)
+    }
+
+    pub fn with_connection(connection: Connection) -> Result<Store, Box<dyn Error>> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Store { connection })
+    }
+
+    // Replaces whatever was known about the window, so cancelled events disappear
+    pub fn save(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        items: &[Value],
+        now: DateTime<Local>,
+    ) -> Result<(), Box<dyn Error>> {
+        let (start, end) = window(from, to);
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM events WHERE end > ?1 AND start < ?2",
+            params![start, end],
+        )?;
+
+        for item in items {
+            let (Some(start), Some(end)) = (bound(&item["start"]), bound(&item["end"])) else {
+                continue;
+            };
+            // Skipped like the fetch path does, rather than losing the whole window
+            let Ok(meeting) = serde_json::from_value::<Meeting>(item.clone()) else {
+                continue;
+            };
+            transaction.execute(
+                "INSERT OR REPLACE INTO events (id, start, end, summary, link, response, raw)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    meeting.id().unwrap_or_default(),
+                    start,
+                    end,
+                    meeting.summary(),
+                    meeting.get_link(),
+                    meeting.response_status(),
+                    item.to_string()
+                ],
+            )?;
+        }
+
+        transaction.execute(
+            "INSERT OR REPLACE INTO fetches (from_date, to_date, fetched_at) VALUES (?1, ?2, ?3)",
+            params![from.to_string(), to.to_string(), now.timestamp()],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn events(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Value>, Box<dyn Error>> {
+        let (start, end) = window(from, to);
+        let mut statement = self
+            .connection
+            .prepare("SELECT raw FROM events WHERE end > ?1 AND start < ?2 ORDER BY start, id")?;
+        let rows = statement.query_map(params![start, end], |row| row.get::<_, String>(0))?;
+
+        let mut events = vec![];
+        for raw in rows {
+            events.push(serde_json::from_str(&raw?)?);
+        }
+        Ok(events)
+    }
+
+    pub fn meetings(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Meeting>, Box<dyn Error>> {
+        Ok(meetings::from_items(self.events(from, to)?))
+    }
+
+    // When a fetch covering the whole window last succeeded
+    pub fn fetched_at(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Option<DateTime<Local>>, Box<dyn Error>> {
+        let fetched_at: Option<i64> = self
+            .connection
+            .query_row(
+                "SELECT MAX(fetched_at) FROM fetches WHERE from_date <= ?1 AND to_date >= ?2",
+                params![from.to_string(), to.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(fetched_at.and_then(|at| Local.timestamp_opt(at, 0).single()))
+    }
+
+    fn remember(&self, meeting: &Meeting) -> Result<(String, i64), Box<dyn Error>> {
+        let id = meeting.id().unwrap_or_default().to_string();
+        let start = meeting.start()?.timestamp();
+        self.connection.execute(
+            "INSERT OR IGNORE INTO history (id, start, end, summary) VALUES (?1, ?2, ?3, ?4)",
+            params![id, start, meeting.end()?.timestamp(), meeting.summary()],
+        )?;
+        Ok((id, start))
+    }
+
+    pub fn record(&self, meeting: &Meeting, ended: bool) -> Result<(), Box<dyn Error>> {
+        let (id, start) = self.remember(meeting)?;
+        let column = if ended { "ended" } else { "started" };
+        self.connection.execute(
+            &format!("UPDATE history SET {column} = 1 WHERE id = ?1 AND start = ?2"),
+            params![id, start],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_join(
+        &self,
+        meeting: &Meeting,
+        at: DateTime<Local>,
+    ) -> Result<(), Box<dyn Error>> {
+        let (id, start) = self.remember(meeting)?;
+        self.connection.execute(
+            "UPDATE history SET joined_at = COALESCE(joined_at, ?3) WHERE id = ?1 AND start = ?2",
+            params![id, start, at.timestamp()],
+        )?;
+        Ok(())
+    }
+
+    pub fn history(&self, since: DateTime<Local>) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        let mut statement = self.connection.prepare(
+            "SELECT summary, start, end, joined_at FROM history WHERE start >= ?1 ORDER BY start",
+        )?;
+        let rows = statement.query_map(params![since.timestamp()], |row| {
+            Ok(HistoryEntry {
+                summary: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                start: timestamp(row.get(1)?),
+                end: timestamp(row.get(2)?),
+                joined_at: row.get::<_, Option<i64>>(3)?.map(timestamp),
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    // until = None dismisses the meeting for good
+    pub fn set_override(
+        &self,
+        id: &str,
+        until: Option<DateTime<Local>>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO overrides (id, until) VALUES (?1, ?2)",
+            params![id, until.map(|until| until.timestamp())],
+        )?;
+        Ok(())
+    }
+
+    // Drops the overrides whose snooze ran out, or whose meeting ended, OVERRIDE_DAYS ago
+    pub fn prune_overrides(&self, now: DateTime<Local>) -> Result<(), Box<dyn Error>> {
+        let cutoff = (now - Duration::days(OVERRIDE_DAYS)).timestamp();
+        self.connection.execute(
+            "DELETE FROM overrides WHERE until < ?1 OR id IN (
+                SELECT id FROM (SELECT id, end FROM events UNION ALL SELECT id, end FROM history)
+                GROUP BY id HAVING MAX(end) < ?1
+            )",
+            params![cutoff],
+        )?;
+        Ok(())
+    }
+
+    pub fn overrides(&self) -> Result<Vec<Override>, Box<dyn Error>> {
+        let mut statement = self.connection.prepare("SELECT id, until FROM overrides")?;
+        let rows = statement.query_map([], |row| {
+            Ok(Override {
+                id: row.get(0)?,
+                until: row.get::<_, Option<i64>>(1)?.map(timestamp),
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    // An empty note removes it
+    pub fn set_note(&self, id: &str, note: &str) -> Result<(), Box<dyn Error>> {
+        if note.is_empty() {
+            self.connection
+                .execute("DELETE FROM notes WHERE id = ?1", params![id])?;
+        } else {
+            self.connection.execute(
+                "INSERT OR REPLACE INTO notes (id, note) VALUES (?1, ?2)",
+                params![id, note],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn notes(&self) -> Result<HashMap<String, String>, Box<dyn Error>> {
+        let mut statement = self.connection.prepare("SELECT id, note FROM notes")?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn forget_fetches(&self) -> Result<(), Box<dyn Error>> {
+        self.connection.execute("DELETE FROM fetches", [])?;
+        Ok(())
+    }
+}
+
+// Bookkeeping must never get in the way of joining or watching
+fn log(result: Result<(), Box<dyn Error>>) {
+    if let Err(err) = result {
+        eprintln!("Warning: could not update the local store: {}", err);
+    }
+}
+
+pub fn record(meeting: &Meeting, ended: bool) {
+    log(Store::open().and_then(|store| store.record(meeting, ended)));
+}
+
+pub fn overrides() -> Vec<Override> {
+    Store::open()
+        .and_then(|store| store.overrides())
+        .unwrap_or_default()
+}
+
+pub fn notes() -> HashMap<String, String> {
+    Store::open()
+        .and_then(|store| store.notes())
+        .unwrap_or_default()
+}
+
+pub fn record_join(meeting: &Meeting) {
+    log(Store::open().and_then(|store| store.record_join(meeting, Local::now())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str, start: &str, end: &str) -> Value {
+        serde_json::json!({
+            "id": id,
+            "summary": id,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc"
+        })
+    }
+
+    fn store() -> Store {
+        Store::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn replaces_the_fetched_window() {
+        let mut store = store();
+        let day = date("2023-03-01");
+        let now = Local::now();
+
+        store
+            .save(
+                day,
+                day,
+                &[
+                    event(
+                        "a",
+                        "2023-03-01T10:00:00+01:00",
+                        "2023-03-01T10:30:00+01:00",
+                    ),
+                    event(
+                        "b",
+                        "2023-03-01T11:00:00+01:00",
+                        "2023-03-01T11:30:00+01:00",
+                    ),
+                ],
+                now,
+            )
+            .unwrap();
+        store
+            .save(
+                date("2023-03-02"),
+                date("2023-03-02"),
+                &[event(
+                    "c",
+                    "2023-03-02T10:00:00+01:00",
+                    "2023-03-02T10:30:00+01:00",
+                )],
+                now,
+            )
+            .unwrap();
+        assert_eq!(store.events(day, day).unwrap().len(), 2);
+
+        // "a" was cancelled
+        store
+            .save(
+                day,
+                day,
+                &[event(
+                    "b",
+                    "2023-03-01T11:00:00+01:00",
+                    "2023-03-01T11:30:00+01:00",
+                )],
+                now,
+            )
+            .unwrap();
+
+        let events = store.events(day, date("2023-03-02")).unwrap();
+        let ids: Vec<_> = events.iter().map(|e| e["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn skips_malformed_meetings() {
+        let mut store = store();
+        let day = date("2023-03-01");
+        let mut malformed = event(
+            "b",
+            "2023-03-01T11:00:00+01:00",
+            "2023-03-01T11:30:00+01:00",
+        );
+        malformed["summary"] = 42.into();
+        let events = [
+            event(
+                "a",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:30:00+01:00",
+            ),
+            malformed,
+        ];
+        store.save(day, day, &events, Local::now()).unwrap();
+
+        let meetings = store.meetings(day, day).unwrap();
+        assert_eq!(meetings.len(), 1);
+        assert_eq!(meetings[0].id(), Some("a"));
+    }
+
+    #[test]
+    fn remembers_fetches() {
+        let mut store = store();
+        let now = Local.timestamp_opt(1_677_661_200, 0).unwrap();
+        store
+            .save(date("2023-02-27"), date("2023-03-05"), &[], now)
+            .unwrap();
+
+        assert_eq!(
+            store
+                .fetched_at(date("2023-03-01"), date("2023-03-01"))
+                .unwrap(),
+            Some(now)
+        );
+        assert_eq!(
+            store
+                .fetched_at(date("2023-03-01"), date("2023-03-06"))
+                .unwrap(),
+            None
+        );
+
+        store.forget_fetches().unwrap();
+        assert_eq!(
+            store
+                .fetched_at(date("2023-03-01"), date("2023-03-01"))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn records_history() {
+        let store = store();
+        let meeting: Meeting = serde_json::from_value(event(
+            "a",
+            "2023-03-01T10:00:00+01:00",
+            "2023-03-01T10:30:00+01:00",
+        ))
+        .unwrap();
+        let other: Meeting = serde_json::from_value(event(
+            "b",
+            "2023-03-01T11:00:00+01:00",
+            "2023-03-01T11:30:00+01:00",
+        ))
+        .unwrap();
+
+        store.record(&meeting, false).unwrap();
+        store
+            .record_join(&meeting, meeting.start().unwrap())
+            .unwrap();
+        store.record_join(&meeting, meeting.end().unwrap()).unwrap();
+        store.record(&meeting, true).unwrap();
+        store.record(&other, false).unwrap();
+
+        let history = store.history(meeting.start().unwrap()).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].summary, "a");
+        assert_eq!(history[0].joined_at, meeting.start().ok());
+        assert!(history[1].to_string().ends_with("b (not joined)"));
+
+        assert_eq!(store.history(other.start().unwrap()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn keeps_overrides() {
+        let store = store();
+        let until = Local.timestamp_opt(1_677_661_200, 0).unwrap();
+        store.set_override("a", Some(until)).unwrap();
+        store.set_override("b", None).unwrap();
+        store.set_override("a", None).unwrap();
+
+        let mut overrides = store.overrides().unwrap();
+        overrides.sort_by(|x, y| x.id.cmp(&y.id));
+        assert_eq!(
+            overrides,
+            vec![
+                Override {
+                    id: "a".to_string(),
+                    until: None
+                },
+                Override {
+                    id: "b".to_string(),
+                    until: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn prunes_overrides() {
+        let mut store = store();
+        let day = date("2023-03-01");
+        let events = [
+            event(
+                "old",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:30:00+01:00",
+            ),
+            event(
+                "recent",
+                "2023-03-01T11:00:00+01:00",
+                "2023-03-01T11:30:00+01:00",
+            ),
+        ];
+        store.save(day, day, &events, Local::now()).unwrap();
+        store
+            .save(
+                date("2023-04-20"),
+                date("2023-04-20"),
+                &[event(
+                    "recent",
+                    "2023-04-20T11:00:00+02:00",
+                    "2023-04-20T11:30:00+02:00",
+                )],
+                Local::now(),
+            )
+            .unwrap();
+        let snoozed = Local.timestamp_opt(1_677_661_200, 0).unwrap();
+        for id in ["old", "recent", "unknown"] {
+            store.set_override(id, None).unwrap();
+        }
+        store.set_override("snoozed", Some(snoozed)).unwrap();
+
+        let now = Local.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+        store.prune_overrides(now).unwrap();
+        let mut ids: Vec<_> = store
+            .overrides()
+            .unwrap()
+            .into_iter()
+            .map(|o| o.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["recent", "unknown"]);
+    }
+
+    #[test]
+    fn keeps_notes() {
+        let store = store();
+        store.set_note("a", "bring the Q3 numbers").unwrap();
+        store.set_note("b", "agenda").unwrap();
+        store.set_note("b", "").unwrap();
+
+        let notes = store.notes().unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes["a"], "bring the Q3 numbers");
+    }
+}
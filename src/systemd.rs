@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::net::TcpListener;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+
+// First descriptor passed by socket activation, see sd_listen_fds(3)
+const LISTEN_FDS_START: i32 = 3;
+
+// sd_notify(3) without libsystemd; a no-op when not started by systemd
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+
+    let path = path.to_string_lossy().into_owned();
+    let sent = match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name)
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => return,
+        None => socket.send_to(state.as_bytes(), &path),
+    };
+    if let Err(err) = sent {
+        eprintln!("Error: systemd notify: {}", err);
+    }
+}
+
+pub fn watchdog_enabled() -> bool {
+    std::env::var_os("WATCHDOG_USEC").is_some()
+}
+
+// The socket systemd listens on for us with socket activation, if any
+pub fn listener() -> Option<TcpListener> {
+    let pid: u32 = std::env::var("LISTEN_PID").ok()?.parse().ok()?;
+    let fds: i32 = std::env::var("LISTEN_FDS").ok()?.parse().ok()?;
+    if pid != std::process::id() || fds < 1 {
+        return None;
+    }
+    // SAFETY: systemd hands over this descriptor to the process named in LISTEN_PID
+    Some(unsafe { TcpListener::from_raw_fd(LISTEN_FDS_START) })
+}
+
+fn unit_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(config) => PathBuf::from(config),
+        None => PathBuf::from(std::env::var_os("HOME").ok_or("HOME is not set")?).join(".config"),
+    };
+    Ok(config.join("systemd").join("user"))
+}
+
+pub fn units(exe: &str, listen: &str) -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "nextmeet.service",
+            format!(
+                "[Unit]\n\
+                 Description=nextmeet reminders and hooks\n\
+                 After=network-online.target\n\n\
+                 [Service]\n\
+                 Type=notify\n\
+                 ExecStart={exe} watch\n\
+                 WatchdogSec=60\n\
+                 Restart=on-failure\n\n\
+                 [Install]\n\
+                 WantedBy=default.target\n"
+            ),
+        ),
+        (
+            "nextmeet-server.socket",
+            format!(
+                "[Unit]\n\
+                 Description=nextmeet HTTP server socket\n\n\
+                 [Socket]\n\
+                 ListenStream={listen}\n\n\
+                 [Install]\n\
+                 WantedBy=sockets.target\n"
+            ),
+        ),
+        (
+            "nextmeet-server.service",
+            format!(
+                "[Unit]\n\
+                 Description=nextmeet HTTP server\n\
+                 Requires=nextmeet-server.socket\n\n\
+                 [Service]\n\
+                 Type=notify\n\
+                 ExecStart={exe} serve\n"
+            ),
+        ),
+        (
+            "nextmeet-refresh.service",
+            format!(
+                "[Unit]\n\
+                 Description=Refresh the nextmeet offline cache\n\n\
+                 [Service]\n\
+                 Type=oneshot\n\
+                 ExecStart={exe} --no-cache -mf\n\
+                 StandardOutput=null\n"
+            ),
+        ),
+        (
+            "nextmeet-refresh.timer",
+            "[Unit]\n\
+             Description=Refresh the nextmeet offline cache every 5 minutes\n\n\
+             [Timer]\n\
+             OnBootSec=1min\n\
+             OnUnitActiveSec=5min\n\n\
+             [Install]\n\
+             WantedBy=timers.target\n"
+                .to_string(),
+        ),
+    ]
+}
+
+pub fn install(listen: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let exe = std::env::current_exe()?;
+    let dir = unit_dir()?;
+    std::fs::create_dir_all(&dir)?;
+
+    units(&exe.to_string_lossy(), listen)
+        .into_iter()
+        .map(|(name, unit)| {
+            let path = dir.join(name);
+            std::fs::write(&path, unit)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_units() {
+        let units = units("/usr/bin/nextmeet", "127.0.0.1:8686");
+
+        let (name, service) = &units[0];
+        assert_eq!(*name, "nextmeet.service");
+        assert!(service.contains("Type=notify\n"));
+        assert!(service.contains("ExecStart=/usr/bin/nextmeet watch\n"));
+
+        let (name, socket) = &units[1];
+        assert_eq!(*name, "nextmeet-server.socket");
+        assert!(socket.contains("ListenStream=127.0.0.1:8686\n"));
+    }
+}
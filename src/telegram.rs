@@ -0,0 +1,88 @@
+use super::notifier::Notifier;
+use super::notifier::Reminder;
+use super::settings;
+use async_trait::async_trait;
+use std::error::Error;
+
+pub struct Telegram {
+    settings: settings::Telegram,
+}
+
+impl Telegram {
+    pub fn new(settings: settings::Telegram) -> Telegram {
+        Telegram { settings }
+    }
+}
+
+fn payload(chat_id: &str, reminder: &Reminder) -> serde_json::Value {
+    let mut text = format!("{}\n{}", reminder.title, reminder.message);
+    if let Some(link) = &reminder.link {
+        text.push('\n');
+        text.push_str(link);
+    }
+
+    let mut payload = serde_json::json!({
+        "chat_id": chat_id,
+        "text": text,
+        "disable_web_page_preview": true,
+    });
+    if let Some(link) = &reminder.link {
+        payload["reply_markup"] = serde_json::json!({
+            "inline_keyboard": [[{ "text": "Join", "url": link }]],
+        });
+    }
+    payload
+}
+
+#[async_trait(?Send)]
+impl Notifier for Telegram {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn before(&self) -> &[String] {
+        &self.settings.before
+    }
+
+    async fn send(&self, reminder: &Reminder) -> Result<(), Box<dyn Error>> {
+        let url = format!(
+            "https://api.telegram.org/bot{}/sendMessage",
+            self.settings.bot_token
+        );
+
+        crate::http::client()
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(payload(&self.settings.chat_id, reminder).to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_with_join_button() {
+        let reminder = Reminder {
+            title: "Standup".to_string(),
+            message: "Starts at 10:00 (in 5 minutes)".to_string(),
+            link: Some("https://meet.google.com/aaa-bbbb-ccc".to_string()),
+            ..Default::default()
+        };
+
+        let result = payload("-100123", &reminder);
+        assert_eq!(result["chat_id"], "-100123");
+        assert_eq!(
+            result["text"],
+            "Standup\nStarts at 10:00 (in 5 minutes)\nhttps://meet.google.com/aaa-bbbb-ccc"
+        );
+        assert_eq!(
+            result["reply_markup"]["inline_keyboard"][0][0]["url"],
+            "https://meet.google.com/aaa-bbbb-ccc"
+        );
+    }
+}
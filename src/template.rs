@@ -0,0 +1,79 @@
+use super::meetings::next_meeting;
+use super::meetings::Meeting;
+use chrono::DateTime;
+use chrono::Local;
+use std::error::Error;
+
+pub fn meeting_value(meeting: &Meeting) -> serde_json::Value {
+    let mut value = serde_json::to_value(meeting).unwrap();
+    value["id"] = meeting.id().into();
+    value["link"] = meeting.get_link().into();
+    value["other_links"] = meeting.get_other_links().into();
+    value["organizer"] = meeting.organizer().into();
+    value["response"] = meeting.response_status().into();
+    value["attendees"] = meeting.attendees().into();
+    value
+}
+
+pub fn render(
+    template: &str,
+    meetings: &[Meeting],
+    now: DateTime<Local>,
+) -> Result<String, Box<dyn Error>> {
+    let mut context = tera::Context::new();
+    context.insert(
+        "meetings",
+        &meetings.iter().map(meeting_value).collect::<Vec<_>>(),
+    );
+    context.insert("next", &next_meeting(meetings, now).map(meeting_value));
+    context.insert("now", &now.to_rfc3339());
+
+    Ok(tera::Tera::one_off(template, &context, false)?)
+}
+
+pub fn render_file(
+    path: &str,
+    meetings: &[Meeting],
+    now: DateTime<Local>,
+) -> Result<String, Box<dyn Error>> {
+    let template =
+        std::fs::read_to_string(path).map_err(|_| format!("Template not found: {path}"))?;
+    render(&template, meetings, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_meetings_and_next() {
+        let m: Meeting = serde_json::from_str(
+            r#"{
+                "summary": "Standup",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" },
+                "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+                "attendees": [{ "self": true, "responseStatus": "accepted" }]
+            }"#,
+        )
+        .unwrap();
+        let now = "2023-03-01T09:00:00+01:00".parse().unwrap();
+
+        let result = render(
+            "{% for m in meetings %}{{ m.summary }} {{ m.link }}{% endfor %}|{{ next.summary }}",
+            &[m],
+            now,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result,
+            "Standup https://meet.google.com/aaa-bbbb-ccc|Standup"
+        );
+    }
+
+    #[test]
+    fn reports_template_errors() {
+        assert!(render("{{ unclosed", &[], Local::now()).is_err());
+    }
+}
@@ -1,8 +1,11 @@
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
 use oauth2::basic::BasicClient;
 use oauth2::reqwest::http_client;
 use oauth2::{
     AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, RedirectUrl,
-    RefreshToken, Scope, TokenResponse, TokenUrl,
+    Scope, TokenResponse, TokenUrl,
 };
 use reqwest::Url;
 use serde::Deserialize;
@@ -12,15 +15,28 @@ use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Write;
 use std::net::TcpListener;
-use std::process::Command;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Tokens {
     pub access_token: String,
     pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Local>>,
+    // granted the events scope, asked for only once a command changes the calendar
+    #[serde(default)]
+    pub writable: bool,
 }
 
-fn config_path() -> String {
+const WRITE_SCOPE: &str = "https://www.googleapis.com/auth/calendar.events";
+
+#[derive(Deserialize)]
+struct RefreshResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+}
+
+pub fn config_path() -> String {
     std::env::var_os("HOME")
         .map(|var| var.to_str().unwrap().to_owned())
         .unwrap()
@@ -38,41 +54,77 @@ impl Tokens {
         serde_json::from_str::<Tokens>(&token).map_err(|_| "Failed to parse file".into())
     }
 
-    pub fn refresh(self) -> Result<Tokens, Box<dyn Error>> {
-        let client_id = crate::config::CLIENT_ID;
-        let client_secret = crate::config::CLIENT_SECRET;
+    pub async fn refresh(self) -> Result<Tokens, Box<dyn Error>> {
+        let tokens = self.renew().await?;
+        tokens.save()?;
+        Ok(tokens)
+    }
+
+    // A new access token, left to the caller to store
+    pub async fn renew(self) -> Result<Tokens, Box<dyn Error>> {
+        let writable = self.writable;
+        let refresh_token = self.refresh_token.ok_or("No refresh token available")?;
+        let params = [
+            ("client_id", crate::config::CLIENT_ID),
+            ("client_secret", crate::config::CLIENT_SECRET),
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token.as_str()),
+        ];
+
+        let response = crate::http::client()
+            .post("https://oauth2.googleapis.com/token")
+            .form(&params)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|_| "Failed to refresh tokens")?
+            .text()
+            .await?;
+        let refreshed: RefreshResponse =
+            serde_json::from_str(&response).map_err(|_| "Failed to refresh tokens")?;
+
+        let tokens = Tokens {
+            access_token: refreshed.access_token,
+            refresh_token: refreshed.refresh_token.or(Some(refresh_token)),
+            expires_at: refreshed
+                .expires_in
+                .map(|seconds| Local::now() + Duration::seconds(seconds)),
+            writable,
+        };
+
+        Ok(tokens)
+    }
 
-        if let Some(refresh_token_str) = self.refresh_token {
-            let client = BasicClient::new(
-                ClientId::new(client_id.to_string()),
-                Some(ClientSecret::new(client_secret.to_string())),
-                AuthUrl::new("https://accounts.google.com/o/oauth2/auth".to_string())?,
-                Some(TokenUrl::new(
-                    "https://oauth2.googleapis.com/token".to_string(),
-                )?),
+    pub fn do_login() -> Result<Tokens, Box<dyn Error>> {
+        Tokens::login(false)
+    }
+
+    // Tokens that may change events, going through consent again for the extra scope
+    pub async fn writable() -> Result<Tokens, Box<dyn Error>> {
+        match Tokens::load() {
+            Ok(tokens) if tokens.writable => match tokens.refresh().await {
+                Ok(tokens) => Ok(tokens),
+                Err(_) => Tokens::login(true),
+            },
+            _ => {
+                eprintln!("nextmeet needs permission to change your calendar");
+                Tokens::login(true)
+            }
+        }
+    }
+
+    // For the daemon and other jobs without anyone at the terminal: an error instead of login
+    pub async fn writable_unattended() -> Result<Tokens, Box<dyn Error>> {
+        let tokens = Tokens::load()?;
+        if !tokens.writable {
+            return Err(
+                "nextmeet may not change your calendar, run e.g. rsvp once to allow it".into(),
             );
-            let refresh_token = RefreshToken::new(refresh_token_str.clone());
-            let tokens = client
-                .exchange_refresh_token(&refresh_token)
-                .request(http_client)
-                .map(|res| Tokens {
-                    access_token: res.access_token().secret().to_string(),
-                    refresh_token: res
-                        .refresh_token()
-                        .map(|token| token.secret().to_string())
-                        .or_else(|| Some(refresh_token_str)),
-                })
-                .map_err(|_| "Failed to refresh tokens")?;
-
-            tokens.save()?;
-
-            Ok(tokens)
-        } else {
-            Err("No refresh token available".into())
         }
+        tokens.refresh().await
     }
 
-    pub fn do_login() -> Result<Tokens, Box<dyn Error>> {
+    fn login(writable: bool) -> Result<Tokens, Box<dyn Error>> {
         let client_id = crate::config::CLIENT_ID;
         let client_secret = crate::config::CLIENT_SECRET;
         let client = BasicClient::new(
@@ -85,7 +137,7 @@ impl Tokens {
 
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
         // Generate the full authorization URL.
-        let (auth_url, _) = client
+        let mut request = client
             .authorize_url(CsrfToken::new_random)
             // Set the desired scopes.
             .add_scope(Scope::new(
@@ -95,49 +147,49 @@ impl Tokens {
                 "https://www.googleapis.com/auth/calendar.readonly".to_string(),
             ))
             // Set the PKCE code challenge.
-            .set_pkce_challenge(pkce_challenge)
-            .url();
+            .set_pkce_challenge(pkce_challenge);
+        if writable {
+            request = request
+                .add_scope(Scope::new(WRITE_SCOPE.to_string()))
+                .add_extra_param("include_granted_scopes", "true");
+        }
+        let (auth_url, _) = request.url();
 
         // This is the URL you should redirect the user to, in order to trigger the authorization
         // process.
 
-        match Command::new("xdg-open").arg(auth_url.to_string()).output() {
-            Ok(_) => {}
-            Err(_) => eprintln!("Failed to open browser automatically. Go to {}", auth_url),
+        if crate::open::open(auth_url.as_ref()).is_err() {
+            eprintln!("Failed to open browser automatically. Go to {}", auth_url);
         }
 
         let mut code: Option<String> = None;
         let listener = TcpListener::bind("127.0.0.1:35426").unwrap();
-        for stream in listener.incoming() {
-            if let Ok(mut stream) = stream {
-                {
-                    let mut reader = BufReader::new(&stream);
-
-                    let mut request_line = String::new();
-                    reader.read_line(&mut request_line).unwrap();
-
-                    let redirect_url = request_line.split_whitespace().nth(1).unwrap();
-                    let url = Url::parse(&("http://localhost".to_string() + redirect_url)).unwrap();
-
-                    code = url
-                        .query_pairs()
-                        .find(|pair| {
-                            let &(ref key, _) = pair;
-                            key == "code"
-                        })
-                        .map(|(_, value)| value.to_string());
-                }
-
-                let message = "Go back to your terminal :)";
-                let response = format!(
-                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
-                    message.len(),
-                    message
-                );
-                stream.write_all(response.as_bytes()).unwrap();
-
-                break;
+        if let Some(mut stream) = listener.incoming().flatten().next() {
+            {
+                let mut reader = BufReader::new(&stream);
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+
+                let redirect_url = request_line.split_whitespace().nth(1).unwrap();
+                let url = Url::parse(&("http://localhost".to_string() + redirect_url)).unwrap();
+
+                code = url
+                    .query_pairs()
+                    .find(|pair| {
+                        let (key, _) = pair;
+                        key == "code"
+                    })
+                    .map(|(_, value)| value.to_string());
             }
+
+            let message = "Go back to your terminal :)";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+                message.len(),
+                message
+            );
+            stream.write_all(response.as_bytes()).unwrap();
         }
 
         let code = code.expect("No code received");
@@ -150,6 +202,11 @@ impl Tokens {
             .map(|res| Tokens {
                 access_token: res.access_token().secret().to_string(),
                 refresh_token: res.refresh_token().map(|token| token.secret().to_string()),
+                expires_at: res
+                    .expires_in()
+                    .and_then(|expires_in| Duration::from_std(expires_in).ok())
+                    .map(|expires_in| Local::now() + expires_in),
+                writable,
             })
             .map_err(|_| "Failed to get access token")?;
 
@@ -0,0 +1,386 @@
+use super::calendar;
+use super::calendar::Rsvp;
+use super::countdown;
+use super::meetings;
+use super::meetings::Meeting;
+use super::open;
+use super::output::time_range;
+use super::store;
+use chrono::Datelike;
+use chrono::Days;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use crossterm::event::Event;
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEventKind;
+use crossterm::terminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::Constraint;
+use ratatui::layout::Direction;
+use ratatui::layout::Layout;
+use ratatui::style::Modifier;
+use ratatui::style::Style;
+use ratatui::widgets::Block;
+use ratatui::widgets::Borders;
+use ratatui::widgets::List;
+use ratatui::widgets::ListState;
+use ratatui::widgets::Paragraph;
+use ratatui::Frame;
+use ratatui::Terminal;
+use std::error::Error;
+
+const REFRESH_MINUTES: i64 = 5;
+const HELP: &str =
+    "↑↓ select  ←→ day  w week  enter join  y copy  d links  a/x/t rsvp  r refresh  q quit";
+
+pub struct App {
+    day: NaiveDate,
+    week: bool,
+    meetings: Vec<Meeting>,
+    selected: usize,
+    status: String,
+}
+
+impl App {
+    pub fn new(day: NaiveDate) -> App {
+        App {
+            day,
+            week: false,
+            meetings: vec![],
+            selected: 0,
+            status: String::new(),
+        }
+    }
+
+    pub fn range(&self) -> (NaiveDate, NaiveDate) {
+        if self.week {
+            let monday = self.day - Days::new(self.day.weekday().num_days_from_monday().into());
+            (monday, monday + Days::new(6))
+        } else {
+            (self.day, self.day)
+        }
+    }
+
+    fn set_meetings(&mut self, meetings: Vec<Meeting>) {
+        self.meetings = meetings;
+        self.selected = self.selected.min(self.meetings.len().saturating_sub(1));
+    }
+
+    fn step(&mut self, forward: bool) {
+        let days = Days::new(if self.week { 7 } else { 1 });
+        self.day = if forward {
+            self.day + days
+        } else {
+            self.day - days
+        };
+        self.selected = 0;
+    }
+
+    fn select(&mut self, down: bool) {
+        self.selected = if down {
+            (self.selected + 1).min(self.meetings.len().saturating_sub(1))
+        } else {
+            self.selected.saturating_sub(1)
+        };
+    }
+
+    fn current(&self) -> Option<&Meeting> {
+        self.meetings.get(self.selected)
+    }
+
+    pub fn rows(&self) -> Vec<String> {
+        self.meetings
+            .iter()
+            .map(|meeting| {
+                let day = match (self.week, meeting.start()) {
+                    (true, Ok(start)) => start.format("%a %d  ").to_string(),
+                    _ => String::new(),
+                };
+                let note = meeting
+                    .note()
+                    .map(|note| format!("  — {note}"))
+                    .unwrap_or_default();
+                format!(
+                    "{}{day}{}  {}{note}",
+                    mark(meeting),
+                    time_range(meeting),
+                    meeting.summary()
+                )
+            })
+            .collect()
+    }
+
+    fn title(&self) -> String {
+        let (from, to) = self.range();
+        if from == to {
+            from.format("%A %d %B %Y").to_string()
+        } else {
+            format!("{} – {}", from.format("%d %b"), to.format("%d %b %Y"))
+        }
+    }
+}
+
+// Invitations stay listed whatever the answer, so it can be changed from here
+fn mark(meeting: &Meeting) -> &'static str {
+    match meeting.response_status() {
+        Some("needsAction") => "? ",
+        Some("tentative") => "~ ",
+        Some("declined") => "✗ ",
+        _ => "  ",
+    }
+}
+
+fn draw(frame: &mut Frame, app: &App, state: &mut ListState) {
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(frame.size());
+
+    frame.render_widget(
+        Paragraph::new(countdown::line(
+            &meetings::agenda(app.meetings.clone()),
+            Local::now(),
+        ))
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+        areas[0],
+    );
+
+    let list = List::new(app.rows())
+        .block(Block::default().borders(Borders::ALL).title(app.title()))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    state.select((!app.meetings.is_empty()).then_some(app.selected));
+    frame.render_stateful_widget(list, areas[1], state);
+
+    let footer = if app.status.is_empty() {
+        HELP
+    } else {
+        &app.status
+    };
+    frame.render_widget(Paragraph::new(footer), areas[2]);
+}
+
+async fn load(app: &mut App) {
+    let (from, to) = app.range();
+    match meetings::retrieve_range(from, to).await {
+        Ok(meetings) => app.set_meetings(
+            meetings
+                .into_iter()
+                .filter(|m| m.start().is_ok() && m.response_status().is_some())
+                .collect(),
+        ),
+        Err(err) => app.status = format!("Error: {err}"),
+    }
+}
+
+fn act(app: &mut App, key: KeyCode) -> Result<(), Box<dyn Error>> {
+    match key {
+        KeyCode::Enter => match app.current().filter(|m| m.get_link().is_some()) {
+            Some(meeting) => {
+                open::open(&meeting.get_link().unwrap_or_default())?;
+                store::record_join(meeting);
+            }
+            None => app.status = "No link for this meeting".to_string(),
+        },
+        KeyCode::Char('y') => match app.current().and_then(|m| m.get_link()) {
+            Some(link) => {
+                open::copy(&link)?;
+                app.status = format!("Copied {link}");
+            }
+            None => app.status = "No link for this meeting".to_string(),
+        },
+        KeyCode::Char('d') => {
+            let links = app
+                .current()
+                .map(|m| m.get_other_links())
+                .unwrap_or_default();
+            if links.is_empty() {
+                app.status = "No links in the description".to_string();
+            }
+            for link in links {
+                open::open(&link)?;
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+// The answered event replaces the selected row, no need to fetch the range again
+async fn respond(app: &mut App, rsvp: Rsvp) -> Result<(), Box<dyn Error>> {
+    let meeting = app.current().ok_or("No meeting selected")?;
+    let id = meeting.id().ok_or("This meeting has no id")?.to_string();
+    let note = meeting.note().map(str::to_owned);
+
+    let mut updated = calendar::rsvp(&id, rsvp, None).await?;
+    updated.set_note(note);
+    app.status = format!("{}: {}", updated.summary(), rsvp.status());
+    app.meetings[app.selected] = updated;
+    Ok(())
+}
+
+async fn event_loop(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(Local::now().date_naive());
+    let mut state = ListState::default();
+    load(&mut app).await;
+    let mut loaded_at = Local::now();
+
+    loop {
+        terminal.draw(|frame| draw(frame, &app, &mut state))?;
+
+        // redraw at least every second to keep the countdown ticking
+        if !crossterm::event::poll(std::time::Duration::from_secs(1))? {
+            if Local::now() - loaded_at >= Duration::minutes(REFRESH_MINUTES) {
+                load(&mut app).await;
+                loaded_at = Local::now();
+            }
+            continue;
+        }
+        let Event::Key(key) = crossterm::event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        app.status.clear();
+        let reload = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.select(true);
+                false
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.select(false);
+                false
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                app.step(true);
+                true
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                app.step(false);
+                true
+            }
+            KeyCode::Char('w') => {
+                app.week = !app.week;
+                true
+            }
+            KeyCode::Char('r') => true,
+            // d stays the description links, x declines
+            KeyCode::Char(key @ ('a' | 'x' | 't')) => {
+                let rsvp = match key {
+                    'a' => Rsvp::Accept,
+                    'x' => Rsvp::Decline,
+                    _ => Rsvp::Tentative,
+                };
+                if let Err(err) = respond(&mut app, rsvp).await {
+                    app.status = format!("Error: {err}");
+                }
+                false
+            }
+            code => {
+                if let Err(err) = act(&mut app, code) {
+                    app.status = format!("Error: {err}");
+                }
+                false
+            }
+        };
+        if reload {
+            load(&mut app).await;
+            loaded_at = Local::now();
+        }
+    }
+}
+
+pub async fn run() -> Result<(), Box<dyn Error>> {
+    // a browser login can't happen in raw mode, answering without write access fails instead
+    calendar::unattended();
+    terminal::enable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), terminal::EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+
+    let result = event_loop(&mut terminal).await;
+
+    terminal::disable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), terminal::LeaveAlternateScreen)?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(summary: &str, start: &str, end: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "summary": summary,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+            "hangoutLink": "https://meet.google.com/aaa-bbbb-ccc",
+            "attendees": [{ "self": true, "responseStatus": "accepted" }]
+        }))
+        .unwrap()
+    }
+
+    fn app() -> App {
+        let mut app = App::new(NaiveDate::from_ymd_opt(2023, 3, 1).unwrap());
+        app.set_meetings(vec![
+            meeting(
+                "First",
+                "2023-03-01T10:00:00+01:00",
+                "2023-03-01T10:30:00+01:00",
+            ),
+            meeting(
+                "Second",
+                "2023-03-01T11:00:00+01:00",
+                "2023-03-01T11:30:00+01:00",
+            ),
+        ]);
+        app
+    }
+
+    #[test]
+    fn navigates() {
+        let mut app = app();
+        app.select(false);
+        assert_eq!(app.selected, 0);
+        app.select(true);
+        app.select(true);
+        assert_eq!(app.current().map(|m| m.summary()), Some("Second"));
+
+        app.week = true;
+        assert_eq!(
+            app.range(),
+            (
+                NaiveDate::from_ymd_opt(2023, 2, 27).unwrap(),
+                NaiveDate::from_ymd_opt(2023, 3, 5).unwrap()
+            )
+        );
+        app.step(true);
+        assert_eq!(app.day, NaiveDate::from_ymd_opt(2023, 3, 8).unwrap());
+        assert_eq!(app.selected, 0);
+    }
+
+    #[test]
+    fn marks_responses() {
+        let mut app = app();
+        let invitation: Meeting = serde_json::from_value(serde_json::json!({
+            "summary": "Invitation",
+            "start": { "dateTime": "2023-03-01T12:00:00+01:00" },
+            "end": { "dateTime": "2023-03-01T12:30:00+01:00" },
+            "attendees": [{ "self": true, "responseStatus": "needsAction" }]
+        }))
+        .unwrap();
+        app.meetings.push(invitation);
+
+        let rows = app.rows();
+        assert!(rows[0].starts_with("  ") && rows[0].ends_with("First"));
+        assert!(rows[2].starts_with("? ") && rows[2].ends_with("Invitation"));
+    }
+}
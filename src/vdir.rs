@@ -0,0 +1,193 @@
+use super::export::ics_event;
+use super::export::NEXTMEET_ID;
+use super::meetings::Meeting;
+use chrono::DateTime;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+
+// vdir storage (https://vdirsyncer.pimutils.org/en/stable/vdir.html): one .ics per event,
+// read by khal and friends with nextmeet doing the fetching; the collection must be nextmeet's
+// own, since whatever it removes a vdirsyncer-managed one would delete upstream too
+
+fn file_name(id: &str) -> String {
+    let id: String = id
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '@' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{id}.ics")
+}
+
+// DTSTAMP changes on every run, rewriting the file only for that would look like an edit
+fn same_event(a: &str, b: &str) -> bool {
+    let lines = |text: &str| -> Vec<String> {
+        text.lines()
+            .filter(|line| !line.starts_with("DTSTAMP:"))
+            .map(str::to_string)
+            .collect()
+    };
+    lines(a) == lines(b)
+}
+
+fn written_by_nextmeet(text: &str) -> bool {
+    text.lines()
+        .any(|line| line.starts_with(&format!("{NEXTMEET_ID}:")))
+}
+
+// The .ics files in the directory, with their contents
+fn calendar_files(dir: &Path) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut files = vec![];
+    for file in std::fs::read_dir(dir)? {
+        let path = file?.path();
+        let Some(name) = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        if name.ends_with(".ics") {
+            files.push((name, std::fs::read_to_string(&path).unwrap_or_default()));
+        }
+    }
+    Ok(files)
+}
+
+fn start_date(text: &str) -> Option<NaiveDate> {
+    let start = text
+        .lines()
+        .find_map(|line| line.strip_prefix("DTSTART:"))?;
+    let start = NaiveDateTime::parse_from_str(start.trim(), "%Y%m%dT%H%M%SZ").ok()?;
+    Some(start.and_utc().with_timezone(&Local).date_naive())
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub written: usize,
+    pub removed: usize,
+}
+
+// Declined events are left out; those nextmeet wrote starting within [from, to] that are no
+// longer fetched are removed. A directory holding anyone else's events is refused outright
+pub fn mirror(
+    dir: &Path,
+    meetings: &[Meeting],
+    from: NaiveDate,
+    to: NaiveDate,
+    now: DateTime<Local>,
+) -> Result<Changes, Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    let existing = calendar_files(dir)?;
+    if let Some((name, _)) = existing.iter().find(|(_, text)| !written_by_nextmeet(text)) {
+        return Err(format!(
+            "{} holds {} from elsewhere, use a directory of nextmeet's own",
+            dir.display(),
+            name
+        )
+        .into());
+    }
+
+    let mut changes = Changes::default();
+    let mut kept = HashSet::new();
+
+    for meeting in meetings {
+        if meeting.response_status() == Some("declined") {
+            continue;
+        }
+        let (Some(id), Some(event)) = (meeting.id(), ics_event(meeting, now)) else {
+            continue;
+        };
+        let name = file_name(id);
+        let path = dir.join(&name);
+        kept.insert(name);
+        if std::fs::read_to_string(&path).is_ok_and(|old| same_event(&old, &event)) {
+            continue;
+        }
+        // renamed into place so readers never see half a file
+        let temporary = dir.join(format!(".{}.tmp", file_name(id)));
+        std::fs::write(&temporary, event)?;
+        std::fs::rename(&temporary, &path)?;
+        changes.written += 1;
+    }
+
+    for (name, text) in existing {
+        if kept.contains(&name) {
+            continue;
+        }
+        if start_date(&text).is_some_and(|date| from <= date && date <= to) {
+            std::fs::remove_file(dir.join(name))?;
+            changes.removed += 1;
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(id: &str, start: &str, end: &str) -> Meeting {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "iCalUID": "series@google.com",
+            "summary": id,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn mirrors_events() {
+        let dir = std::env::temp_dir().join(format!("nextmeet-vdir-{}", std::process::id()));
+        let day: NaiveDate = "2023-03-01".parse().unwrap();
+        let now = Local::now();
+        let standup = meeting(
+            "standup_20230301",
+            "2023-03-01T10:00:00+01:00",
+            "2023-03-01T10:15:00+01:00",
+        );
+        let retro = meeting(
+            "retro",
+            "2023-03-01T15:00:00+01:00",
+            "2023-03-01T16:00:00+01:00",
+        );
+        std::fs::create_dir_all(&dir).unwrap();
+        let other = dir.join("other.ics");
+        std::fs::write(&other, "BEGIN:VCALENDAR\r\nEND:VCALENDAR").unwrap();
+        assert!(mirror(&dir, &[], day, day, now).is_err());
+        assert!(other.exists());
+        std::fs::remove_file(&other).unwrap();
+
+        let changes = mirror(&dir, &[standup.clone(), retro], day, day, now).unwrap();
+        assert_eq!(
+            changes,
+            Changes {
+                written: 2,
+                removed: 0
+            }
+        );
+        let text = std::fs::read_to_string(dir.join("standup_20230301.ics")).unwrap();
+        assert!(text.contains("\r\nUID:standup_20230301\r\n"));
+        assert!(written_by_nextmeet(&text));
+        assert_eq!(start_date(&text), Some(day));
+
+        let later = now + chrono::Duration::minutes(5);
+        let changes = mirror(&dir, &[standup], day, day, later).unwrap();
+        assert_eq!(
+            changes,
+            Changes {
+                written: 0,
+                removed: 1
+            }
+        );
+        assert!(!dir.join("retro.ics").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
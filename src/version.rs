@@ -0,0 +1,77 @@
+use super::settings;
+use super::store;
+use super::tokens;
+
+// Compiled in by build.rs
+const COMMIT: &str = env!("NEXTMEET_COMMIT");
+const BUILD_DATE: &str = env!("NEXTMEET_BUILD_DATE");
+
+// Optional parts of nextmeet this binary was built with
+pub fn features() -> Vec<&'static str> {
+    [
+        ("desktop", cfg!(feature = "desktop")),
+        ("mqtt", cfg!(feature = "mqtt")),
+        ("tui", cfg!(feature = "tui")),
+        ("email", cfg!(feature = "email")),
+        ("ffi", cfg!(feature = "ffi")),
+        ("grpc", cfg!(feature = "grpc")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+fn file(path: String) -> String {
+    match std::path::Path::new(&path).exists() {
+        true => path,
+        false => format!("{path} (missing)"),
+    }
+}
+
+// What goes in a bug report: `nextmeet version --verbose`
+pub fn render(verbose: bool) -> String {
+    let version = format!("nextmeet {}", env!("CARGO_PKG_VERSION"));
+    if !verbose {
+        return version;
+    }
+
+    let features = features();
+    let mut lines = vec![
+        version,
+        format!("commit: {COMMIT}"),
+        format!("built: {BUILD_DATE}"),
+        format!(
+            "features: {}",
+            if features.is_empty() {
+                "none".to_string()
+            } else {
+                features.join(", ")
+            }
+        ),
+        format!("settings: {}", file(settings::settings_path())),
+        format!("tokens: {}", file(tokens::config_path())),
+        format!("store: {}", file(store::path())),
+    ];
+    #[cfg(unix)]
+    lines.push(format!("socket: {}", super::socket::socket_path()));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_build() {
+        assert_eq!(
+            render(false),
+            format!("nextmeet {}", env!("CARGO_PKG_VERSION"))
+        );
+
+        let verbose = render(true);
+        assert!(verbose.starts_with(&render(false)));
+        assert!(verbose.contains("\ncommit: "));
+        assert!(verbose.contains("\nfeatures: "));
+        assert!(verbose.contains("\ntokens: "));
+    }
+}
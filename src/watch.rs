@@ -0,0 +1,692 @@
+use super::calendar;
+use super::calendar::Rsvp;
+#[cfg(feature = "desktop")]
+use super::desktop;
+use super::diff;
+#[cfg(unix)]
+use super::discord;
+#[cfg(feature = "email")]
+use super::email;
+use super::hooks;
+use super::meetings;
+use super::meetings::Meeting;
+#[cfg(feature = "mqtt")]
+use super::mqtt;
+use super::notefile;
+use super::notifier;
+use super::policy;
+use super::push;
+use super::server;
+use super::settings::settings_path;
+use super::settings::Policy;
+use super::settings::Settings;
+#[cfg(unix)]
+use super::socket;
+use super::store;
+#[cfg(unix)]
+use super::systemd;
+use super::tokens::Tokens;
+use super::vdir;
+use super::webhooks;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use notify::RecursiveMode;
+use notify::Watcher;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Before(Duration),
+    Started,
+    Ended,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Before(_) => "before",
+            Event::Started => "started",
+            Event::Ended => "ended",
+        }
+    }
+}
+
+const TICK_SECONDS: u64 = 15;
+const BACKOFF_SECONDS: i64 = 30;
+// Push messages can get lost, so the agenda is still re-read now and then
+const PUSH_FALLBACK_HOURS: i64 = 1;
+const PUSH_RENEW_MINUTES: i64 = 10;
+// Close to a meeting changes matter more, so the agenda is re-read more often
+const NEAR_MINUTES: i64 = 10;
+const NEAR_POLL_SECONDS: i64 = 30;
+
+// The configured interval while idle, never sleeping past the point where a meeting
+// is NEAR_MINUTES away, and every NEAR_POLL_SECONDS from then until it starts
+pub fn poll_interval(meetings: &[Meeting], now: DateTime<Local>, interval: Duration) -> Duration {
+    let near_poll = Duration::seconds(NEAR_POLL_SECONDS);
+    meetings
+        .iter()
+        .filter_map(|meeting| meeting.start().ok())
+        .filter(|start| *start > now)
+        .map(|start| (start - Duration::minutes(NEAR_MINUTES) - now).max(near_poll))
+        .fold(interval, Duration::min)
+}
+
+// Successful refreshes are spread over [interval, interval + jitter) so that several
+// instances don't hit the API in lockstep; failures back off exponentially instead
+pub fn refresh_delay(
+    interval: Duration,
+    jitter: Duration,
+    max_backoff: Duration,
+    failures: u32,
+    seed: u32,
+) -> Duration {
+    if failures == 0 {
+        let jitter_ms = jitter.num_milliseconds().max(0);
+        let spread = if jitter_ms > 0 {
+            seed as i64 % jitter_ms
+        } else {
+            0
+        };
+        return interval + Duration::milliseconds(spread);
+    }
+
+    let backoff = Duration::seconds(BACKOFF_SECONDS << (failures - 1).min(16));
+    backoff.min(max_backoff)
+}
+
+// Every boundary that falls in (since, now] fires exactly once
+pub fn transitions<'a>(
+    meetings: &'a [Meeting],
+    since: DateTime<Local>,
+    now: DateTime<Local>,
+    before: &[Duration],
+) -> Vec<(Event, &'a Meeting)> {
+    let crossed = |time: DateTime<Local>| since < time && time <= now;
+    let mut events = vec![];
+
+    for meeting in meetings {
+        if let Ok(start) = meeting.start() {
+            for offset in before {
+                if crossed(start - *offset) {
+                    events.push((Event::Before(*offset), meeting));
+                }
+            }
+            if crossed(start) {
+                events.push((Event::Started, meeting));
+            }
+        }
+        if meeting.end().map(crossed).unwrap_or(false) {
+            events.push((Event::Ended, meeting));
+        }
+    }
+
+    events
+}
+
+// What the loop derives from the settings, rebuilt whenever the config file changes
+struct Config {
+    before: Vec<Duration>,
+    interval: Duration,
+    jitter: Duration,
+    max_backoff: Duration,
+    alert_after: Duration,
+    notifiers: Vec<Box<dyn notifier::Notifier>>,
+    #[cfg(feature = "email")]
+    email_at: Option<chrono::NaiveTime>,
+}
+
+impl Config {
+    fn new(settings: &Settings) -> Result<Config, Box<dyn Error>> {
+        Ok(Config {
+            before: settings.reminder_offsets()?,
+            interval: settings.polling.interval()?,
+            jitter: settings.polling.jitter()?,
+            max_backoff: settings.polling.max_backoff()?,
+            alert_after: settings.polling.alert_after()?,
+            notifiers: settings
+                .notifiers
+                .iter()
+                .map(notifier::from_settings)
+                .collect::<Result<_, _>>()?,
+            #[cfg(feature = "email")]
+            email_at: settings
+                .email
+                .as_ref()
+                .map(|email| email.at())
+                .transpose()?,
+        })
+    }
+}
+
+// Connections made at startup are not redone on reload; listen is left out entirely
+// since --listen may override it
+const RESTART_ONLY: [&str; 3] = ["push", "discord", "mqtt"];
+
+pub fn changes(old: &Settings, new: &Settings) -> Vec<&'static str> {
+    [
+        ("calendars", old.calendars != new.calendars),
+        (
+            "reminders",
+            old.reminder_offsets() != new.reminder_offsets(),
+        ),
+        ("hooks", old.hooks != new.hooks),
+        ("webhooks", old.webhooks != new.webhooks),
+        ("notifiers", old.notifiers != new.notifiers),
+        ("polling", old.polling != new.polling),
+        ("notes_file", old.notes_file != new.notes_file),
+        ("vdir", old.vdir != new.vdir),
+        ("working_hours", old.working_hours != new.working_hours),
+        ("policies", old.policies != new.policies),
+        ("push", old.push != new.push),
+        ("discord", old.discord != new.discord),
+        ("mqtt", old.mqtt != new.mqtt),
+        ("email", old.email != new.email),
+    ]
+    .into_iter()
+    .filter(|(_, changed)| *changed)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+// Editors often replace the file rather than write it, so the directory is watched
+fn watch_settings() -> notify::Result<(notify::RecommendedWatcher, mpsc::Receiver<()>)> {
+    let path = PathBuf::from(settings_path());
+    let name = path.file_name().map(|name| name.to_owned());
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if event
+                .paths
+                .iter()
+                .any(|path| path.file_name() == name.as_deref())
+            {
+                let _ = sender.send(());
+            }
+        }
+    })?;
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    watcher.watch(dir.unwrap_or(Path::new(".")), RecursiveMode::NonRecursive)?;
+    Ok((watcher, receiver))
+}
+
+// Each meeting is declined at most once per run, even if the RSVP did not go through;
+// without write access policies are skipped, with a single warning
+async fn apply_policies(
+    policies: &[Policy],
+    meetings: &[Meeting],
+    declined: &mut HashSet<String>,
+    warned: &mut bool,
+) {
+    if policies.is_empty() {
+        return;
+    }
+    if !Tokens::load().is_ok_and(|tokens| tokens.writable) {
+        if !*warned {
+            eprintln!(
+                "Warning: policies need permission to change your calendar, run e.g. rsvp once"
+            );
+            *warned = true;
+        }
+        return;
+    }
+    for decision in policy::evaluate(policies, meetings) {
+        let Some(id) = decision.meeting.id() else {
+            continue;
+        };
+        if !declined.insert(id.to_string()) {
+            continue;
+        }
+        let result = match policy::comment(&decision) {
+            Ok(comment) => calendar::rsvp(id, Rsvp::Decline, Some(&comment)).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(_) => eprintln!(
+                "Declined {} ({})",
+                decision.meeting.summary(),
+                decision.policy.name
+            ),
+            Err(err) => eprintln!("Error: policy {}: {}", decision.policy.name, err),
+        }
+    }
+}
+
+// Only today is fetched, so a meeting gone from it may have moved to another day instead
+async fn moved_elsewhere(meeting: &Meeting) -> Option<Meeting> {
+    let event = calendar::event_unattended(meeting.id()?).await.ok()?;
+    if event["status"] == "cancelled" {
+        return None;
+    }
+    serde_json::from_value(event).ok()
+}
+
+fn alert(message: &str) {
+    eprintln!("Error: {}", message);
+    #[cfg(feature = "desktop")]
+    if let Err(err) = desktop::alert("nextmeet", message) {
+        eprintln!("Error: desktop notification: {}", err);
+    }
+}
+
+pub async fn run(mut settings: Settings) -> Result<(), Box<dyn Error>> {
+    calendar::unattended();
+    let mut config = Config::new(&settings)?;
+    let reload = watch_settings()
+        .map_err(|err| eprintln!("Warning: config changes need a restart: {}", err))
+        .ok();
+
+    let shared = server::Shared::default();
+    if let Some(listen) = &settings.listen {
+        server::spawn(listen, shared.clone())?;
+    } else if settings.push.is_some() {
+        return Err("Push notifications need the server, set listen too".into());
+    }
+    if let Some(push) = &settings.push {
+        shared.lock().unwrap().push_token = push.token.clone();
+    }
+    let mut channels: Vec<push::Channel> = vec![];
+    // the calendars changed, so the channels watch the wrong ones
+    let mut resubscribe = false;
+    #[cfg(unix)]
+    socket::spawn(shared.clone())?;
+
+    #[cfg(unix)]
+    systemd::notify("READY=1");
+    #[cfg(unix)]
+    let watchdog = systemd::watchdog_enabled();
+
+    let mut declined = HashSet::new();
+    let mut policies_warned = false;
+    let started = Local::now();
+    let mut alerted = false;
+    let mut since = Local::now();
+    let mut meetings: Vec<Meeting> = vec![];
+    // everything fetched last time, declined meetings included, so declining isn't a cancellation
+    let mut fetched_before: Vec<Meeting> = vec![];
+    // the day fetched_before is for, only that day's agenda goes out by email
+    #[cfg(feature = "email")]
+    let mut fetched_on = None;
+    #[cfg(feature = "email")]
+    let mut email_pending = None;
+    let mut refresh_at = Local::now();
+    let mut failures = 0;
+    let mut in_meeting: Option<Meeting> = None;
+    #[cfg(unix)]
+    let mut presence = settings
+        .discord
+        .as_ref()
+        .map(|discord| discord::Presence::new(&discord.client_id));
+
+    #[cfg(feature = "mqtt")]
+    let publisher = settings.mqtt.as_ref().map(mqtt::Publisher::connect);
+    #[cfg(feature = "mqtt")]
+    let mut busy_state = None;
+    #[cfg(not(feature = "mqtt"))]
+    if settings.mqtt.is_some() {
+        return Err("MQTT needs the mqtt feature".into());
+    }
+    #[cfg(not(feature = "email"))]
+    if settings.email.is_some() {
+        return Err("The agenda email needs the email feature".into());
+    }
+
+    loop {
+        let now = Local::now();
+
+        if reload
+            .as_ref()
+            .is_some_and(|(_, changed)| changed.try_iter().count() > 0)
+        {
+            match Settings::load().and_then(|new| Ok((Config::new(&new)?, new))) {
+                Ok((new_config, mut new)) => {
+                    let changed = changes(&settings, &new);
+                    if !changed.is_empty() {
+                        eprintln!("Config reloaded, changed: {}", changed.join(", "));
+                    }
+                    let restart: Vec<_> = changed
+                        .iter()
+                        .filter(|name| RESTART_ONLY.contains(name))
+                        .copied()
+                        .collect();
+                    if !restart.is_empty() {
+                        eprintln!("Warning: restart to apply {}", restart.join(", "));
+                    }
+                    if changed.contains(&"calendars") {
+                        refresh_at = now;
+                        resubscribe = true;
+                    }
+                    new.listen = settings.listen.take();
+                    new.push = settings.push.take();
+                    settings = new;
+                    config = new_config;
+                }
+                Err(err) => eprintln!("Error: config not reloaded: {}", err),
+            }
+        }
+
+        if let Some(push) = &settings.push {
+            // without channels registration is retried on the polling schedule
+            let expiring = if channels.is_empty() || resubscribe {
+                now >= refresh_at
+            } else {
+                channels.iter().any(|channel| {
+                    channel
+                        .expiration
+                        .is_some_and(|at| at - now < Duration::minutes(PUSH_RENEW_MINUTES))
+                })
+            };
+            if expiring {
+                match server::tokens(&shared).await {
+                    Ok(tokens) => {
+                        match push::watch_all(&tokens, push, &settings.calendars()).await {
+                            Ok(renewed) => {
+                                resubscribe = false;
+                                for old in std::mem::replace(&mut channels, renewed) {
+                                    if let Err(err) = push::stop(&tokens, &old).await {
+                                        eprintln!("Error: push channel: {}", err);
+                                    }
+                                }
+                            }
+                            Err(err) => eprintln!("Error: push channel: {}", err),
+                        }
+                    }
+                    Err(err) => eprintln!("Error: {}", err),
+                }
+            }
+        }
+
+        let pushed = failures == 0 && shared.lock().unwrap().pushed;
+        let new_day = since.date_naive() != now.date_naive();
+        if now >= refresh_at || pushed || new_day {
+            // cleared before fetching, so a push arriving meanwhile isn't lost
+            shared.lock().unwrap().pushed = false;
+            let today = now.date_naive();
+            match meetings::retrieve_range_unattended(today, today).await {
+                Ok(fetched) => {
+                    if let Some(change) = diff::next_change(&fetched_before, &fetched, now) {
+                        let elsewhere = match change {
+                            diff::Change::Cancelled(old) => moved_elsewhere(old).await,
+                            _ => None,
+                        };
+                        let change = match (change, &elsewhere) {
+                            (diff::Change::Cancelled(old), Some(new)) => {
+                                diff::Change::Moved(old, new)
+                            }
+                            (change, _) => change,
+                        };
+                        let change = [change];
+                        eprintln!("Next meeting changed: {}", diff::render(&change));
+                        notifier::notify_change(&config.notifiers, &change[0]).await;
+                    }
+                    apply_policies(
+                        &settings.policies,
+                        &fetched,
+                        &mut declined,
+                        &mut policies_warned,
+                    )
+                    .await;
+                    if let Some(dir) = &settings.vdir {
+                        let dir = notefile::expand(dir);
+                        if let Err(err) = vdir::mirror(&dir, &fetched, today, today, now) {
+                            eprintln!("Error: vdir: {}", err);
+                        }
+                    }
+                    fetched_before = fetched.clone();
+                    #[cfg(feature = "email")]
+                    {
+                        fetched_on = Some(today);
+                    }
+                    meetings = meetings::agenda(fetched);
+                    failures = 0;
+                    alerted = false;
+
+                    shared.lock().unwrap().update_today(meetings.clone(), now);
+                }
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    failures += 1;
+                    shared.lock().unwrap().failed();
+                    let last_sync = shared.lock().unwrap().refreshed_at.unwrap_or(started);
+                    if !alerted && now - last_sync > config.alert_after {
+                        alerted = true;
+                        alert(&format!(
+                            "Calendar sync has been failing since {}: {}",
+                            last_sync.format("%H:%M"),
+                            err
+                        ));
+                    }
+                }
+            }
+            refresh_at = if !channels.is_empty() && failures == 0 {
+                now + Duration::hours(PUSH_FALLBACK_HOURS)
+            } else {
+                let interval = poll_interval(&meetings, now, config.interval);
+                now + refresh_delay(
+                    interval,
+                    config.jitter.min(interval / 6),
+                    config.max_backoff,
+                    failures,
+                    now.timestamp_subsec_nanos(),
+                )
+            };
+        }
+
+        for (event, meeting) in transitions(&meetings, since, now, &config.before) {
+            hooks::run(&settings.hooks, event, meeting);
+            webhooks::fire(&settings.webhooks, event, meeting).await;
+            notifier::notify_all(&config.notifiers, event, meeting).await;
+            if !matches!(event, Event::Before(_)) {
+                store::record(meeting, event == Event::Ended);
+            }
+            if let Some(notes_file) = settings.notes_file.as_ref().filter(|n| n.on_start) {
+                if event == Event::Started {
+                    match notefile::create(notes_file, meeting) {
+                        Ok(path) => eprintln!("Notes: {}", path.display()),
+                        Err(err) => eprintln!("Error: notes file: {}", err),
+                    }
+                }
+            }
+        }
+
+        // the whole day goes in, meetings without a link are part of the digest; while today's
+        // agenda hasn't been fetched yet the email waits for a later tick
+        #[cfg(feature = "email")]
+        if let (Some(email), Some(at)) = (&settings.email, config.email_at) {
+            if email::due(at, since, now) {
+                email_pending = Some(now.date_naive());
+            }
+            if email_pending.is_some_and(|day| day != now.date_naive()) {
+                email_pending = None;
+            }
+            if email_pending.is_some() && fetched_on == email_pending {
+                email_pending = None;
+                let sent = match email::message(email, &fetched_before, now) {
+                    Ok(message) => email::send(email, message).await,
+                    Err(err) => Err(err),
+                };
+                match sent {
+                    Ok(_) => eprintln!("Sent the agenda to {}", email.to.join(", ")),
+                    Err(err) => eprintln!("Error: email: {}", err),
+                }
+            }
+        }
+
+        let current = meetings::ongoing_meeting(&meetings, now);
+        if current.map(|m| (m.id(), m.start().ok()))
+            != in_meeting.as_ref().map(|m| (m.id(), m.start().ok()))
+        {
+            #[cfg(unix)]
+            if let Some(presence) = presence.as_mut() {
+                let result = match current {
+                    Some(meeting) => presence.set_meeting(meeting),
+                    None => presence.clear(),
+                };
+                if let Err(err) = result {
+                    eprintln!("Error: Discord presence: {}", err);
+                }
+            }
+            in_meeting = current.cloned();
+        }
+
+        #[cfg(feature = "mqtt")]
+        if let Some(publisher) = publisher.as_ref() {
+            let state = mqtt::busy_state(&meetings, now);
+            if busy_state.as_ref() != Some(&state) {
+                match publisher.publish(&state).await {
+                    Ok(_) => busy_state = Some(state),
+                    Err(err) => eprintln!("Error: MQTT: {}", err),
+                }
+            }
+        }
+
+        #[cfg(unix)]
+        if watchdog {
+            systemd::notify("WATCHDOG=1");
+        }
+
+        since = now;
+        tokio::time::sleep(std::time::Duration::from_secs(TICK_SECONDS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting() -> Meeting {
+        serde_json::from_str(
+            r#"{
+                "summary": "Standup",
+                "start": { "dateTime": "2023-03-01T10:00:00+01:00" },
+                "end": { "dateTime": "2023-03-01T10:15:00+01:00" }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn at(date_time: &str) -> DateTime<Local> {
+        date_time.parse().unwrap()
+    }
+
+    #[test]
+    fn jitters_refreshes() {
+        let delay = refresh_delay(
+            Duration::minutes(5),
+            Duration::seconds(30),
+            Duration::minutes(30),
+            0,
+            12_345,
+        );
+        assert_eq!(delay, Duration::minutes(5) + Duration::milliseconds(12_345));
+
+        let delay = refresh_delay(
+            Duration::minutes(5),
+            Duration::zero(),
+            Duration::minutes(30),
+            0,
+            12_345,
+        );
+        assert_eq!(delay, Duration::minutes(5));
+    }
+
+    #[test]
+    fn backs_off_on_failures() {
+        let delay = |failures| {
+            refresh_delay(
+                Duration::minutes(5),
+                Duration::seconds(30),
+                Duration::minutes(10),
+                failures,
+                0,
+            )
+        };
+
+        assert_eq!(delay(1), Duration::seconds(30));
+        assert_eq!(delay(2), Duration::minutes(1));
+        assert_eq!(delay(3), Duration::minutes(2));
+        assert_eq!(delay(10), Duration::minutes(10));
+        assert_eq!(delay(100), Duration::minutes(10));
+    }
+
+    #[test]
+    fn polls_faster_near_meetings() {
+        let meetings = [meeting()];
+        let interval = Duration::minutes(5);
+
+        let idle = poll_interval(&meetings, at("2023-03-01T09:00:00+01:00"), interval);
+        assert_eq!(idle, interval);
+
+        let approaching = poll_interval(&meetings, at("2023-03-01T09:48:00+01:00"), interval);
+        assert_eq!(approaching, Duration::minutes(2));
+
+        let near = poll_interval(&meetings, at("2023-03-01T09:55:00+01:00"), interval);
+        assert_eq!(near, Duration::seconds(30));
+
+        let started = poll_interval(&meetings, at("2023-03-01T10:05:00+01:00"), interval);
+        assert_eq!(started, interval);
+    }
+
+    #[test]
+    fn lists_changed_settings() {
+        let old: Settings = serde_json::from_str(r#"{ "hooks": { "before": ["5m"] } }"#).unwrap();
+        let new: Settings = serde_json::from_str(
+            r#"{ "hooks": { "before": ["10m"] }, "calendars": ["team@example.org"] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(changes(&old, &old.clone()), Vec::<&str>::new());
+        assert_eq!(changes(&old, &new), vec!["calendars", "reminders", "hooks"]);
+    }
+
+    #[test]
+    fn fires_start_once() {
+        let meetings = [meeting()];
+
+        let events = transitions(
+            &meetings,
+            at("2023-03-01T09:59:50+01:00"),
+            at("2023-03-01T10:00:00+01:00"),
+            &[],
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, Event::Started);
+
+        let events = transitions(
+            &meetings,
+            at("2023-03-01T10:00:00+01:00"),
+            at("2023-03-01T10:00:10+01:00"),
+            &[],
+        );
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn fires_before_and_end() {
+        let meetings = [meeting()];
+
+        let events = transitions(
+            &meetings,
+            at("2023-03-01T09:54:50+01:00"),
+            at("2023-03-01T09:55:05+01:00"),
+            &[Duration::minutes(5), Duration::minutes(1)],
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, Event::Before(Duration::minutes(5)));
+
+        let events = transitions(
+            &meetings,
+            at("2023-03-01T10:14:50+01:00"),
+            at("2023-03-01T10:15:05+01:00"),
+            &[],
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, Event::Ended);
+    }
+}
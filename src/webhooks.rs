@@ -0,0 +1,121 @@
+use super::duration;
+use super::meetings::Meeting;
+use super::settings::Webhook;
+use super::template::meeting_value;
+use super::watch::Event;
+use std::error::Error;
+
+fn wants(webhook: &Webhook, event: Event) -> bool {
+    let subscribed =
+        webhook.events.is_empty() || webhook.events.iter().any(|name| name == event.name());
+
+    match event {
+        Event::Before(offset) => {
+            subscribed
+                && webhook
+                    .before
+                    .iter()
+                    .any(|before| duration::parse(before) == Ok(offset))
+        }
+        _ => subscribed,
+    }
+}
+
+fn payload(webhook: &Webhook, event: Event, meeting: &Meeting) -> Result<String, Box<dyn Error>> {
+    let minutes_before = match event {
+        Event::Before(offset) => Some(offset.num_minutes()),
+        _ => None,
+    };
+
+    match &webhook.payload {
+        None => Ok(serde_json::json!({
+            "event": event.name(),
+            "minutes_before": minutes_before,
+            "meeting": meeting_value(meeting),
+        })
+        .to_string()),
+        Some(template) => {
+            let mut context = tera::Context::new();
+            context.insert("event", event.name());
+            context.insert("minutes_before", &minutes_before);
+            context.insert("meeting", &meeting_value(meeting));
+            Ok(tera::Tera::one_off(template, &context, false)?)
+        }
+    }
+}
+
+pub async fn fire(webhooks: &[Webhook], event: Event, meeting: &Meeting) {
+    let client = crate::http::client();
+
+    for webhook in webhooks.iter().filter(|webhook| wants(webhook, event)) {
+        let result = match payload(webhook, event, meeting) {
+            Ok(body) => client
+                .post(&webhook.url)
+                .header("Content-Type", "application/json")
+                .body(body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map(|_| ())
+                .map_err(Into::into),
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            eprintln!("Error: webhook {}: {}", webhook.url, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn webhook(events: &[&str], before: &[&str], payload: Option<&str>) -> Webhook {
+        Webhook {
+            url: "http://localhost/hook".to_string(),
+            events: events.iter().map(|e| e.to_string()).collect(),
+            before: before.iter().map(|b| b.to_string()).collect(),
+            payload: payload.map(String::from),
+        }
+    }
+
+    fn meeting() -> Meeting {
+        serde_json::from_str(r#"{ "summary": "Standup" }"#).unwrap()
+    }
+
+    #[test]
+    fn filters_events() {
+        let all = webhook(&[], &["5m"], None);
+        assert!(wants(&all, Event::Started));
+        assert!(wants(&all, Event::Before(Duration::minutes(5))));
+        assert!(!wants(&all, Event::Before(Duration::minutes(1))));
+
+        let started = webhook(&["started"], &[], None);
+        assert!(wants(&started, Event::Started));
+        assert!(!wants(&started, Event::Ended));
+    }
+
+    #[test]
+    fn renders_payload_template() {
+        let hook = webhook(
+            &[],
+            &["5m"],
+            Some(r#"{"text": "{{ meeting.summary }} in {{ minutes_before }} minutes"}"#),
+        );
+
+        let body = payload(&hook, Event::Before(Duration::minutes(5)), &meeting()).unwrap();
+        assert_eq!(body, r#"{"text": "Standup in 5 minutes"}"#);
+    }
+
+    #[test]
+    fn default_payload() {
+        let hook = webhook(&[], &[], None);
+
+        let body = payload(&hook, Event::Ended, &meeting()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["event"], "ended");
+        assert_eq!(json["meeting"]["summary"], "Standup");
+    }
+}